@@ -1,6 +1,14 @@
-use crate::scanner::Token;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
 
-#[derive(Clone)]
+use crate::class::{LoxClass, LoxInstance};
+use crate::error::RuntimeError;
+use crate::function::{ LoxFunction, NativeFunction };
+use crate::scanner::{ Token, TokenType };
+
+// comparing trees compares tokens by type and lexeme only, so where the code was written doesn't matter
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Assign {
         name: Token,
@@ -11,18 +19,44 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
+    // runs its statements in a fresh scope, then yields the final expression
+    Block {
+        statements: Vec<Stmt>,
+        final_expr: Box<Expr>,
+    },
     Call {
         callee: Box<Expr>,
         paren: Token,
         arguments: Vec<Expr>,
+        // `name: value` arguments, which the parser only accepts after the positional ones
+        keywords: Vec<(Token, Expr)>,
+    },
+    // evaluates every operand in order and yields the last
+    Comma {
+        exprs: Vec<Expr>,
     },
     Get {
         object: Box<Expr>,
         name: Token,
     },
+    // `object?.name`, which is nil when the object is, so `a?.b?.c` stops at the first nil
+    GetOptional {
+        object: Box<Expr>,
+        name: Token,
+    },
     Grouping {
         expression: Box<Expr>,
     },
+    // `object[index]` on a list, tuple or byte string
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    List {
+        bracket: Token,
+        elements: Vec<Expr>,
+    },
     Literal {
         value: Value,
     },
@@ -43,10 +77,27 @@ pub enum Expr {
     This {
         keyword: Token,
     },
+    // written `(a, b)`; a single element needs a trailing comma, since `(a)` is a grouping
+    Tuple {
+        paren: Token,
+        elements: Vec<Expr>,
+    },
     Unary {
         operator: Token,
         right: Box<Expr>,
     },
+    // `(a, b) = value`, assigning each variable its element of a tuple and yielding the tuple
+    Unpack {
+        paren: Token,
+        names: Vec<Token>,
+        value: Box<Expr>,
+    },
+    // `++`/`--` on a variable, yielding the new value when prefix and the old one when postfix
+    Update {
+        name: Token,
+        operator: Token,
+        prefix: bool,
+    },
     Variable {
         name: Token,
     },
@@ -54,11 +105,163 @@ pub enum Expr {
 #[derive(Clone)]
 pub enum Value {
     Number(f64),
+    // literals without a fractional part; mixing with a Number promotes to Number
+    Int(i64),
     String(String),
     Boolean(bool),
     Nil,
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<Vec<Value>>),
+    Tuple(Rc<Vec<Value>>),
+    Bytes(Rc<Vec<u8>>),
+}
+
+impl Value {
+    // ints are numbers too as far as scripts can tell, so `type()` has one name for both
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) | Value::Int(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Nil => "nil",
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Bytes(_) => "bytes",
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(num) => Some(*num),
+            Value::Int(int) => Some(*int as f64),
+            _ => None,
+        }
+    }
+
+    fn conversion_error(&self, target: &str) -> RuntimeError {
+        RuntimeError::without_token(&format!("Can't convert {} to {}.", self.type_name(), target))
+    }
+}
+
+// literals show as the variant holding them, and anything else as it prints
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(num) => f.debug_tuple("Number").field(num).finish(),
+            Value::Int(int) => f.debug_tuple("Int").field(int).finish(),
+            Value::String(string) => f.debug_tuple("String").field(string).finish(),
+            Value::Boolean(boolean) => f.debug_tuple("Boolean").field(boolean).finish(),
+            Value::Nil => write!(f, "Nil"),
+            Value::List(elements) => f.debug_tuple("List").field(elements).finish(),
+            Value::Tuple(elements) => f.debug_tuple("Tuple").field(elements).finish(),
+            _ => write!(f, "{}", self.print()),
+        }
+    }
+}
+
+// lox's `==`: an Int equals the Number it promotes to, so `1 == 1.0`, while two Ints compare exactly;
+// functions, classes and instances are equal only to themselves
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::String(lstr), Value::String(rstr)) => lstr == rstr,
+            (Value::Number(lnum), Value::Number(rnum)) => lnum == rnum,
+            (Value::Int(lint), Value::Int(rint)) => lint == rint,
+            (Value::Int(int), Value::Number(num)) | (Value::Number(num), Value::Int(int)) => *int as f64 == *num,
+            (Value::Boolean(lbool), Value::Boolean(rbool)) => lbool == rbool,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Function(lfun), Value::Function(rfun)) => Rc::ptr_eq(lfun, rfun),
+            (Value::NativeFunction(lfun), Value::NativeFunction(rfun)) => Rc::ptr_eq(lfun, rfun),
+            (Value::Class(lclass), Value::Class(rclass)) => Rc::ptr_eq(lclass, rclass),
+            (Value::Instance(linst), Value::Instance(rinst)) => Rc::ptr_eq(linst, rinst),
+            (Value::List(llist), Value::List(rlist)) => llist == rlist,
+            (Value::Tuple(ltuple), Value::Tuple(rtuple)) => ltuple == rtuple,
+            (Value::Bytes(lbytes), Value::Bytes(rbytes)) => lbytes == rbytes,
+            (_, _) => false,
+        }
+    }
+}
+
+// conversions for natives and embedders, failing with a RuntimeError so `?` works inside a native
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
 }
 
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+// an Int converts too, the same way arithmetic promotes it
+impl TryFrom<Value> for f64 {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.as_number().ok_or_else(|| value.conversion_error("f64"))
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int(int) => Ok(int),
+            _ => Err(value.conversion_error("i64")),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Boolean(boolean) => Ok(boolean),
+            _ => Err(value.conversion_error("bool")),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(string) => Ok(string),
+            _ => Err(value.conversion_error("String")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Block {
         statements: Vec<Stmt>,
@@ -67,44 +270,264 @@ pub enum Stmt {
         name: Token,
         superclass: Option<Expr>,
         methods: Vec<Stmt>,
+        static_methods: Vec<Stmt>,
+    },
+    // `label` names the loop to leave; without one the innermost loop is meant
+    Break {
+        keyword: Token,
+        label: Option<Token>,
+    },
+    Const {
+        name: Token,
+        initializer: Expr,
+    },
+    Continue {
+        keyword: Token,
+        label: Option<Token>,
+    },
+    DoWhile {
+        body: Box<Stmt>,
+        condition: Expr,
+        label: Option<Token>,
     },
     Expression {
         expression: Expr,
     },
+    ForEach {
+        var_name: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
+        label: Option<Token>,
+    },
     Function {
         name: Token,
         params: Vec<Token>,
-        body: Vec<Stmt>,
+        body: Rc<Vec<Stmt>>,
     },
     If {
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
     },
+    Match {
+        keyword: Token,
+        subject: Expr,
+        arms: Vec<(Pattern, Stmt)>,
+    },
     Print {
-        expression: Expr,
+        expressions: Vec<Expr>,
     },
     Return {
         keyword: Token,
         value: Option<Expr>,
     },
+    Switch {
+        subject: Expr,
+        cases: Vec<(Expr, Vec<Stmt>)>,
+        default: Option<Vec<Stmt>>,
+    },
+    Throw {
+        keyword: Token,
+        value: Expr,
+    },
+    // `name` is bound to the thrown value, or the caught error's message, inside `handler`
+    Try {
+        keyword: Token,
+        body: Vec<Stmt>,
+        name: Token,
+        handler: Vec<Stmt>,
+    },
     Var {
         name: Token,
         initializer: Option<Expr>,
     },
+    // `var (a, b) = value;`, declaring a variable for each element of a tuple
+    VarUnpack {
+        paren: Token,
+        names: Vec<Token>,
+        initializer: Expr,
+    },
+    // `increment` is kept apart from the body of a desugared for loop so `continue` still runs it
     While {
         condition: Expr,
         body: Box<Stmt>,
+        increment: Option<Expr>,
+        label: Option<Token>,
     },
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Literal(Value),
+    // `_`, which matches anything
+    Wildcard,
+}
+
+// a parsed program that can be run repeatedly without scanning or parsing again
+pub struct Program {
+    // shared, so a reparse can keep the statements an edit didn't touch without copying them
+    pub statements: Vec<Rc<Stmt>>,
+    // where each top-level statement sits in the source, empty for a program built without any
+    pub spans: Vec<Span>,
+}
+
+// the lines a statement's first and last tokens are on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start_line: u32,
+    pub end_line: u32,
+}
+
+// constructors for building a program without any source text, e.g. when lox is a compiler's target;
+// the tokens they make up all sit on line 1, so errors in such a program can't point anywhere useful
+impl Expr {
+    pub fn number(value: f64) -> Expr {
+        Expr::Literal { value: Value::Number(value) }
+    }
+
+    pub fn int(value: i64) -> Expr {
+        Expr::Literal { value: Value::Int(value) }
+    }
+
+    pub fn string(value: &str) -> Expr {
+        Expr::Literal { value: Value::String(value.to_string()) }
+    }
+
+    pub fn boolean(value: bool) -> Expr {
+        Expr::Literal { value: Value::Boolean(value) }
+    }
+
+    pub fn nil() -> Expr {
+        Expr::Literal { value: Value::Nil }
+    }
+
+    pub fn variable(name: &str) -> Expr {
+        Expr::Variable { name: identifier(name) }
+    }
+
+    pub fn assign(name: &str, value: Expr) -> Expr {
+        Expr::Assign { name: identifier(name), value: Box::new(value) }
+    }
+
+    pub fn binary(left: Expr, operator: TokenType, right: Expr) -> Expr {
+        Expr::Binary { left: Box::new(left), operator: synthesized(operator), right: Box::new(right) }
+    }
+
+    // `operator` is `And` or `Or`
+    pub fn logical(left: Expr, operator: TokenType, right: Expr) -> Expr {
+        Expr::Logical { left: Box::new(left), operator: synthesized(operator), right: Box::new(right) }
+    }
+
+    pub fn unary(operator: TokenType, right: Expr) -> Expr {
+        Expr::Unary { operator: synthesized(operator), right: Box::new(right) }
+    }
+
+    pub fn grouping(expression: Expr) -> Expr {
+        Expr::Grouping { expression: Box::new(expression) }
+    }
+
+    pub fn call(callee: Expr, arguments: Vec<Expr>) -> Expr {
+        Expr::Call { callee: Box::new(callee), paren: synthesized(TokenType::RightParen), arguments, keywords: Vec::new() }
+    }
+
+    pub fn list(elements: Vec<Expr>) -> Expr {
+        Expr::List { bracket: synthesized(TokenType::LeftBracket), elements }
+    }
+}
+
+impl Stmt {
+    pub fn expression(expression: Expr) -> Stmt {
+        Stmt::Expression { expression }
+    }
+
+    pub fn print(expressions: Vec<Expr>) -> Stmt {
+        Stmt::Print { expressions }
+    }
+
+    pub fn var(name: &str, initializer: Option<Expr>) -> Stmt {
+        Stmt::Var { name: identifier(name), initializer }
+    }
+
+    pub fn block(statements: Vec<Stmt>) -> Stmt {
+        Stmt::Block { statements }
+    }
+
+    pub fn if_else(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>) -> Stmt {
+        Stmt::If { condition, then_branch: Box::new(then_branch), else_branch: else_branch.map(Box::new) }
+    }
+
+    pub fn while_loop(condition: Expr, body: Stmt) -> Stmt {
+        Stmt::While { condition, body: Box::new(body), increment: None, label: None }
+    }
+}
+
+fn identifier(name: &str) -> Token {
+    Token::new(TokenType::Identifier, name.to_string(), 1, 1)
+}
+
+// the lexeme is how the token would have been written, so error messages still read naturally
+fn synthesized(token_type: TokenType) -> Token {
+    Token::new(token_type, token_type.to_string(), 1, 1)
+}
+
+// shortest digits that read back as the same number, switching to exponents only for extreme magnitudes
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return String::from("nan");
+    }
+    if n.is_infinite() {
+        return String::from(if n > 0.0 { "inf" } else { "-inf" });
+    }
+    let magnitude = n.abs();
+    if magnitude != 0.0 && !(1e-7..1e21).contains(&magnitude) {
+        return format!("{:e}", n);
+    }
+    n.to_string()
+}
+
+// as a `b"..."` literal, escaping anything that isn't printable ASCII
+pub fn format_bytes(bytes: &[u8]) -> String {
+    let mut formatted = String::from("b\"");
+    for byte in bytes {
+        match byte {
+            b'"' => formatted.push_str("\\\""),
+            b'\\' => formatted.push_str("\\\\"),
+            b'\n' => formatted.push_str("\\n"),
+            b'\t' => formatted.push_str("\\t"),
+            b'\r' => formatted.push_str("\\r"),
+            b'\0' => formatted.push_str("\\0"),
+            b' '..=b'~' => formatted.push(char::from(*byte)),
+            _ => formatted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    formatted.push('"');
+    formatted
+}
+
 impl AstPrinter for Value {
     fn print(&self) -> String {
         match self {
-            Value::Number(n) => n.to_string(),
+            Value::Number(n) => format_number(*n),
+            Value::Int(i) => i.to_string(),
             Value::String(s) => s.to_owned(),
             Value::Boolean(b) => b.to_string(),
-            Value::Nil => String::from("nil")
+            Value::Nil => String::from("nil"),
+            Value::Function(function) => format!("<fn {}>", function.name.lexeme),
+            Value::NativeFunction(_) => String::from("<native fn>"),
+            Value::Class(class) => class.name.clone(),
+            Value::Instance(instance) => format!("<instance of {}>", instance.borrow().class.name),
+            Value::List(elements) => {
+                let elements: Vec<String> = elements.iter().map(Value::print).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Value::Tuple(elements) => match elements.as_slice() {
+                [element] => format!("({},)", element.print()),
+                _ => {
+                    let elements: Vec<String> = elements.iter().map(Value::print).collect();
+                    format!("({})", elements.join(", "))
+                }
+            },
+            Value::Bytes(bytes) => format_bytes(bytes),
         }
     }
 }
@@ -112,11 +535,34 @@ impl AstPrinter for Value {
 impl AstPrinter for Expr {
     fn print(&self) -> String {
         match self {
-            Expr::Binary { left, operator, right } => self.parenthesize(&operator.lexeme, vec![left, right]),
+            Expr::Assign { name, value } => self.parenthesize(&format!("= {}", name.lexeme), vec![value]),
+            Expr::Binary { left, operator, right } | Expr::Logical { left, operator, right } => self.parenthesize(&operator.lexeme, vec![left, right]),
+            Expr::Block { statements, final_expr } => {
+                let parts = statements.iter().map(AstPrinter::print).chain(std::iter::once(final_expr.print())).collect();
+                self.parenthesize_parts("block", parts)
+            }
+            Expr::Call { callee, arguments, keywords, .. } => {
+                let keywords = keywords.iter().map(|(label, value)| format!("{}: {}", label.lexeme, value.print()));
+                let parts = std::iter::once(callee.print()).chain(arguments.iter().map(Expr::print)).chain(keywords).collect();
+                self.parenthesize_parts("call", parts)
+            }
+            Expr::Comma { exprs } => self.parenthesize(&",".to_string(), exprs.iter().collect()),
+            Expr::Get { object, name } => format!("(. {} {})", object.print(), name.lexeme),
+            Expr::GetOptional { object, name } => format!("(?. {} {})", object.print(), name.lexeme),
             Expr::Grouping { expression } => self.parenthesize(&"group".to_string(), vec![expression]),
+            Expr::Index { object, index, .. } => self.parenthesize(&"[]".to_string(), vec![object, index]),
+            Expr::List { elements, .. } => self.parenthesize(&"list".to_string(), elements.iter().collect()),
             Expr::Literal { value } => value.print(),
+            Expr::Set { object, name, value } => format!("(= (. {} {}) {})", object.print(), name.lexeme, value.print()),
+            Expr::Super { method, .. } => format!("(super {})", method.lexeme),
+            Expr::This { .. } => String::from("this"),
+            Expr::Tuple { elements, .. } => self.parenthesize(&"tuple".to_string(), elements.iter().collect()),
             Expr::Unary { operator, right } => self.parenthesize(&operator.lexeme, vec![right]),
-            _ => String::new()
+            Expr::Unpack { names, value, .. } => format!("(= {} {})", names_list(names), value.print()),
+            // postfix updates put the operator after the name, as written
+            Expr::Update { name, operator, prefix: true } => format!("({} {})", operator.lexeme, name.lexeme),
+            Expr::Update { name, operator, prefix: false } => format!("({} {})", name.lexeme, operator.lexeme),
+            Expr::Variable { name } => name.lexeme.clone(),
         }
     }
 }
@@ -124,8 +570,60 @@ impl AstPrinter for Expr {
 impl AstPrinter for Stmt {
     fn print(&self) -> String {
         match self {
+            Stmt::Block { statements } => self.parenthesize_parts("block", statements.iter().map(AstPrinter::print).collect()),
+            Stmt::Class { name, superclass, methods, static_methods } => {
+                let mut parts = vec![name.lexeme.clone()];
+                if let Some(superclass) = superclass {
+                    parts.push(format!("(< {})", superclass.print()));
+                }
+                parts.extend(methods.iter().map(AstPrinter::print));
+                parts.extend(static_methods.iter().map(|method| format!("(class {})", method.print())));
+                self.parenthesize_parts("class", parts)
+            }
+            Stmt::Break { label, .. } => self.parenthesize_parts("break", label.iter().map(|label| label.lexeme.clone()).collect()),
+            Stmt::Const { name, initializer } => self.parenthesize_parts("const", vec![name.lexeme.clone(), initializer.print()]),
+            Stmt::Continue { label, .. } => self.parenthesize_parts("continue", label.iter().map(|label| label.lexeme.clone()).collect()),
+            Stmt::DoWhile { body, condition, .. } => self.parenthesize_parts("do", vec![body.print(), condition.print()]),
             Stmt::Expression { expression } => expression.print(),
-            Stmt::Print { expression } => expression.print(),
+            Stmt::ForEach { var_name, iterable, body, .. } => {
+                self.parenthesize_parts("for", vec![var_name.lexeme.clone(), iterable.print(), body.print()])
+            }
+            Stmt::Function { name, params, body } => {
+                let parts = [name.lexeme.clone(), names_list(params)].into_iter().chain(body.iter().map(AstPrinter::print)).collect();
+                self.parenthesize_parts("fun", parts)
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                let parts = [condition.print(), then_branch.print()].into_iter().chain(else_branch.iter().map(|branch| branch.print())).collect();
+                self.parenthesize_parts("if", parts)
+            }
+            Stmt::Match { subject, arms, .. } => {
+                let arms = arms.iter().map(|(pattern, body)| {
+                    let pattern = match pattern {
+                        Pattern::Literal(value) => value.print(),
+                        Pattern::Wildcard => String::from("_"),
+                    };
+                    format!("({} {})", pattern, body.print())
+                });
+                self.parenthesize_parts("match", std::iter::once(subject.print()).chain(arms).collect())
+            }
+            Stmt::Print { expressions } => match expressions.as_slice() {
+                [expression] => expression.print(),
+                _ => self.parenthesize(&"print".to_string(), expressions.iter().collect()),
+            },
+            Stmt::Return { value, .. } => self.parenthesize(&"return".to_string(), value.iter().collect()),
+            Stmt::Switch { subject, cases, default } => {
+                let cases = cases.iter().map(|(value, body)| {
+                    self.parenthesize_parts("case", std::iter::once(value.print()).chain(body.iter().map(AstPrinter::print)).collect())
+                });
+                let default = default.iter().map(|body| self.parenthesize_parts("default", body.iter().map(AstPrinter::print).collect()));
+                self.parenthesize_parts("switch", std::iter::once(subject.print()).chain(cases).chain(default).collect())
+            }
+            Stmt::Throw { value, .. } => self.parenthesize(&"throw".to_string(), vec![value]),
+            Stmt::Try { body, name, handler, .. } => {
+                let body = self.parenthesize_parts("block", body.iter().map(AstPrinter::print).collect());
+                let handler = std::iter::once(name.lexeme.clone()).chain(handler.iter().map(AstPrinter::print)).collect();
+                self.parenthesize_parts("try", vec![body, self.parenthesize_parts("catch", handler)])
+            }
             Stmt::Var { name, initializer } => {
                 if let Some(expr) = initializer {
                     self.parenthesize(&"var".to_string(), vec![&Expr::Variable { name: name.clone() }, expr])
@@ -133,11 +631,27 @@ impl AstPrinter for Stmt {
                     self.parenthesize(&"var".to_string(), vec![&Expr::Variable { name: name.clone() }])
                 }
             }
-            _ => String::new()
+            Stmt::VarUnpack { names, initializer, .. } => format!("(var {} {})", names_list(names), initializer.print()),
+            Stmt::While { condition, body, increment, .. } => {
+                let parts = [condition.print(), body.print()].into_iter().chain(increment.iter().map(Expr::print)).collect();
+                self.parenthesize_parts("while", parts)
+            }
         }
     }
 }
 
+// parameter and destructuring names, as `(a b)`
+fn names_list(names: &[Token]) -> String {
+    let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+    format!("({})", names.join(" "))
+}
+
+// whether two programs are the same code, however they're laid out in their source;
+// an Int literal still equals the Number it promotes to, as `1 == 1.0` does in lox
+pub fn ast_eq_ignoring_spans(a: &[Stmt], b: &[Stmt]) -> bool {
+    a == b
+}
+
 pub trait AstPrinter {
     fn print(&self) -> String;
 
@@ -150,5 +664,16 @@ pub trait AstPrinter {
         builder.push(')');
         builder
     }
+
+    // for forms whose parts aren't all expressions, such as statements
+    fn parenthesize_parts(&self, name: &str, parts: Vec<String>) -> String {
+        let mut builder = format!("({}", name);
+        for part in parts {
+            builder.push(' ');
+            builder.push_str(&part);
+        }
+        builder.push(')');
+        builder
+    }
 }
 
@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::Value;
+use crate::function::{ Arity, LoxCallable, LoxFunction };
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::scanner::Token;
+
+pub struct LoxClass {
+    pub name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+    static_methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Rc<LoxFunction>>,
+        static_methods: HashMap<String, Rc<LoxFunction>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+            static_methods,
+        }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    // static methods live on the class object itself and are never bound to an instance
+    pub fn find_static_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.static_methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find_static_method(name)),
+        }
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match self.find_static_method(&name.lexeme) {
+            Some(method) => Ok(Value::Function(method)),
+            None => Err(RuntimeError::new(name, &format!("Undefined property '{}'.", name.lexeme))),
+        }
+    }
+}
+
+impl LoxCallable for Rc<LoxClass> {
+    fn arity(&self) -> Arity {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => Arity::Fixed(0),
+        }
+    }
+
+    fn params(&self) -> Option<Vec<Token>> {
+        match self.find_method("init") {
+            Some(initializer) => initializer.params(),
+            None => Some(Vec::new()),
+        }
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(self))));
+        if let Some(initializer) = self.find_method("init") {
+            initializer.bind(Rc::clone(&instance)).call(interpreter, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    fields: HashMap<String, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(&name.lexeme);
+        match method {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(Rc::clone(instance))))),
+            None => Err(RuntimeError::new(name, &format!("Undefined property '{}'.", name.lexeme))),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.set_field(&name.lexeme, value);
+    }
+
+    // for natives that build instances without any source tokens
+    pub fn set_field(&mut self, name: &str, value: Value) {
+        self.fields.insert(name.to_string(), value);
+    }
+}
@@ -0,0 +1,20 @@
+use std::fmt::Display;
+
+const BOLD_YELLOW: &str = "1;33";
+const BOLD_RED: &str = "1;31";
+
+fn paint(text: impl Display, code: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn line(line: u32, enabled: bool) -> String {
+    paint(line, BOLD_YELLOW, enabled)
+}
+
+pub fn lexeme(lexeme: &str, enabled: bool) -> String {
+    paint(lexeme, BOLD_RED, enabled)
+}
@@ -1,36 +1,69 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{ HashMap, HashSet };
+use std::rc::Rc;
 
-use crate::ast::{ Value, AstPrinter };
-use crate::scanner::{Token};
+use crate::ast::Value;
+use crate::error::RuntimeError;
+use crate::scanner::Token;
+use crate::symbol::Symbol;
 
 pub struct Environment {
-    values: HashMap<String, Value>,
+    values: HashMap<Symbol, Value>,
+    constants: HashSet<Symbol>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Self {
             values: HashMap::new(),
+            constants: HashSet::new(),
+            enclosing: None,
         }
     }
 
-    pub fn define(&mut self, name: String, value: Value) {
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // redeclaring a name with `var` makes it assignable again
+    pub fn define(&mut self, name: Symbol, value: Value) {
+        self.constants.remove(&name);
         self.values.insert(name, value);
     }
 
-    pub fn get(&self, name: &Token) -> Result<Value, String> {
-        match self.values.get(name.lexeme.as_str()) {
+    pub fn define_constant(&mut self, name: Symbol, value: Value) {
+        self.constants.insert(name);
+        self.values.insert(name, value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match self.values.get(&name.symbol()) {
             Some(val) => Ok(val.clone()),
-            None => Err(format!("Undefined variable '{}'.", name.lexeme.as_str()))
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow().get(name),
+                None => Err(RuntimeError::new(name, &format!("Undefined variable '{}'.", name.lexeme.as_str())))
+            }
         }
     }
 
-    pub fn assign(&mut self, name: Token, value: Value) -> Result<Value, String> {
-        if self.values.contains_key(&name.lexeme) {
-            self.values.insert(name.lexeme.clone(), value.clone());
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<Value, RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.symbol()) {
+            if self.constants.contains(&name.symbol()) {
+                return Err(RuntimeError::new(name, &format!("Cannot assign to constant '{}'.", name.lexeme)));
+            }
+            *slot = value.clone();
             return Ok(value);
         }
 
-        Err(format!("Undefined variable {}.", &name.lexeme))
+        if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow_mut().assign(name, value);
+        }
+
+        Err(RuntimeError::new(name, &format!("Undefined variable {}.", &name.lexeme)))
     }
 }
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use crate::ast::{ AstPrinter, Value };
+use crate::color;
+use crate::scanner::{ self, Token };
+
+#[derive(Clone)]
+pub struct RuntimeError {
+    pub token: Option<Token>,
+    pub message: String,
+    // the value given to `throw`, which `catch` binds in place of the message
+    pub value: Option<Value>,
+    // set when the program called `exit()`, which stops it without being an error at all; it only
+    // travels this way inside the interpreter, and comes out of `run` as an `Ok`
+    pub(crate) exit_code: Option<i64>,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, message: &str) -> Self {
+        Self {
+            token: Some(token.clone()),
+            message: message.to_string(),
+            value: None,
+            exit_code: None,
+        }
+    }
+
+    // for errors raised away from any source token, such as inside natives
+    pub fn without_token(message: &str) -> Self {
+        Self {
+            token: None,
+            message: message.to_string(),
+            value: None,
+            exit_code: None,
+        }
+    }
+
+    pub fn thrown(token: &Token, value: Value) -> Self {
+        Self {
+            token: Some(token.clone()),
+            message: value.print(),
+            value: Some(value),
+            exit_code: None,
+        }
+    }
+
+    pub fn exit(code: i64) -> Self {
+        Self {
+            token: None,
+            message: format!("Exited with code {}.", code),
+            value: None,
+            exit_code: Some(code),
+        }
+    }
+}
+
+impl fmt::Debug for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RuntimeError")
+            .field("token", &self.token)
+            .field("message", &self.message)
+            .field("exit_code", &self.exit_code)
+            .finish()
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.token {
+            Some(token) => write!(f, "[line {}] {}", token.line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// where a problem was found, for tools that render diagnostics themselves;
+// `lexeme` is empty when the problem is at the end of the input
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub line: u32,
+    pub column: Option<u32>,
+    pub lexeme: String,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    // highlights the line number and lexeme with ANSI codes when asked, e.g. for a terminal
+    pub fn render(&self, color: bool) -> String {
+        let severity = match self.severity {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+        if self.lexeme.is_empty() {
+            format!("{} on line {} at end. {}", severity, color::line(self.line, color), self.message)
+        } else {
+            format!("{} on line {} at '{}'. {}", severity, color::line(self.line, color), color::lexeme(&self.lexeme, color), self.message)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
+
+// one object per diagnostic, with a null column when it has no single position, like most runtime errors
+pub fn diagnostics_to_json(diagnostics: &[Diagnostic]) -> String {
+    let objects: Vec<String> = diagnostics.iter()
+        .map(|diagnostic| format!(
+            "{{ \"severity\": \"{}\", \"line\": {}, \"column\": {}, \"message\": \"{}\" }}",
+            diagnostic.severity,
+            diagnostic.line,
+            diagnostic.column.map_or(String::from("null"), |column| column.to_string()),
+            scanner::json_escape(&diagnostic.message),
+        ))
+        .collect();
+    format!("[{}]", objects.join(", "))
+}
+
+// warnings are reported but never stop a program from running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoxError {
+    Parse(Vec<String>),
+    // the source stopped partway through a declaration, e.g. an unclosed brace
+    Incomplete(Vec<String>),
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoxError::Parse(errors) | LoxError::Incomplete(errors) => write!(f, "{}", errors.join("\n")),
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use crate::ast::{ Expr, Pattern, Stmt, Value };
+use crate::scanner::{ Token, TokenType };
+
+const INDENT: &str = "  ";
+
+// renders a parsed program back into lox source with two-space indents and uniform spacing;
+// comments aren't in the syntax tree, so they're lost
+pub fn format_source(program: &[Stmt]) -> String {
+    let mut output = String::new();
+    for (index, stmt) in program.iter().enumerate() {
+        // functions and classes get a blank line on either side
+        if index > 0 && (is_declaration(stmt) || is_declaration(&program[index - 1])) {
+            output.push('\n');
+        }
+        output.push_str(&format_stmt(stmt, 0));
+        output.push('\n');
+    }
+    output
+}
+
+// how tightly an expression binds, loosest first, following the parser's levels
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Comma,
+    Assignment,
+    Coalesce,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Comma => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Coalesce,
+            Precedence::Coalesce => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+
+    fn of(operator: &Token) -> Precedence {
+        match operator.token_type {
+            TokenType::QuestionQuestion => Precedence::Coalesce,
+            TokenType::Or => Precedence::Or,
+            TokenType::And => Precedence::And,
+            TokenType::EqualEqual | TokenType::BangEqual => Precedence::Equality,
+            TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => Precedence::Comparison,
+            TokenType::Plus | TokenType::Minus => Precedence::Term,
+            _ => Precedence::Factor,
+        }
+    }
+}
+
+fn is_declaration(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Function { .. } | Stmt::Class { .. })
+}
+
+fn pad(depth: usize) -> String {
+    INDENT.repeat(depth)
+}
+
+// every format_ function leaves the first line unindented, since the caller decides where it starts
+fn format_stmt(stmt: &Stmt, depth: usize) -> String {
+    if let Some((initializer, while_loop)) = as_for_loop(stmt) {
+        return format_for(initializer, while_loop, depth);
+    }
+    match stmt {
+        Stmt::Block { statements } => format_block(statements, depth),
+        Stmt::Class { name, superclass, methods, static_methods } => {
+            let mut header = format!("class {}", name.lexeme);
+            if let Some(superclass) = superclass {
+                header.push_str(&format!(" < {}", format_expr(superclass, Precedence::Primary, depth)));
+            }
+            let mut members: Vec<String> = methods.iter().map(|method| format_method(method, depth + 1)).collect();
+            members.extend(static_methods.iter().map(|method| format!("class {}", format_method(method, depth + 1))));
+            if members.is_empty() {
+                return format!("{} {{}}", header);
+            }
+            let separator = format!("\n\n{}", pad(depth + 1));
+            format!("{} {{\n{}{}\n{}}}", header, pad(depth + 1), members.join(&separator), pad(depth))
+        }
+        Stmt::Break { label, .. } => format!("break{};", format_jump_label(label)),
+        Stmt::Const { name, initializer } => format!("const {} = {};", name.lexeme, format_expr(initializer, Precedence::Comma, depth)),
+        Stmt::Continue { label, .. } => format!("continue{};", format_jump_label(label)),
+        Stmt::DoWhile { body, condition, label } => {
+            let closing = if is_braced(body) { String::from(" ") } else { format!("\n{}", pad(depth)) };
+            format!(
+                "{}do{}{}while ({});",
+                format_loop_label(label),
+                format_branch(body, depth),
+                closing,
+                format_expr(condition, Precedence::Comma, depth),
+            )
+        }
+        Stmt::Expression { expression } => format!("{};", format_expr(expression, Precedence::Comma, depth)),
+        Stmt::ForEach { var_name, iterable, body, label } => format!(
+            "{}for ({} in {}){}",
+            format_loop_label(label),
+            var_name.lexeme,
+            format_expr(iterable, Precedence::Comma, depth),
+            format_branch(body, depth),
+        ),
+        Stmt::Function { .. } => format!("fun {}", format_method(stmt, depth)),
+        Stmt::If { condition, then_branch, else_branch } => {
+            let mut output = format!("if ({}){}", format_expr(condition, Precedence::Comma, depth), format_branch(then_branch, depth));
+            if let Some(else_branch) = else_branch {
+                if is_braced(then_branch) {
+                    output.push_str(" else");
+                } else {
+                    output.push_str(&format!("\n{}else", pad(depth)));
+                }
+                match else_branch.as_ref() {
+                    Stmt::If { .. } => output.push_str(&format!(" {}", format_stmt(else_branch, depth))),
+                    _ => output.push_str(&format_branch(else_branch, depth)),
+                }
+            }
+            output
+        }
+        Stmt::Match { subject, arms, .. } => {
+            let mut output = format!("match {} {{", format_expr(subject, Precedence::Comma, depth));
+            for (pattern, body) in arms {
+                let pattern = match pattern {
+                    Pattern::Literal(value) => format_literal(value),
+                    Pattern::Wildcard => String::from("_"),
+                };
+                output.push_str(&format!("\n{}{} => {}", pad(depth + 1), pattern, format_stmt(body, depth + 1)));
+            }
+            format!("{}\n{}}}", output, pad(depth))
+        }
+        Stmt::Print { expressions } => format!("print {};", format_list(expressions, depth)),
+        Stmt::Return { value: Some(value), .. } => format!("return {};", format_expr(value, Precedence::Comma, depth)),
+        Stmt::Return { value: None, .. } => String::from("return;"),
+        Stmt::Switch { subject, cases, default } => {
+            let mut output = format!("switch ({}) {{", format_expr(subject, Precedence::Comma, depth));
+            for (value, body) in cases {
+                let label = format!("case {}:", format_expr(value, Precedence::Comma, depth + 1));
+                output.push_str(&format_case(&label, body, depth + 1));
+            }
+            if let Some(default) = default {
+                output.push_str(&format_case("default:", default, depth + 1));
+            }
+            format!("{}\n{}}}", output, pad(depth))
+        }
+        Stmt::Throw { value, .. } => format!("throw {};", format_expr(value, Precedence::Comma, depth)),
+        Stmt::Try { body, name, handler, .. } => {
+            format!("try {} catch ({}) {}", format_block(body, depth), name.lexeme, format_block(handler, depth))
+        }
+        Stmt::Var { name, initializer: Some(initializer) } => {
+            format!("var {} = {};", name.lexeme, format_expr(initializer, Precedence::Comma, depth))
+        }
+        Stmt::Var { name, initializer: None } => format!("var {};", name.lexeme),
+        Stmt::VarUnpack { names, initializer, .. } => {
+            let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+            format!("var ({}) = {};", names.join(", "), format_expr(initializer, Precedence::Comma, depth))
+        }
+        Stmt::While { condition, body, label, .. } => format!(
+            "{}while ({}){}",
+            format_loop_label(label),
+            format_expr(condition, Precedence::Comma, depth),
+            format_branch(body, depth),
+        ),
+    }
+}
+
+fn format_block(statements: &[Stmt], depth: usize) -> String {
+    if statements.is_empty() {
+        return String::from("{}");
+    }
+    let mut output = String::from("{");
+    for stmt in statements {
+        output.push_str(&format!("\n{}{}", pad(depth + 1), format_stmt(stmt, depth + 1)));
+    }
+    format!("{}\n{}}}", output, pad(depth))
+}
+
+// a block stays on the line of whatever it belongs to, and any other statement goes on the next one, indented
+fn format_branch(body: &Stmt, depth: usize) -> String {
+    if is_braced(body) {
+        format!(" {}", format_stmt(body, depth))
+    } else {
+        format!("\n{}{}", pad(depth + 1), format_stmt(body, depth + 1))
+    }
+}
+
+fn is_braced(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Block { .. }) && as_for_loop(stmt).is_none()
+}
+
+// a for loop desugars into a while with an increment, wrapped in a block when it has an initializer
+fn as_for_loop(stmt: &Stmt) -> Option<(Option<&Stmt>, &Stmt)> {
+    match stmt {
+        Stmt::Block { statements } => match statements.as_slice() {
+            [initializer @ (Stmt::Var { .. } | Stmt::Expression { .. }), while_loop @ Stmt::While { increment: Some(_), .. }] => {
+                Some((Some(initializer), while_loop))
+            }
+            _ => None,
+        },
+        Stmt::While { increment: Some(_), .. } => Some((None, stmt)),
+        _ => None,
+    }
+}
+
+fn format_case(label: &str, body: &[Stmt], depth: usize) -> String {
+    let mut output = format!("\n{}{}", pad(depth), label);
+    for stmt in body {
+        output.push_str(&format!("\n{}{}", pad(depth + 1), format_stmt(stmt, depth + 1)));
+    }
+    output
+}
+
+fn format_for(initializer: Option<&Stmt>, while_loop: &Stmt, depth: usize) -> String {
+    let Stmt::While { condition, body, increment, label } = while_loop else {
+        return format_stmt(while_loop, depth);
+    };
+    let initializer = initializer.map_or_else(|| String::from(";"), |initializer| format_stmt(initializer, depth));
+    // an omitted condition was filled in with `true`
+    let condition = match condition {
+        Expr::Literal { value: Value::Boolean(true) } => String::new(),
+        _ => format!(" {}", format_expr(condition, Precedence::Comma, depth)),
+    };
+    let increment = increment.as_ref().map(|increment| format!(" {}", format_expr(increment, Precedence::Comma, depth))).unwrap_or_default();
+    format!("{}for ({}{};{}){}", format_loop_label(label), initializer, condition, increment, format_branch(body, depth))
+}
+
+// a function without the `fun`, which methods don't have
+fn format_method(method: &Stmt, depth: usize) -> String {
+    match method {
+        Stmt::Function { name, params, body } => {
+            let params: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+            format!("{}({}) {}", name.lexeme, params.join(", "), format_block(body, depth))
+        }
+        _ => format_stmt(method, depth),
+    }
+}
+
+fn format_loop_label(label: &Option<Token>) -> String {
+    label.as_ref().map(|label| format!("{}: ", label.lexeme)).unwrap_or_default()
+}
+
+fn format_jump_label(label: &Option<Token>) -> String {
+    label.as_ref().map(|label| format!(" {}", label.lexeme)).unwrap_or_default()
+}
+
+fn format_list(exprs: &[Expr], depth: usize) -> String {
+    let exprs: Vec<String> = exprs.iter().map(|expr| format_expr(expr, Precedence::Assignment, depth)).collect();
+    exprs.join(", ")
+}
+
+// parenthesizes the expression when it binds more loosely than `context` allows, which only happens
+// for nodes the parser made up itself, like the negated condition of an `unless`
+fn format_expr(expr: &Expr, context: Precedence, depth: usize) -> String {
+    let (text, precedence) = match expr {
+        Expr::Assign { name, value } => {
+            (format!("{} = {}", name.lexeme, format_expr(value, Precedence::Assignment, depth)), Precedence::Assignment)
+        }
+        Expr::Binary { left, operator, right } | Expr::Logical { left, operator, right } => {
+            let precedence = Precedence::of(operator);
+            // comparisons can't be chained, so neither side may be another comparison
+            let left_context = if precedence == Precedence::Comparison { precedence.next() } else { precedence };
+            let text = format!(
+                "{} {} {}",
+                format_expr(left, left_context, depth),
+                operator.lexeme,
+                format_expr(right, precedence.next(), depth),
+            );
+            (text, precedence)
+        }
+        Expr::Block { statements, final_expr } => {
+            let mut text = String::from("{");
+            for stmt in statements {
+                text.push_str(&format!("\n{}{}", pad(depth + 1), format_stmt(stmt, depth + 1)));
+            }
+            let final_expr = format_expr(final_expr, Precedence::Comma, depth + 1);
+            let text = if statements.is_empty() {
+                format!("{{ {} }}", final_expr)
+            } else {
+                format!("{}\n{}{}\n{}}}", text, pad(depth + 1), final_expr, pad(depth))
+            };
+            (text, Precedence::Primary)
+        }
+        Expr::Call { callee, arguments, keywords, .. } => {
+            let mut arguments: Vec<String> = arguments.iter().map(|argument| format_expr(argument, Precedence::Assignment, depth)).collect();
+            arguments.extend(keywords.iter().map(|(label, argument)| {
+                format!("{}: {}", label.lexeme, format_expr(argument, Precedence::Assignment, depth))
+            }));
+            (format!("{}({})", format_expr(callee, Precedence::Call, depth), arguments.join(", ")), Precedence::Call)
+        }
+        Expr::Comma { exprs } => {
+            let exprs: Vec<String> = exprs.iter().map(|expr| format_expr(expr, Precedence::Assignment, depth)).collect();
+            (exprs.join(", "), Precedence::Comma)
+        }
+        Expr::Get { object, name } => (format!("{}.{}", format_expr(object, Precedence::Call, depth), name.lexeme), Precedence::Call),
+        Expr::GetOptional { object, name } => {
+            (format!("{}?.{}", format_expr(object, Precedence::Call, depth), name.lexeme), Precedence::Call)
+        }
+        Expr::Grouping { expression } => (format!("({})", format_expr(expression, Precedence::Assignment, depth)), Precedence::Primary),
+        Expr::Index { object, index, .. } => {
+            let text = format!("{}[{}]", format_expr(object, Precedence::Call, depth), format_expr(index, Precedence::Assignment, depth));
+            (text, Precedence::Call)
+        }
+        Expr::List { elements, .. } => (format!("[{}]", format_list(elements, depth)), Precedence::Primary),
+        Expr::Literal { value } => (format_literal(value), Precedence::Primary),
+        Expr::Set { object, name, value } => {
+            let text = format!(
+                "{}.{} = {}",
+                format_expr(object, Precedence::Call, depth),
+                name.lexeme,
+                format_expr(value, Precedence::Assignment, depth),
+            );
+            (text, Precedence::Assignment)
+        }
+        Expr::Super { method, .. } => (format!("super.{}", method.lexeme), Precedence::Primary),
+        Expr::This { .. } => (String::from("this"), Precedence::Primary),
+        Expr::Tuple { elements, .. } => match elements.as_slice() {
+            [element] => (format!("({},)", format_expr(element, Precedence::Assignment, depth)), Precedence::Primary),
+            _ => (format!("({})", format_list(elements, depth)), Precedence::Primary),
+        },
+        Expr::Unary { operator, right } => {
+            let right = format_expr(right, Precedence::Unary, depth);
+            // `- -x` written without the space would be a decrement
+            let separator = if operator.token_type == TokenType::Minus && right.starts_with('-') { " " } else { "" };
+            (format!("{}{}{}", operator.lexeme, separator, right), Precedence::Unary)
+        }
+        Expr::Unpack { names, value, .. } => {
+            let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+            (format!("({}) = {}", names.join(", "), format_expr(value, Precedence::Assignment, depth)), Precedence::Assignment)
+        }
+        Expr::Update { name, operator, prefix: true } => (format!("{}{}", operator.lexeme, name.lexeme), Precedence::Unary),
+        Expr::Update { name, operator, prefix: false } => (format!("{}{}", name.lexeme, operator.lexeme), Precedence::Call),
+        Expr::Variable { name } => (name.lexeme.clone(), Precedence::Primary),
+    };
+    if precedence < context {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+// numbers keep a decimal point so they read back as numbers rather than ints
+fn format_literal(value: &Value) -> String {
+    match value {
+        Value::Number(number) => {
+            let text = number.to_string();
+            if number.is_finite() && !text.contains('.') {
+                format!("{}.0", text)
+            } else {
+                text
+            }
+        }
+        Value::String(string) => {
+            let mut text = String::from("\"");
+            for c in string.chars() {
+                match c {
+                    '\n' => text.push_str("\\n"),
+                    '\t' => text.push_str("\\t"),
+                    '\r' => text.push_str("\\r"),
+                    '\0' => text.push_str("\\0"),
+                    '\\' => text.push_str("\\\\"),
+                    '"' => text.push_str("\\\""),
+                    // any other control character would otherwise land in the output raw
+                    c if c.is_control() => text.push_str(&format!("\\u{{{:x}}}", c as u32)),
+                    _ => text.push(c),
+                }
+            }
+            text.push('"');
+            text
+        }
+        _ => crate::ast::AstPrinter::print(value),
+    }
+}
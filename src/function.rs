@@ -0,0 +1,158 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{ Stmt, Value };
+use crate::class::LoxInstance;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::interpreter::{ stray_jump, ControlFlow, Interpreter };
+use crate::scanner::{ Token, TokenType };
+use crate::symbol::Symbol;
+
+#[derive(Clone, Copy)]
+pub enum Arity {
+    Fixed(usize),
+    Range(usize, usize),
+    // variadic, with a required minimum
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Fixed(arity) => count == *arity,
+            Arity::Range(min, max) => (*min..=*max).contains(&count),
+            Arity::AtLeast(min) => count >= *min,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Fixed(arity) => write!(f, "{}", arity),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+        }
+    }
+}
+
+pub trait LoxCallable {
+    fn arity(&self) -> Arity;
+    // the parameter names keyword arguments are matched against, which natives don't have
+    fn params(&self) -> Option<Vec<Token>> {
+        None
+    }
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError>;
+}
+
+type NativeFn = dyn Fn(&mut Interpreter, Vec<Value>) -> Result<Value, RuntimeError>;
+
+pub struct NativeFunction {
+    pub name: String,
+    arity: Arity,
+    // boxed so embedders can register closures that capture host state, not just plain functions
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: Arity, function: F) -> Self
+    where
+        F: Fn(&mut Interpreter, Vec<Value>) -> Result<Value, RuntimeError> + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        (self.function)(interpreter, arguments)
+    }
+}
+
+pub struct LoxFunction {
+    pub name: Token,
+    params: Vec<Token>,
+    body: Rc<Vec<Stmt>>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        name: Token,
+        params: Vec<Token>,
+        body: Rc<Vec<Stmt>>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            body,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define(Symbol::intern("this"), Value::Instance(instance));
+        LoxFunction::new(
+            self.name.clone(),
+            self.params.clone(),
+            Rc::clone(&self.body),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    fn this(&self) -> Result<Value, RuntimeError> {
+        let keyword = Token::new(TokenType::This, String::from("this"), self.name.line, self.name.column);
+        self.closure.borrow().get(&keyword)
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> Arity {
+        Arity::Fixed(self.params.len())
+    }
+
+    fn params(&self) -> Option<Vec<Token>> {
+        Some(self.params.clone())
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in self.params.iter().zip(arguments) {
+            environment.define(param.symbol(), argument);
+        }
+
+        match interpreter.execute_block(&self.body, Rc::new(RefCell::new(environment))) {
+            Ok(()) => (),
+            Err(ControlFlow::Return(value)) => {
+                if !self.is_initializer {
+                    return Ok(value);
+                }
+            }
+            Err(ControlFlow::Error(error)) => return Err(error),
+            Err(ControlFlow::Exit(code)) => return Err(RuntimeError::exit(code)),
+            Err(ControlFlow::Break(_)) => return Err(stray_jump("break")),
+            Err(ControlFlow::Continue(_)) => return Err(stray_jump("continue")),
+        }
+
+        if self.is_initializer {
+            return self.this();
+        }
+        Ok(Value::Nil)
+    }
+}
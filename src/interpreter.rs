@@ -1,130 +1,990 @@
-use crate::{ast::{ Expr, Value, AstPrinter, Stmt }, scanner::TokenType, Lox, environment::Environment};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{ self, stderr, stdin, stdout, BufRead, BufReader, Write };
+use std::rc::Rc;
+use std::time::{ Duration, Instant, SystemTime, UNIX_EPOCH };
+
+use input_stream::InputStream;
+
+use crate::{ast::{ Expr, Pattern, Value, AstPrinter, Program, Stmt }, scanner::{ Token, TokenType }, environment::Environment};
+use crate::class::{ LoxClass, LoxInstance };
+use crate::error::RuntimeError;
+use crate::function::{ Arity, LoxCallable, LoxFunction, NativeFunction };
+use crate::native;
+use crate::symbol::Symbol;
+
+// the default for max_depth: low enough that even a debug build can't overflow the 2 MB stack of a spawned
+// thread, which is where an embedder or a test is most likely to run a script; the command line raises it
+pub const MAX_DEPTH: usize = 200;
+
+// the most elements a list built by `*` or `range`, or bytes a string built by `*`, may have
+pub const MAX_SEQUENCE_LEN: usize = 1 << 24;
+
+// unwinds out of statements, either to the enclosing call for a return or to the top level for an error
+pub enum ControlFlow {
+    Return(Value),
+    // carries the label of the targeted loop, or none for the innermost one
+    Break(Option<Symbol>),
+    Continue(Option<Symbol>),
+    Error(RuntimeError),
+    // `exit()` was called; unlike an error, `catch` can't stop it
+    Exit(i64),
+}
+
+// expressions can only fail with a RuntimeError, so an exit travels through them as one and turns back here
+impl From<RuntimeError> for ControlFlow {
+    fn from(error: RuntimeError) -> Self {
+        match error.exit_code {
+            Some(code) => ControlFlow::Exit(code),
+            None => ControlFlow::Error(error),
+        }
+    }
+}
+
+// a break or continue that got out of every loop; the parser rejects these, but a hand-built AST can hold one
+pub(crate) fn stray_jump(keyword: &str) -> RuntimeError {
+    RuntimeError::without_token(&format!("Can't use '{}' outside of a loop.", keyword))
+}
+
+// what an embedder lets scripts do; the defaults match the command line, apart from max_depth, which it
+// raises to suit the bigger stack it runs on
+#[derive(Debug, Clone, Copy)]
+pub struct InterpreterConfig {
+    pub allow_io: bool,
+    pub max_steps: Option<u64>,
+    pub max_call_depth: usize,
+    // how deeply statements and expressions may nest while running, counted through calls, since that
+    // rather than the number of calls is what uses up the native stack
+    pub max_depth: usize,
+    // logs every expression and its value, to stderr unless `with_trace_output` says otherwise
+    pub trace: bool,
+}
+
+// how much work the current program has done, for profiling from lox with `stats()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub exprs: u64,
+    pub calls: u64,
+}
+
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        Self {
+            allow_io: true,
+            max_steps: None,
+            max_call_depth: 1000,
+            max_depth: MAX_DEPTH,
+            trace: false,
+        }
+    }
+}
 
 pub struct Interpreter {
-    pub environment: Environment,
+    pub environment: Rc<RefCell<Environment>>,
+    input: InputStream<Box<dyn BufRead>>,
+    output: Box<dyn Write>,
+    // where the trace goes when the config turns it on
+    trace_output: Box<dyn Write>,
+    config: InterpreterConfig,
+    steps: u64,
+    call_depth: usize,
+    depth: usize,
+    // how many expressions are being evaluated right now, for indenting the trace
+    trace_depth: usize,
+    stats: Stats,
+    // xorshift state for `random()`, never zero
+    random_state: u64,
+    // what `clock()` measures from
+    started: Instant,
+    // registered by the host, and defined again whenever `run` starts over with fresh globals
+    host_natives: Vec<Rc<NativeFunction>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
-    pub fn interpret(&mut self, stmt: Stmt) {
+    pub fn new() -> Self {
+        Self::with_config(InterpreterConfig::default())
+    }
+
+    // natives that need IO are only registered when the config allows it
+    pub fn with_config(config: InterpreterConfig) -> Self {
+        Self {
+            environment: Interpreter::globals(config.allow_io),
+            input: InputStream::new(Box::new(BufReader::new(stdin()))),
+            output: Box::new(stdout()),
+            trace_output: Box::new(stderr()),
+            config,
+            host_natives: Vec::new(),
+            steps: 0,
+            call_depth: 0,
+            depth: 0,
+            trace_depth: 0,
+            stats: Stats::default(),
+            random_state: random_seed(),
+            started: Instant::now(),
+        }
+    }
+
+    // replaces stdin as the source for `input()`, e.g. with canned lines
+    pub fn with_input<R: BufRead + 'static>(mut self, reader: R) -> Self {
+        self.input = InputStream::new(Box::new(reader));
+        self
+    }
+
+    // replaces stdout as the destination for `print` and prompts
+    pub fn with_output<W: Write + 'static>(mut self, writer: W) -> Self {
+        self.output = Box::new(writer);
+        self
+    }
+
+    // replaces stderr as the destination for the trace
+    pub fn with_trace_output<W: Write + 'static>(mut self, writer: W) -> Self {
+        self.trace_output = Box::new(writer);
+        self
+    }
+
+    pub fn write(&mut self, text: &str) -> Result<(), RuntimeError> {
+        self.output.write_all(text.as_bytes())
+            .and_then(|()| self.output.flush())
+            .map_err(|error| RuntimeError::without_token(&format!("Could not write output: {}", error)))
+    }
+
+    // one line without its line ending, or None at end of input
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let trimmed = line.trim_end_matches('\n').trim_end_matches('\r').len();
+        line.truncate(trimmed);
+        Ok(Some(line))
+    }
+
+    // exposes a host function to lox code under `name`, replacing any global already called that
+    pub fn define_native<F>(&mut self, name: &str, arity: Arity, function: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Value>) -> Result<Value, RuntimeError> + 'static,
+    {
+        let native = Rc::new(NativeFunction::new(name, arity, function));
+        self.environment.borrow_mut().define(Symbol::intern(name), Value::NativeFunction(Rc::clone(&native)));
+        self.host_natives.push(native);
+    }
+
+    fn globals(allow_io: bool) -> Rc<RefCell<Environment>> {
+        let mut globals = Environment::new();
+        native::define_globals(&mut globals, allow_io);
+        Rc::new(RefCell::new(globals))
+    }
+
+    // every run starts from a fresh global scope, so runs can't see each other's variables
+    pub fn run(&mut self, program: &Program) -> Result<Option<i64>, RuntimeError> {
+        self.environment = Interpreter::globals(self.config.allow_io);
+        for native in &self.host_natives {
+            self.environment.borrow_mut().define(Symbol::intern(&native.name), Value::NativeFunction(Rc::clone(native)));
+        }
+        self.run_program(program)
+    }
+
+    // like run, but keeps the globals left by earlier programs, so setup code can prepare them for later ones;
+    // a program that called `exit()` stops there and gives back its code rather than an error
+    pub fn run_program(&mut self, program: &Program) -> Result<Option<i64>, RuntimeError> {
+        self.steps = 0;
+        self.call_depth = 0;
+        self.depth = 0;
+        self.trace_depth = 0;
+        self.stats = Stats::default();
+        for stmt in &program.statements {
+            if let Some(code) = self.interpret(stmt)? {
+                return Ok(Some(code));
+            }
+        }
+        Ok(None)
+    }
+
+    // the code given to `exit()` if the statement called it, which callers should stop at
+    pub fn interpret(&mut self, stmt: &Stmt) -> Result<Option<i64>, RuntimeError> {
+        match self.execute(stmt) {
+            Err(ControlFlow::Error(error)) => Err(error),
+            Err(ControlFlow::Exit(code)) => Ok(Some(code)),
+            _ => Ok(None),
+        }
+    }
+
+    // every executed statement and evaluated expression counts towards max_steps
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+        match self.config.max_steps {
+            Some(max_steps) if self.steps > max_steps => Err(RuntimeError::without_token("Execution step limit exceeded.")),
+            _ => Ok(()),
+        }
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), ControlFlow> {
+        self.step()?;
+        self.enter()?;
+        let result = self.execute_unguarded(stmt);
+        self.depth -= 1;
+        result
+    }
+
+    // a statement or expression nested too deeply gives an error instead of overflowing the native stack
+    fn enter(&mut self) -> Result<(), RuntimeError> {
+        if self.depth >= self.config.max_depth {
+            return Err(RuntimeError::without_token("Stack overflow."));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    // each arm hands its work to a method or stays small, which keeps this frame small in a debug build,
+    // where it is repeated for every level of nesting in the running program
+    fn execute_unguarded(&mut self, stmt: &Stmt) -> Result<(), ControlFlow> {
         match stmt {
-            Stmt::Expression { expression } => match self.evaluate(expression) {
-                Ok(_) => (),
-                Err(msg) => Lox::runtime_error(msg)
-            }
-            Stmt::Print { expression } => match self.evaluate(expression) {
-                Ok(val) => println!("{}", val.print()),
-                Err(msg) => Lox::runtime_error(msg)
-            } 
-            Stmt::Var { name, initializer } => {
-                let val = match initializer {
-                    Some(expr) => self.evaluate(expr),
-                    None => Ok(Value::Nil)
-                };
-                self.environment.define(name.lexeme, val.unwrap());
-            }
-            _ => Lox::runtime_error(String::from("Not implemented."))
-        } 
-    }
-
-    fn evaluate(&mut self, expr: Expr) -> Result<Value, String> {
+            Stmt::Block { statements } => {
+                let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                self.execute_block(statements, Rc::new(RefCell::new(environment)))
+            }
+            Stmt::Class { name, superclass, methods, static_methods } => self.class(name, superclass, methods, static_methods),
+            Stmt::Break { label, .. } => Err(ControlFlow::Break(label.as_ref().map(Token::symbol))),
+            Stmt::Continue { label, .. } => Err(ControlFlow::Continue(label.as_ref().map(Token::symbol))),
+            Stmt::DoWhile { body, condition, label } => self.do_while(body, condition, label),
+            Stmt::Const { name, initializer } => self.define(name, Some(initializer), true),
+            Stmt::Expression { expression } => self.evaluate(expression).map(drop).map_err(ControlFlow::from),
+            Stmt::ForEach { var_name, iterable, body, label } => self.for_each(var_name, iterable, body, label),
+            Stmt::Function { name, params, body } => {
+                let function = LoxFunction::new(name.clone(), params.clone(), Rc::clone(body), Rc::clone(&self.environment), false);
+                self.environment.borrow_mut().define(name.symbol(), Value::Function(Rc::new(function)));
+                Ok(())
+            }
+            Stmt::If { condition, then_branch, else_branch } => self.if_statement(condition, then_branch, else_branch),
+            Stmt::Match { keyword, subject, arms } => self.match_arms(keyword, subject, arms),
+            Stmt::Print { expressions } => self.print(expressions),
+            Stmt::Return { keyword: _, value } => self.return_statement(value),
+            Stmt::Var { name, initializer } => self.define(name, initializer.as_ref(), false),
+            Stmt::VarUnpack { paren, names, initializer } => self.var_unpack(paren, names, initializer),
+            Stmt::Switch { subject, cases, default } => self.switch(subject, cases, default),
+            Stmt::Throw { keyword, value } => self.throw(keyword, value),
+            Stmt::Try { body, name, handler, .. } => self.try_catch(body, name, handler),
+            Stmt::While { condition, body, increment, label } => self.while_loop(condition, body, increment, label),
+        }
+    }
+
+    fn class(&mut self, name: &Token, superclass: &Option<Expr>, methods: &[Stmt], static_methods: &[Stmt]) -> Result<(), ControlFlow> {
+        let superclass = match superclass {
+            Some(expr) => match self.evaluate(expr)? {
+                Value::Class(class) => Some(class),
+                _ => return Err(ControlFlow::Error(RuntimeError::new(name, "Superclass must be a class."))),
+            },
+            None => None,
+        };
+
+        self.environment.borrow_mut().define(name.symbol(), Value::Nil);
+
+        let enclosing = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(Rc::clone(&enclosing));
+            environment.define(Symbol::intern("super"), Value::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let methods = self.methods(methods, true);
+        let static_methods = self.methods(static_methods, false);
+        self.environment = enclosing;
+
+        let class = LoxClass::new(name.lexeme.clone(), superclass, methods, static_methods);
+        self.environment.borrow_mut().assign(name, Value::Class(Rc::new(class)))?;
+        Ok(())
+    }
+
+    fn do_while(&mut self, body: &Stmt, condition: &Expr, label: &Option<Token>) -> Result<(), ControlFlow> {
+        loop {
+            let result = self.execute(body);
+            if !keep_looping(result, label)? {
+                break;
+            }
+            let condition = self.evaluate(condition)?;
+            if !self.is_truthy(&condition) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // `var` and `const`; a `var` without an initializer starts out nil
+    fn define(&mut self, name: &Token, initializer: Option<&Expr>, constant: bool) -> Result<(), ControlFlow> {
+        let val = match initializer {
+            Some(expr) => self.evaluate(expr)?,
+            None => Value::Nil
+        };
+        if constant {
+            self.environment.borrow_mut().define_constant(name.symbol(), val);
+        } else {
+            self.environment.borrow_mut().define(name.symbol(), val);
+        }
+        Ok(())
+    }
+
+    fn for_each(&mut self, var_name: &Token, iterable: &Expr, body: &Stmt, label: &Option<Token>) -> Result<(), ControlFlow> {
+        let elements = match self.evaluate(iterable)? {
+            Value::List(list) => list.as_ref().clone(),
+            Value::String(string) => string.chars().map(|c| Value::String(c.to_string())).collect(),
+            _ => return Err(ControlFlow::Error(RuntimeError::new(var_name, "Can only iterate over lists and strings."))),
+        };
+
+        // each iteration gets its own scope, so closures capture that iteration's element
+        for element in elements {
+            let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+            environment.define(var_name.symbol(), element);
+            let result = self.execute_block(std::slice::from_ref(body), Rc::new(RefCell::new(environment)));
+            if !keep_looping(result, label)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn if_statement(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) -> Result<(), ControlFlow> {
+        let condition = self.evaluate(condition)?;
+        if self.is_truthy(&condition) {
+            self.execute(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn match_arms(&mut self, keyword: &Token, subject: &Expr, arms: &[(Pattern, Stmt)]) -> Result<(), ControlFlow> {
+        let subject = self.evaluate(subject)?;
+        let arm = arms.iter().find(|(pattern, _)| match pattern {
+            Pattern::Literal(value) => self.is_equal(&subject, value),
+            Pattern::Wildcard => true,
+        });
+        match arm {
+            Some((_, body)) => self.execute(body)?,
+            None => {
+                let message = format!("No match arm for {}.", subject.print());
+                return Err(ControlFlow::Error(RuntimeError::new(keyword, &message)));
+            }
+        }
+        Ok(())
+    }
+
+    fn print(&mut self, expressions: &[Expr]) -> Result<(), ControlFlow> {
+        let mut values = Vec::new();
+        for expression in expressions {
+            let value = self.evaluate(expression)?;
+            values.push(self.stringify(&value)?);
+        }
+        self.write(&format!("{}\n", values.join(" ")))?;
+        Ok(())
+    }
+
+    fn return_statement(&mut self, value: &Option<Expr>) -> Result<(), ControlFlow> {
+        let value = match value {
+            Some(expr) => self.evaluate(expr)?,
+            None => Value::Nil,
+        };
+        Err(ControlFlow::Return(value))
+    }
+
+    fn var_unpack(&mut self, paren: &Token, names: &[Token], initializer: &Expr) -> Result<(), ControlFlow> {
+        let tuple = self.evaluate(initializer)?;
+        for (name, element) in names.iter().zip(unpack(paren, names.len(), &tuple)?) {
+            self.environment.borrow_mut().define(name.symbol(), element);
+        }
+        Ok(())
+    }
+
+    fn switch(&mut self, subject: &Expr, cases: &[(Expr, Vec<Stmt>)], default: &Option<Vec<Stmt>>) -> Result<(), ControlFlow> {
+        let subject = self.evaluate(subject)?;
+        let mut arm = default.as_ref();
+        for (value, body) in cases {
+            let value = self.evaluate(value)?;
+            if self.is_equal(&subject, &value) {
+                arm = Some(body);
+                break;
+            }
+        }
+
+        if let Some(statements) = arm {
+            let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+            self.execute_block(statements, Rc::new(RefCell::new(environment)))?;
+        }
+        Ok(())
+    }
+
+    fn throw(&mut self, keyword: &Token, value: &Expr) -> Result<(), ControlFlow> {
+        let value = self.evaluate(value)?;
+        Err(ControlFlow::Error(RuntimeError::thrown(keyword, value)))
+    }
+
+    fn try_catch(&mut self, body: &[Stmt], name: &Token, handler: &[Stmt]) -> Result<(), ControlFlow> {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        // only runtime errors are caught; returns and loop jumps pass straight through
+        match self.execute_block(body, Rc::new(RefCell::new(environment))) {
+            Err(ControlFlow::Error(error)) => {
+                let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                let caught = error.value.unwrap_or(Value::String(error.message));
+                environment.define(name.symbol(), caught);
+                self.execute_block(handler, Rc::new(RefCell::new(environment)))?;
+            }
+            result => result?,
+        }
+        Ok(())
+    }
+
+    fn while_loop(&mut self, condition: &Expr, body: &Stmt, increment: &Option<Expr>, label: &Option<Token>) -> Result<(), ControlFlow> {
+        loop {
+            let condition = self.evaluate(condition)?;
+            if !self.is_truthy(&condition) {
+                break;
+            }
+            let result = self.execute(body);
+            if !keep_looping(result, label)? {
+                break;
+            }
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn execute_block(&mut self, statements: &[Stmt], environment: Rc<RefCell<Environment>>) -> Result<(), ControlFlow> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
+        self.environment = previous;
+        result
+    }
+
+    fn methods(&self, methods: &[Stmt], bindable: bool) -> HashMap<String, Rc<LoxFunction>> {
+        let mut functions = HashMap::new();
+        for method in methods {
+            if let Stmt::Function { name, params, body } = method {
+                let is_initializer = bindable && name.lexeme == "init";
+                let function = LoxFunction::new(name.clone(), params.clone(), Rc::clone(body), Rc::clone(&self.environment), is_initializer);
+                functions.insert(name.lexeme.clone(), Rc::new(function));
+            }
+        }
+        functions
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    // monotonic, unlike the wall clock, so differences between calls are safe to use for timing
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    // the same seed always gives the same sequence
+    pub fn seed_random(&mut self, seed: u64) {
+        self.random_state = scramble(seed);
+    }
+
+    pub fn next_random(&mut self) -> u64 {
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.random_state = x;
+        x
+    }
+
+    pub(crate) fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        if !self.config.trace {
+            return self.evaluate_expr(expr);
+        }
+        // a line is written once the value is known, so subexpressions come before the expression using them
+        self.trace_depth += 1;
+        let result = self.evaluate_expr(expr);
+        self.trace_depth -= 1;
+        if let Ok(value) = &result {
+            writeln!(self.trace_output, "{}{} => {}", "  ".repeat(self.trace_depth), expr.print(), value.print())
+                .map_err(|error| RuntimeError::without_token(&format!("Could not write trace: {}", error)))?;
+        }
+        result
+    }
+
+    fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        self.step()?;
+        self.stats.exprs += 1;
+        self.enter()?;
+        let result = self.evaluate_unguarded(expr);
+        self.depth -= 1;
+        result
+    }
+
+    // like execute_unguarded, the larger arms live in methods of their own to keep this frame small
+    fn evaluate_unguarded(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
         match expr {
-            Expr::Assign { name, value } => {
-                let val = self.evaluate(*value);
-                self.environment.assign(name, val?)
-            }
-            Expr::Variable { name } => self.environment.get(&name),
-            Expr::Literal { value } => Ok(value),
-            Expr::Grouping { expression } => self.evaluate(*expression),
-            Expr::Unary { operator, right } => {
-                let right = self.evaluate(*right)?;
-
-                match operator.token_type {
-                    TokenType::Bang => Ok(Value::Boolean(!self.is_truthy(right))),
-                    TokenType::Minus => match right {
-                        Value::Number(num) => Ok(Value::Number(-(num))),
-                        _ => Err("Not a valid operand".to_string())
-                    }
-                    _ => Err("Unknown unary operator.".to_string())
-                }
+            Expr::Assign { name, value } => self.assign(name, value),
+            Expr::Block { statements, final_expr } => self.block_expression(statements, final_expr),
+            Expr::Call { callee, paren, arguments, keywords } => self.call(callee, paren, arguments, keywords),
+            Expr::Comma { exprs } => self.comma(exprs),
+            Expr::Get { object, name } => self.get(object, name, false),
+            Expr::GetOptional { object, name } => self.get(object, name, true),
+            Expr::Index { object, bracket, index } => self.index(object, bracket, index),
+            Expr::Logical { left, operator, right } => self.logical(left, operator, right),
+            Expr::Set { object, name, value } => self.set(object, name, value),
+            Expr::Super { keyword, method } => self.super_method(keyword, method),
+            Expr::This { keyword } => self.environment.borrow().get(keyword),
+            Expr::Variable { name } => self.environment.borrow().get(name),
+            Expr::List { elements, .. } => self.elements(elements).map(|values| Value::List(Rc::new(values))),
+            Expr::Tuple { elements, .. } => self.elements(elements).map(|values| Value::Tuple(Rc::new(values))),
+            Expr::Unpack { paren, names, value } => self.unpack(paren, names, value),
+            Expr::Literal { value } => Ok(value.clone()),
+            Expr::Grouping { expression } => self.evaluate(expression),
+            Expr::Unary { operator, right } => self.unary(operator, right),
+            Expr::Update { name, operator, prefix } => self.update(name, operator, *prefix),
+            Expr::Binary { left, operator, right } => self.binary(left, operator, right),
+        }
+    }
+
+    fn assign(&mut self, name: &Token, value: &Expr) -> Result<Value, RuntimeError> {
+        let val = self.evaluate(value);
+        self.environment.borrow_mut().assign(name, val?)
+    }
+
+    fn block_expression(&mut self, statements: &[Stmt], final_expr: &Expr) -> Result<Value, RuntimeError> {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = match statements.iter().try_for_each(|stmt| self.execute(stmt)) {
+            Ok(()) => self.evaluate(final_expr),
+            Err(ControlFlow::Error(error)) => Err(error),
+            Err(ControlFlow::Exit(code)) => Err(RuntimeError::exit(code)),
+            Err(ControlFlow::Return(_)) => Err(RuntimeError::without_token("Can't return from inside a block expression.")),
+            Err(ControlFlow::Break(_)) => Err(stray_jump("break")),
+            Err(ControlFlow::Continue(_)) => Err(stray_jump("continue")),
+        };
+        self.environment = previous;
+        result
+    }
+
+    fn call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr], keywords: &[(Token, Expr)]) -> Result<Value, RuntimeError> {
+        let callee = self.evaluate(callee)?;
+
+        let mut args = Vec::new();
+        for argument in arguments {
+            args.push(self.evaluate(argument)?);
+        }
+
+        let function: &dyn LoxCallable = match &callee {
+            Value::Function(function) => function.as_ref(),
+            Value::NativeFunction(function) => function.as_ref(),
+            Value::Class(class) => class,
+            _ => return Err(RuntimeError::new(paren, "Can only call functions and classes."))
+        };
+        if !keywords.is_empty() {
+            args = self.keyword_arguments(function, args, paren, keywords)?;
+        }
+
+        // natives have no token of their own, and nor does running out of depth inside a call, so those
+        // errors are reported at the call site
+        self.invoke(function, args, paren).map_err(|mut error| {
+            error.token.get_or_insert_with(|| paren.clone());
+            error
+        })
+    }
+
+    fn comma(&mut self, exprs: &[Expr]) -> Result<Value, RuntimeError> {
+        let mut value = Value::Nil;
+        for expr in exprs {
+            value = self.evaluate(expr)?;
+        }
+        Ok(value)
+    }
+
+    // `?.` gives nil for a nil object instead of an error
+    fn get(&mut self, object: &Expr, name: &Token, optional: bool) -> Result<Value, RuntimeError> {
+        match self.evaluate(object)? {
+            Value::Nil if optional => Ok(Value::Nil),
+            Value::Instance(instance) => LoxInstance::get(&instance, name),
+            Value::Class(class) => class.get(name),
+            _ => Err(RuntimeError::new(name, "Only instances have properties."))
+        }
+    }
+
+    fn index(&mut self, object: &Expr, bracket: &Token, index: &Expr) -> Result<Value, RuntimeError> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        if let Some(method) = operator_method(&object, "__index__") {
+            return self.invoke(&method, vec![index], bracket);
+        }
+        element_at(bracket, &object, &index)
+    }
+
+    fn logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(left)?;
+
+        let short_circuits = match operator.token_type {
+            TokenType::Or => self.is_truthy(&left),
+            TokenType::QuestionQuestion => !matches!(left, Value::Nil),
+            _ => !self.is_truthy(&left),
+        };
+        if short_circuits {
+            return Ok(left);
+        }
+
+        self.evaluate(right)
+    }
+
+    fn set(&mut self, object: &Expr, name: &Token, value: &Expr) -> Result<Value, RuntimeError> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => {
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
             }
-            Expr::Binary { left, operator, right } => {
-                let left = self.evaluate(*left)?;
-                let right = self.evaluate(*right)?;
+            _ => Err(RuntimeError::new(name, "Only instances have fields."))
+        }
+    }
 
-                match operator.token_type {
-                    TokenType::Greater => {
-                        match (left, right) {
-                            (Value::Number(lnum), Value::Number(rnum)) => Ok(Value::Boolean(lnum > rnum)),
-                            (_, _) => Err("Operands must be numbers.".to_string())
-                        }
-                    }
-                    TokenType::GreaterEqual => {
-                        match (left, right) {
-                            (Value::Number(lnum), Value::Number(rnum)) => Ok(Value::Boolean(lnum >= rnum)),
-                            (_, _) => Err("Operands must be numbers.".to_string())
-                        }
-                    }
-                    TokenType::Less => {
-                        match (left, right) {
-                            (Value::Number(lnum), Value::Number(rnum)) => Ok(Value::Boolean(lnum < rnum)),
-                            (_, _) => Err("Operands must be numbers.".to_string())
-                        }
-                    }
-                    TokenType::LessEqual => {
-                        match (left, right) {
-                            (Value::Number(lnum), Value::Number(rnum)) => Ok(Value::Boolean(lnum <= rnum)),
-                            (_, _) => Err("Operands must be numbers.".to_string())
-                        }
-                    }
-                    TokenType::Minus => {
-                        match (left, right) {
-                            (Value::Number(lnum), Value::Number(rnum)) => Ok(Value::Number(lnum - rnum)),
-                            (_, _) => Err("Operands must be numbers.".to_string())
-                        }
-                    }
-                    TokenType::Slash => {
-                        match (left, right) {
-                            (Value::Number(lnum), Value::Number(rnum)) => Ok(Value::Number(lnum / rnum)),
-                            (_, _) => Err("Operands must be numbers.".to_string())
-                        }
+    fn super_method(&mut self, keyword: &Token, method: &Token) -> Result<Value, RuntimeError> {
+        let superclass = match self.environment.borrow().get(keyword)? {
+            Value::Class(class) => class,
+            _ => return Err(RuntimeError::new(keyword, "Superclass must be a class."))
+        };
+        let this = Token::new(TokenType::This, String::from("this"), keyword.line, keyword.column);
+        let instance = match self.environment.borrow().get(&this)? {
+            Value::Instance(instance) => instance,
+            _ => return Err(RuntimeError::new(keyword, "Can't use 'super' outside of a method."))
+        };
+
+        match superclass.find_method(&method.lexeme) {
+            Some(function) => Ok(Value::Function(Rc::new(function.bind(instance)))),
+            None => Err(RuntimeError::new(method, &format!("Undefined property '{}'.", method.lexeme)))
+        }
+    }
+
+    fn elements(&mut self, elements: &[Expr]) -> Result<Vec<Value>, RuntimeError> {
+        let mut values = Vec::new();
+        for element in elements {
+            values.push(self.evaluate(element)?);
+        }
+        Ok(values)
+    }
+
+    fn unpack(&mut self, paren: &Token, names: &[Token], value: &Expr) -> Result<Value, RuntimeError> {
+        let tuple = self.evaluate(value)?;
+        for (name, element) in names.iter().zip(unpack(paren, names.len(), &tuple)?) {
+            self.environment.borrow_mut().assign(name, element)?;
+        }
+        Ok(tuple)
+    }
+
+    fn unary(&mut self, operator: &Token, right: &Expr) -> Result<Value, RuntimeError> {
+        let right = self.evaluate(right)?;
+
+        match operator.token_type {
+            TokenType::Bang => Ok(Value::Boolean(!self.is_truthy(&right))),
+            TokenType::Minus => match right {
+                Value::Number(num) => Ok(Value::Number(-(num))),
+                Value::Int(int) => int.checked_neg().map(Value::Int).ok_or_else(|| RuntimeError::new(operator, "Integer overflow.")),
+                _ => Err(RuntimeError::new(operator, "Operand must be a number."))
+            }
+            _ => Err(RuntimeError::new(operator, "Unknown unary operator."))
+        }
+    }
+
+    fn update(&mut self, name: &Token, operator: &Token, prefix: bool) -> Result<Value, RuntimeError> {
+        let old = self.environment.borrow().get(name)?;
+        let step = Value::Int(if operator.token_type == TokenType::PlusPlus { 1 } else { -1 });
+        let new = match old {
+            Value::Number(_) | Value::Int(_) => arithmetic(operator, old.clone(), step, i64::checked_add, |a, b| a + b)?,
+            _ => return Err(RuntimeError::new(operator, "Operand must be a number."))
+        };
+        self.environment.borrow_mut().assign(name, new.clone())?;
+        Ok(if prefix { new } else { old })
+    }
+
+    fn binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(left)?;
+        let right = self.evaluate(right)?;
+        self.binary_operation(operator, left, right)
+    }
+
+    fn binary_operation(&mut self, operator: &Token, left: Value, right: Value) -> Result<Value, RuntimeError> {
+        // an instance on the left can define the operator for itself; `!=` is the opposite of its `__eq__`
+        let method_name = match operator.token_type {
+            TokenType::Plus => Some("__add__"),
+            TokenType::EqualEqual | TokenType::BangEqual => Some("__eq__"),
+            TokenType::Less => Some("__lt__"),
+            _ => None,
+        };
+        if let Some(method) = method_name.and_then(|name| operator_method(&left, name)) {
+            let result = self.invoke(&method, vec![right], operator)?;
+            if operator.token_type == TokenType::BangEqual {
+                return Ok(Value::Boolean(!self.is_truthy(&result)));
+            }
+            return Ok(result);
+        }
+
+        match operator.token_type {
+            TokenType::Greater => compare(operator, left, right, i64::gt, f64::gt),
+            TokenType::GreaterEqual => compare(operator, left, right, i64::ge, f64::ge),
+            TokenType::Less => compare(operator, left, right, i64::lt, f64::lt),
+            TokenType::LessEqual => compare(operator, left, right, i64::le, f64::le),
+            TokenType::Minus => arithmetic(operator, left, right, i64::checked_sub, |a, b| a - b),
+            // `/` always divides exactly, even between two Ints
+            TokenType::Slash => match (left.as_number(), right.as_number()) {
+                (Some(lnum), Some(rnum)) => Ok(Value::Number(lnum / rnum)),
+                (_, _) => Err(RuntimeError::new(operator, "Operands must be numbers."))
+            }
+            // rounds towards negative infinity, so `-7 ~/ 2` is -4
+            TokenType::TildeSlash => {
+                if let (Value::Int(_), Value::Int(0)) = (&left, &right) {
+                    return Err(RuntimeError::new(operator, "Division by zero."));
+                }
+                arithmetic(operator, left, right, floor_divide, |a, b| (a / b).floor())
+            }
+            // truncating like C's fmod, so the result takes the sign of the left operand: -7 % 3 is -1
+            TokenType::Percent => {
+                if let (Value::Int(_), Value::Int(0)) = (&left, &right) {
+                    return Err(RuntimeError::new(operator, "Division by zero."));
+                }
+                arithmetic(operator, left, right, i64::checked_rem, |a, b| a % b)
+            }
+            TokenType::Star => {
+                match (left, right) {
+                    (Value::String(string), count) => {
+                        Ok(Value::String(string.repeat(repeat_count(operator, &count, string.len())?)))
                     }
-                    TokenType::Star => {
-                        match (left, right) {
-                            (Value::Number(lnum), Value::Number(rnum)) => Ok(Value::Number(lnum * rnum)),
-                            (_, _) => Err("Operands must be numbers.".to_string())
+                    (Value::List(list), count) => {
+                        let count = repeat_count(operator, &count, list.len())?;
+                        let mut elements = Vec::with_capacity(list.len() * count);
+                        for _ in 0..count {
+                            elements.extend(list.iter().cloned());
                         }
+                        Ok(Value::List(Rc::new(elements)))
                     }
-                    TokenType::Plus => {
-                       match (left, right) {
-                            (Value::String(lstr), Value::String(rstr)) => Ok(Value::String(format!("{lstr}{rstr}"))),
-                            (Value::Number(lnum), Value::Number(rnum)) => Ok(Value::Number(lnum + rnum)),
-                            (_, _) => Err("Invalid operator for operands".to_string())
-                        } 
+                    (left, right) => arithmetic(operator, left, right, i64::checked_mul, |a, b| a * b),
+                }
+            }
+            TokenType::Plus => {
+               match (left, right) {
+                    (Value::String(lstr), Value::String(rstr)) => Ok(Value::String(format!("{lstr}{rstr}"))),
+                    (Value::String(lstr), right @ Value::Instance(_)) => Ok(Value::String(format!("{lstr}{}", self.stringify(&right)?))),
+                    (left @ Value::Instance(_), Value::String(rstr)) => Ok(Value::String(format!("{}{rstr}", self.stringify(&left)?))),
+                    (left, right) if left.as_number().is_some() && right.as_number().is_some() => {
+                        arithmetic(operator, left, right, i64::checked_add, |a, b| a + b)
                     }
-                    TokenType::BangEqual => Ok(Value::Boolean(!self.is_equal(left, right))),
-                    TokenType::EqualEqual => Ok(Value::Boolean(self.is_equal(left, right))),
-                    _ => Err("Unkown binary operator".to_string()),
+                    (_, _) => Err(RuntimeError::new(operator, "Invalid operator for operands"))
+                } 
+            }
+            TokenType::BangEqual => Ok(Value::Boolean(!self.is_equal(&left, &right))),
+            TokenType::EqualEqual => Ok(Value::Boolean(self.is_equal(&left, &right))),
+            _ => Err(RuntimeError::new(operator, "Unkown binary operator")),
+        }
+    }
+
+    fn is_equal(&self, left: &Value, right: &Value) -> bool {
+        left == right
+    }
+
+    // how `print` and `+` with a string show a value, calling `__str__` or else `toString` on any instance
+    // whose class defines one, even inside a list or tuple
+    pub fn stringify(&mut self, value: &Value) -> Result<String, RuntimeError> {
+        match value {
+            Value::Instance(_) => match operator_method(value, "__str__").or_else(|| operator_method(value, "toString")) {
+                Some(method) => match self.invoke(&method, Vec::new(), &method.name)? {
+                    Value::String(string) => Ok(string),
+                    _ => Err(RuntimeError::new(&method.name, &format!("{} must return a string.", method.name.lexeme))),
+                },
+                None => Ok(value.print()),
+            },
+            Value::List(elements) => {
+                let elements = elements.iter().map(|element| self.stringify(element)).collect::<Result<Vec<String>, RuntimeError>>()?;
+                Ok(format!("[{}]", elements.join(", ")))
+            }
+            Value::Tuple(elements) => {
+                let elements = elements.iter().map(|element| self.stringify(element)).collect::<Result<Vec<String>, RuntimeError>>()?;
+                match elements.as_slice() {
+                    [element] => Ok(format!("({},)", element)),
+                    _ => Ok(format!("({})", elements.join(", "))),
                 }
             }
-            _ => Err("Not an expression".to_string())
+            _ => Ok(value.print()),
         }
     }
 
-    fn is_equal(&self, left: Value, right: Value) -> bool {
-        match (left, right) {
-            (Value::String(lstr), Value::String(rstr)) => lstr == rstr,
-            (Value::Number(lnum), Value::Number(rnum)) => lnum == rnum,
-            (Value::Boolean(lbool), Value::Boolean(rbool)) => lbool == rbool,
-            (Value::Nil, Value::Nil) => true,
-            (_, _) => false,
+    // `paren` is where a wrong argument count or a stack overflow is reported
+    pub(crate) fn invoke(&mut self, function: &dyn LoxCallable, args: Vec<Value>, paren: &Token) -> Result<Value, RuntimeError> {
+        if !function.arity().accepts(args.len()) {
+            let message = format!("Expected {} arguments but got {}.", function.arity(), args.len());
+            return Err(RuntimeError::new(paren, &message));
+        }
+
+        // recursion is bounded here rather than by the native stack
+        if self.call_depth >= self.config.max_call_depth {
+            return Err(RuntimeError::new(paren, "Stack overflow."));
         }
+        self.call_depth += 1;
+        self.stats.calls += 1;
+        let result = function.call(self, args);
+        self.call_depth -= 1;
+        result
     }
 
-    fn is_truthy(&self, val: Value) -> bool {
+    // puts keyword arguments in the slots of the parameters they name, after the positional ones
+    fn keyword_arguments(
+        &mut self,
+        function: &dyn LoxCallable,
+        positional: Vec<Value>,
+        paren: &Token,
+        keywords: &[(Token, Expr)],
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let Some(params) = function.params() else {
+            return Err(RuntimeError::new(&keywords[0].0, "Native functions don't take keyword arguments."));
+        };
+        let mut slots: Vec<Option<Value>> = positional.into_iter().map(Some).collect();
+        if slots.len() < params.len() {
+            slots.resize(params.len(), None);
+        }
+        for (label, argument) in keywords {
+            let value = self.evaluate(argument)?;
+            let Some(index) = params.iter().position(|param| param.lexeme == label.lexeme) else {
+                return Err(RuntimeError::new(label, &format!("Unknown parameter '{}'.", label.lexeme)));
+            };
+            if slots[index].is_some() {
+                return Err(RuntimeError::new(label, &format!("Parameter '{}' was given more than once.", label.lexeme)));
+            }
+            slots[index] = Some(value);
+        }
+        // too many positional arguments leave no gaps here, and fail the arity check afterwards instead
+        slots.into_iter().enumerate().map(|(index, slot)| {
+            slot.ok_or_else(|| RuntimeError::new(paren, &format!("Missing argument for parameter '{}'.", params[index].lexeme)))
+        }).collect()
+    }
+
+    // as in the book, only nil and false are falsey; 0 and "" are both truthy
+    pub fn is_truthy(&self, val: &Value) -> bool {
         match val {
             Value::Nil => false,
-            Value::Boolean(boolean) => boolean,
+            Value::Boolean(boolean) => *boolean,
             _ => true,
         }
     }
 } 
+
+// decides whether a loop goes on after running its body, consuming any break or continue aimed at it
+fn keep_looping(result: Result<(), ControlFlow>, label: &Option<Token>) -> Result<bool, ControlFlow> {
+    let targets = |target: &Option<Symbol>| target.is_none() || *target == label.as_ref().map(Token::symbol);
+    match result {
+        Ok(()) => Ok(true),
+        Err(ControlFlow::Break(target)) if targets(&target) => Ok(false),
+        Err(ControlFlow::Continue(target)) if targets(&target) => Ok(true),
+        Err(other) => Err(other),
+    }
+}
+
+// `*` on strings and lists repeats them a whole number of times, as long as the result isn't too big to build
+fn repeat_count(operator: &Token, count: &Value, len: usize) -> Result<usize, RuntimeError> {
+    let count = match count {
+        Value::Int(count) if *count >= 0 => usize::try_from(*count).unwrap_or(usize::MAX),
+        Value::Number(count) if *count >= 0.0 && count.fract() == 0.0 => *count as usize,
+        _ => return Err(RuntimeError::new(operator, "Repeat count must be a non-negative whole number.")),
+    };
+    match len.checked_mul(count) {
+        Some(size) if size <= MAX_SEQUENCE_LEN => Ok(count),
+        _ => Err(RuntimeError::new(operator, "Repetition result too large.")),
+    }
+}
+
+// two Ints stay an Int unless the result overflows; any Number makes the result a Number
+fn arithmetic(
+    operator: &Token,
+    left: Value,
+    right: Value,
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Value, RuntimeError> {
+    if let (Value::Int(lint), Value::Int(rint)) = (&left, &right) {
+        return int_op(*lint, *rint).map(Value::Int).ok_or_else(|| RuntimeError::new(operator, "Integer overflow."));
+    }
+    match (left.as_number(), right.as_number()) {
+        (Some(lnum), Some(rnum)) => Ok(Value::Number(float_op(lnum, rnum))),
+        (_, _) => Err(RuntimeError::new(operator, "Operands must be numbers.")),
+    }
+}
+
+// Ints are compared exactly rather than through f64, which can't hold every i64
+fn compare(
+    operator: &Token,
+    left: Value,
+    right: Value,
+    int_cmp: fn(&i64, &i64) -> bool,
+    float_cmp: fn(&f64, &f64) -> bool,
+) -> Result<Value, RuntimeError> {
+    if let (Value::Int(lint), Value::Int(rint)) = (&left, &right) {
+        return Ok(Value::Boolean(int_cmp(lint, rint)));
+    }
+    match (left.as_number(), right.as_number()) {
+        (Some(lnum), Some(rnum)) => Ok(Value::Boolean(float_cmp(&lnum, &rnum))),
+        (_, _) => Err(RuntimeError::new(operator, "Operands must be numbers.")),
+    }
+}
+
+pub(crate) fn floor_divide(left: i64, right: i64) -> Option<i64> {
+    let quotient = left.checked_div(right)?;
+    if left % right != 0 && (left < 0) != (right < 0) {
+        return Some(quotient - 1);
+    }
+    Some(quotient)
+}
+
+// the elements a destructuring pattern of `count` names binds, which must match the tuple exactly
+fn unpack(paren: &Token, count: usize, value: &Value) -> Result<Vec<Value>, RuntimeError> {
+    match value {
+        Value::Tuple(elements) if elements.len() == count => Ok(elements.to_vec()),
+        Value::Tuple(elements) => {
+            Err(RuntimeError::new(paren, &format!("Expected {} values to unpack but got {}.", count, elements.len())))
+        }
+        _ => Err(RuntimeError::new(paren, "Can only unpack a tuple.")),
+    }
+}
+
+// the method a class defines for an operator, bound to the instance it's used on
+fn operator_method(value: &Value, name: &str) -> Option<LoxFunction> {
+    let Value::Instance(instance) = value else {
+        return None;
+    };
+    let method = instance.borrow().class.find_method(name)?;
+    Some(method.bind(Rc::clone(instance)))
+}
+
+// indexes count from zero, and a byte string's elements are Ints from 0 to 255
+fn element_at(bracket: &Token, object: &Value, index: &Value) -> Result<Value, RuntimeError> {
+    let Value::Int(index) = index else {
+        return Err(RuntimeError::new(bracket, "Index must be an int."));
+    };
+    let position = usize::try_from(*index).ok();
+    let (element, len) = match object {
+        Value::List(elements) | Value::Tuple(elements) => (position.and_then(|position| elements.get(position).cloned()), elements.len()),
+        Value::Bytes(bytes) => (position.and_then(|position| bytes.get(position)).map(|byte| Value::Int(*byte as i64)), bytes.len()),
+        _ => return Err(RuntimeError::new(bracket, "Can only index lists, tuples and byte strings.")),
+    };
+    element.ok_or_else(|| RuntimeError::new(bracket, &format!("Index {} is out of range for length {}.", index, len)))
+}
+
+// unseeded runs differ from each other, which is all `random()` promises without `seed()`
+fn random_seed() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    scramble(nanos)
+}
+
+// splitmix64, so nearby seeds like 1 and 2 don't start nearby sequences, and never zero since xorshift stays at zero
+fn scramble(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    if z == 0 { 1 } else { z }
+}
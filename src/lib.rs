@@ -1,74 +1,529 @@
+use std::cell::RefCell;
+use std::env;
 use std::fs;
-use std::io::{stdin, Error, ErrorKind};
-use input_stream::InputStream;
-use scanner::{TokenType, Scanner, Token};
+use std::io::{ self, stdin, BufRead, IsTerminal, Write };
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
 
-use crate::parser::Parser;
+pub use crate::ast::{ ast_eq_ignoring_spans, Expr, Program, Span, Stmt, Value };
+pub use crate::error::{ Diagnostic, LoxError, RuntimeError, Severity };
+pub use crate::formatter::format_source;
+pub use crate::function::Arity;
+pub use crate::interpreter::{ Interpreter, InterpreterConfig, Stats };
+pub use crate::optimize::{ fold_constants, fold_program };
+pub use crate::parser::{ ParseError, Parser };
+pub use crate::scanner::{ tokens_to_json, ReaderScanner, ScanError, ScanErrorKind, Scanner, Token, TokenType };
 
 mod scanner;
 mod ast;
 mod parser;
 mod interpreter;
 mod environment;
+mod function;
+mod class;
+mod native;
+mod error;
+mod color;
+mod vm;
+mod symbol;
+mod optimize;
+mod resolver;
+mod formatter;
+
+// lines `start_line` to `old_end_line` of the previous source were replaced by lines `start_line` to
+// `new_end_line`, so an end before the start means no lines were removed or none were added
+#[derive(Debug, Clone, Copy)]
+pub struct TextEdit {
+    pub start_line: u32,
+    pub old_end_line: u32,
+    pub new_end_line: u32,
+}
+
+// everything a captured run printed, alongside the diagnostics it would have reported
+#[derive(Debug, Default)]
+pub struct RunResult {
+    pub output: String,
+    pub errors: Vec<String>,
+    // the code given to `exit()`, if the program stopped that way
+    pub exit_code: Option<i64>,
+}
+
+// how the command line wants a program compiled and its problems shown
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOptions {
+    // fold constants and drop dead branches before running
+    pub optimize: bool,
+    // highlight line numbers and lexemes in diagnostics with ANSI codes
+    pub color: bool,
+    // raise the parser's nesting limit above its default, which is only safe on an 8 MB stack, for a
+    // caller running on a bigger one like the command line
+    pub max_nesting: Option<usize>,
+    // likewise for the interpreter's max_depth, whose default is sized for a 2 MB stack
+    pub max_depth: Option<usize>,
+}
+
+impl RunOptions {
+    fn interpreter(&self) -> Interpreter {
+        let defaults = InterpreterConfig::default();
+        Interpreter::with_config(InterpreterConfig { max_depth: self.max_depth.unwrap_or(defaults.max_depth), ..defaults })
+    }
+}
+
+// an in-memory writer the caller can still read from after handing it to the interpreter
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
 
 pub struct Lox {
     pub had_error: bool,
 }
 
 impl Lox {
-    pub fn run_file(path: &String) -> std::io::Result<()> {
+    // these return the code passed to `exit()`, leaving it to the caller whether to end the process
+    pub fn run_file(path: &String, options: RunOptions) -> std::io::Result<Option<i64>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Lox::run(contents, options))
+    }
+
+    pub fn run_file_timed(path: &String, options: RunOptions) -> std::io::Result<Option<i64>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Lox::run_timed(contents, options))
+    }
+
+    // prints the scanned tokens as a JSON array instead of running the file
+    pub fn dump_tokens(path: &String, color: bool) -> std::io::Result<()> {
         let contents = fs::read_to_string(path)?;
-        Lox::run(contents);
+        let mut scanner = Scanner::new(&contents);
+        let tokens = scanner.scan_tokens();
+        // the skipped input goes to stderr, so stdout stays valid JSON
+        for error in scanner.into_errors() {
+            eprintln!("{}", error.render(color));
+        }
+        println!("{}", tokens_to_json(&tokens));
         Ok(())
     }
 
-    pub fn run_prompt() -> std::io::Result<()> {
-        let stdin = stdin();
-        let mut input = InputStream::new(stdin.lock());
+    pub fn run_file_vm(path: &String, options: RunOptions) -> std::io::Result<Option<i64>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Lox::run_vm(contents, options))
+    }
+
+    // a terminal gets line editing and history kept in ~/.lox_history, anything else is read as plain lines
+    pub fn run_prompt(options: RunOptions) -> io::Result<Option<i64>> {
+        if !stdin().is_terminal() {
+            let mut interpreter = options.interpreter().with_input(stdin().lock());
+            return Lox::repl(&mut interpreter, options, |interpreter, _| interpreter.read_line());
+        }
+
+        let mut editor = DefaultEditor::new().map_err(io::Error::other)?;
+        let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(".lox_history"));
+        if let Some(history) = &history {
+            // there's no history yet on the first run
+            let _ = editor.load_history(history);
+        }
+
+        let mut interpreter = options.interpreter();
+        let result = Lox::repl(&mut interpreter, options, |_, prompt| match editor.readline(prompt) {
+            Ok(line) => {
+                let _ = editor.add_history_entry(line.as_str());
+                Ok(Some(line))
+            }
+            // ctrl-c abandons the current entry the same way a blank line does
+            Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(error) => Err(io::Error::other(error)),
+        });
+
+        if let Some(history) = &history {
+            editor.save_history(history).map_err(io::Error::other)?;
+        }
+        result
+    }
+
+    // the non-interactive REPL, without prompts, for piped input or any other reader
+    pub fn run_lines<R: BufRead + 'static>(reader: R) -> io::Result<Option<i64>> {
+        let mut interpreter = Interpreter::new().with_input(reader);
+        Lox::repl(&mut interpreter, RunOptions::default(), |interpreter, _| interpreter.read_line())
+    }
+
+    // lines accumulate until they parse, and a blank line gives up on an incomplete entry
+    fn repl<F>(interpreter: &mut Interpreter, options: RunOptions, mut read_line: F) -> io::Result<Option<i64>>
+    where
+        F: FnMut(&mut Interpreter, &str) -> io::Result<Option<String>>,
+    {
+        let mut entry = String::new();
         loop {
-            print!("> ");
-            let cmd: String = match input.scan() {
-                Ok(cmd) => cmd,
-                Err(_) => break,
+            let prompt = if entry.is_empty() { "> " } else { "... " };
+            let line = match read_line(interpreter, prompt)? {
+                Some(line) => line,
+                None => break,
             };
-            Lox::run(cmd);
+            let blank = line.trim().is_empty();
+            entry.push_str(&line);
+            entry.push('\n');
+
+            match Lox::compile_with(&entry, options) {
+                Err(LoxError::Incomplete(_)) if !blank => continue,
+                Err(error) => Lox::compile_error(error),
+                Ok(program) => {
+                    for stmt in &program.statements {
+                        match interpreter.interpret(stmt) {
+                            Ok(Some(code)) => return Ok(Some(code)),
+                            Err(error) => Lox::runtime_error(error, options.color),
+                            Ok(None) => (),
+                        }
+                    }
+                }
+            }
+            entry.clear();
         }
-        Ok(())
+        Ok(None)
+    }
+
+    pub fn run(source: String, options: RunOptions) -> Option<i64> {
+        Lox::run_source(&source, options, false)
+    }
+
+    // like run, but reports how long each phase took on stderr so the program's own output is untouched
+    pub fn run_timed(source: String, options: RunOptions) -> Option<i64> {
+        Lox::run_source(&source, options, true)
     }
 
-    pub fn run(source: String) {
-        let mut scanner = Scanner::new(source.as_str());
+    fn run_source(source: &str, options: RunOptions, time: bool) -> Option<i64> {
+        let report = |phase: &str, started: Instant| {
+            if time {
+                eprintln!("{:<6}{:?}", phase, started.elapsed());
+            }
+        };
+
+        let started = Instant::now();
+        let mut scanner = Scanner::new(source);
         let tokens = scanner.scan_tokens();
-        println!("{:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let statements = parser.parse();
-        if parser.had_error {
-            println!("Parser error.");
-            return;
-        }
-        let mut interpreter = interpreter::Interpreter{
-            environment: environment::Environment::new(),
+        let scan_errors = scanner.into_errors();
+        report("scan", started);
+
+        let started = Instant::now();
+        let program = Lox::parse(tokens, scan_errors, source, options);
+        report("parse", started);
+        let program = match program {
+            Ok(program) => program,
+            Err(error) => {
+                Lox::compile_error(error);
+                return None;
+            }
         };
-        match statements {
-            Ok(stmt) => {
-                for stmt in stmt {
-                    interpreter.interpret(stmt);
+
+        let started = Instant::now();
+        let mut interpreter = options.interpreter();
+        let mut exit_code = None;
+        for stmt in &program.statements {
+            match interpreter.interpret(stmt) {
+                Ok(Some(code)) => {
+                    exit_code = Some(code);
+                    break;
                 }
-            },
-            Err(err) => panic!("{}", err),
+                Err(error) => Lox::runtime_error(error, options.color),
+                Ok(None) => (),
+            }
         }
+        report("run", started);
+        exit_code
     }
 
-    pub fn error(token: &Token, message: String) -> String {
-        if token.token_type == TokenType::EoF {
-            format!("Error on line {} at end. {}", token.line, message)
-        } else {
-            format!("Error on line {} at '{}'. {}", token.line, token.lexeme, message)
+    // runs without touching stdin, stdout or stderr, e.g. when compiled to WASM
+    pub fn run_capture(source: &str) -> RunResult {
+        let mut result = RunResult::default();
+        match Lox::compile(source) {
+            Ok(program) => {
+                let capture = Capture::default();
+                let mut interpreter = Interpreter::new().with_input(io::empty()).with_output(capture.clone());
+                for stmt in &program.statements {
+                    match interpreter.interpret(stmt) {
+                        Ok(Some(code)) => {
+                            result.exit_code = Some(code);
+                            break;
+                        }
+                        Err(error) => result.errors.push(error.to_string()),
+                        Ok(None) => (),
+                    }
+                }
+                result.output = String::from_utf8_lossy(&capture.0.borrow()).into_owned();
+            }
+            Err(LoxError::Parse(errors) | LoxError::Incomplete(errors)) => result.errors = errors,
         }
+        result
+    }
+
+    // scans and parses once, so the program can be run many times
+    pub fn compile(source: &str) -> Result<Program, LoxError> {
+        Lox::compile_with(source, RunOptions::default())
     }
 
-    pub fn runtime_error(message: String) {
-        println!("{}", message);
+    // compile, then fold constants and drop dead branches, as the command line's --optimize does
+    pub fn compile_optimized(source: &str) -> Result<Program, LoxError> {
+        Lox::compile_with(source, RunOptions { optimize: true, ..RunOptions::default() })
+    }
+
+    // compile with the errors rendered and the program optimized as the options say
+    pub fn compile_with(source: &str, options: RunOptions) -> Result<Program, LoxError> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens();
+        let scan_errors = scanner.into_errors();
+        Lox::parse(tokens, scan_errors, source, options)
+    }
+
+    // re-scans and re-parses only the top-level statements an edit touched, keeping the rest of `old`;
+    // statements after the edit are only kept when it didn't add or remove lines, since their tokens
+    // would otherwise point at the wrong lines
+    pub fn reparse(old: &Program, edit: &TextEdit, source: &str) -> Result<Program, LoxError> {
+        let count = old.statements.len();
+        if old.spans.len() != count {
+            return Lox::compile(source);
+        }
+        let spans = &old.spans;
+        let mut before = spans.iter().take_while(|span| span.end_line < edit.start_line).count();
+        let mut after = match edit.new_end_line == edit.old_end_line {
+            true => spans[before..].iter().rev().take_while(|span| span.start_line > edit.old_end_line).count(),
+            false => 0,
+        };
+        // whole lines are re-scanned, so a kept statement can't share a line with a re-parsed one
+        while before > 0 && before < count - after && spans[before].start_line == spans[before - 1].end_line {
+            before -= 1;
+        }
+        while after > 0 && count - after > before && spans[count - after].start_line == spans[count - after - 1].end_line {
+            after -= 1;
+        }
+
+        let start_line = if before > 0 { spans[before - 1].end_line + 1 } else { 1 };
+        let end = if after > 0 { scanner::line_offset(source, spans[count - after].start_line) } else { source.len() };
+        let region = &source[scanner::line_offset(source, start_line).min(end)..end];
+        let mut scanner = Scanner::new(region).starting_at_line(start_line);
+        let tokens = scanner.scan_tokens();
+        let scan_errors = scanner.into_errors();
+        // cutting the source apart can itself cause an error, e.g. a string opened by the edit, so the
+        // whole source decides what's really wrong
+        let Ok(middle) = Lox::parse(tokens, scan_errors, source, RunOptions::default()) else {
+            return Lox::compile(source);
+        };
+
+        let mut statements = old.statements[..before].to_vec();
+        statements.extend(middle.statements);
+        statements.extend_from_slice(&old.statements[count - after..]);
+        let mut new_spans = spans[..before].to_vec();
+        new_spans.extend(middle.spans);
+        new_spans.extend_from_slice(&spans[count - after..]);
+        Ok(Program { statements, spans: new_spans })
+    }
+
+    // the parser still runs after a scan error, so every mistake is reported in one go
+    fn parse(tokens: Vec<Token>, scan_errors: Vec<ScanError>, source: &str, options: RunOptions) -> Result<Program, LoxError> {
+        let mut parser = Parser::new(tokens, source);
+        if let Some(max_nesting) = options.max_nesting {
+            parser = parser.with_max_nesting(max_nesting);
+        }
+        let result = parser.parse();
+        if !scan_errors.is_empty() {
+            // a string still open at the end could be closed by more input
+            let incomplete = scan_errors[0].kind == ScanErrorKind::UnterminatedString || parser.is_incomplete();
+            let mut errors: Vec<String> = scan_errors.iter().map(|error| error.render(options.color)).collect();
+            if let Err(parse_errors) = result {
+                errors.extend(parse_errors.iter().map(|error| error.render(options.color)));
+            }
+            return Err(if incomplete { LoxError::Incomplete(errors) } else { LoxError::Parse(errors) });
+        }
+        match result {
+            Ok(mut statements) => {
+                // warnings are only shown by --check, so running a program stays quiet
+                let errors: Vec<String> = resolver::resolve(&statements)
+                    .iter()
+                    .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                    .map(|diagnostic| diagnostic.render(options.color))
+                    .collect();
+                if !errors.is_empty() {
+                    return Err(LoxError::Parse(errors));
+                }
+                if options.optimize {
+                    optimize::fold_program(&mut statements);
+                }
+                let statements = statements.into_iter().map(Rc::new).collect();
+                Ok(Program { statements, spans: parser.into_spans() })
+            }
+            Err(errors) => {
+                let errors = errors.iter().map(|error| error.render(options.color)).collect();
+                if parser.is_incomplete() {
+                    Err(LoxError::Incomplete(errors))
+                } else {
+                    Err(LoxError::Parse(errors))
+                }
+            }
+        }
+    }
+
+    // finds every problem that can be spotted without running the program
+    pub fn check(source: &str) -> Vec<Diagnostic> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens();
+        let mut diagnostics: Vec<Diagnostic> = scanner.into_errors().iter().map(ScanError::diagnostic).collect();
+        // what did parse is still resolved, so a syntax error doesn't hide the problems around it
+        let (statements, errors) = Parser::new(tokens, source).parse_recovering();
+        diagnostics.extend(errors.into_iter().map(|error| error.diagnostic));
+        diagnostics.extend(resolver::check(&statements));
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+        diagnostics
+    }
+
+    // the same problems as check, as a JSON array for editors and other tools to read
+    pub fn check_json(source: &str) -> String {
+        error::diagnostics_to_json(&Lox::check(source))
+    }
+
+    // prints one `line:column: severity: message` per problem, returning whether none were errors
+    pub fn check_file(path: &String) -> io::Result<bool> {
+        let contents = fs::read_to_string(path)?;
+        let diagnostics = Lox::check(&contents);
+        for diagnostic in &diagnostics {
+            let column = diagnostic.column.map(|column| format!("{}:", column)).unwrap_or_default();
+            println!("{}:{}:{} {}: {}", path, diagnostic.line, column, diagnostic.severity, diagnostic.message);
+        }
+        Ok(diagnostics.iter().all(|diagnostic| diagnostic.severity == Severity::Warning))
+    }
+
+    // prints the problems as a JSON array, returning whether none were errors
+    pub fn check_file_json(path: &String) -> io::Result<bool> {
+        let contents = fs::read_to_string(path)?;
+        let diagnostics = Lox::check(&contents);
+        println!("{}", error::diagnostics_to_json(&diagnostics));
+        Ok(diagnostics.iter().all(|diagnostic| diagnostic.severity == Severity::Warning))
+    }
+
+    // reformats source from its syntax tree, parsed without folding constants so it still reads as written
+    pub fn format(source: &str) -> Result<String, LoxError> {
+        Lox::format_with(source, false)
+    }
+
+    fn format_with(source: &str, color: bool) -> Result<String, LoxError> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens();
+        let mut errors: Vec<String> = scanner.into_errors().iter().map(|error| error.render(color)).collect();
+        match Parser::new(tokens, source).parse() {
+            Ok(statements) if errors.is_empty() => Ok(format_source(&statements)),
+            Ok(_) => Err(LoxError::Parse(errors)),
+            Err(parse_errors) => {
+                errors.extend(parse_errors.iter().map(|error| error.render(color)));
+                Err(LoxError::Parse(errors))
+            }
+        }
+    }
+
+    // prints the file reformatted, or its syntax errors, returning whether it could be formatted
+    pub fn format_file(path: &String, color: bool) -> io::Result<bool> {
+        let contents = fs::read_to_string(path)?;
+        match Lox::format_with(&contents, color) {
+            Ok(formatted) => {
+                print!("{}", formatted);
+                Ok(true)
+            }
+            Err(error) => {
+                Lox::compile_error(error);
+                Ok(false)
+            }
+        }
+    }
+
+    // runs the program on the bytecode VM instead of the tree-walking interpreter
+    pub fn run_vm(source: String, options: RunOptions) -> Option<i64> {
+        let functions = match Lox::compile_vm(&source, options) {
+            Ok(functions) => functions,
+            Err(error) => {
+                Lox::compile_error(error);
+                return None;
+            }
+        };
+        let mut vm = vm::Vm::new();
+        for function in functions {
+            match vm.run(function) {
+                Ok(Some(code)) => return Some(code),
+                Err(error) => Lox::runtime_error(error, options.color),
+                Ok(None) => (),
+            }
+        }
+        None
+    }
+
+    // run_capture on the VM, so the two can be compared on the same program
+    pub fn run_vm_capture(source: &str) -> RunResult {
+        let mut result = RunResult::default();
+        let functions = match Lox::compile_vm(source, RunOptions::default()) {
+            Ok(functions) => functions,
+            Err(LoxError::Parse(errors) | LoxError::Incomplete(errors)) => {
+                result.errors = errors;
+                return result;
+            }
+        };
+        let capture = Capture::default();
+        let mut vm = vm::Vm::with_host(Interpreter::new().with_input(io::empty()).with_output(capture.clone()));
+        for function in functions {
+            match vm.run(function) {
+                Ok(Some(code)) => {
+                    result.exit_code = Some(code);
+                    break;
+                }
+                Err(error) => result.errors.push(error.to_string()),
+                Ok(None) => (),
+            }
+        }
+        result.output = String::from_utf8_lossy(&capture.0.borrow()).into_owned();
+        result
+    }
+
+    // every statement is compiled before any runs, so something the VM can't do stops the whole program
+    fn compile_vm(source: &str, options: RunOptions) -> Result<Vec<Rc<vm::VmFunction>>, LoxError> {
+        let program = Lox::compile_with(source, options)?;
+        program.statements.iter()
+            .map(|stmt| vm::compile(stmt))
+            .collect::<Result<Vec<_>, Diagnostic>>()
+            .map_err(|error| LoxError::Parse(vec![error.render(options.color)]))
+    }
+
+    pub fn error(token: &Token, message: String) -> Diagnostic {
+        let lexeme = if token.token_type == TokenType::EoF { String::new() } else { token.lexeme.clone() };
+        Diagnostic {
+            line: token.line,
+            column: Some(token.column),
+            lexeme,
+            message,
+            severity: Severity::Error,
+        }
+    }
+
+    pub fn compile_error(error: LoxError) {
+        match error {
+            LoxError::Parse(errors) | LoxError::Incomplete(errors) => {
+                for error in errors {
+                    println!("{}", error);
+                }
+                println!("Parser error.");
+            }
+        }
+    }
+
+    pub fn runtime_error(error: RuntimeError, color: bool) {
+        match &error.token {
+            Some(token) => println!("[line {}] {}", color::line(token.line, color), error.message),
+            None => println!("{}", error.message),
+        }
     }
 
 }
@@ -1,17 +1,72 @@
-use std::env::args;
-use lox::Lox;
+use std::env::{args, var_os};
+use std::io::{stdout, IsTerminal};
+use std::process;
+use std::thread;
+use lox::{ Lox, RunOptions };
+
+// the parser and interpreter recurse natively for every level of nesting and every Lox call, so give them
+// room for limits well above the library's defaults, which are sized for an ordinary thread
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+const MAX_NESTING: usize = 1024;
+const MAX_DEPTH: usize = 5000;
 
 fn main() {
+    let runner = thread::Builder::new().stack_size(STACK_SIZE).spawn(run).unwrap();
+    runner.join().unwrap();
+}
+
+fn run() {
 
-    let args: Vec<String> = args().collect();
+    let mut args: Vec<String> = args().collect();
+
+    let no_color = args.iter().any(|arg| arg == "--no-color");
+    let use_vm = args.iter().any(|arg| arg == "--vm");
+    let dump_tokens = args.iter().any(|arg| arg == "--dump-tokens=json");
+    let time = args.iter().any(|arg| arg == "--time");
+    let check = args.iter().any(|arg| arg == "--check");
+    let check_json = args.iter().any(|arg| arg == "--check=json");
+    let fmt = args.iter().any(|arg| arg == "--fmt");
+    let optimize = args.iter().any(|arg| arg == "--optimize");
+    args.retain(|arg| !matches!(arg.as_str(), "--no-color" | "--vm" | "--dump-tokens=json" | "--time" | "--check" | "--check=json" | "--fmt" | "--optimize"));
+    let options = RunOptions {
+        optimize,
+        color: !no_color && var_os("NO_COLOR").is_none() && stdout().is_terminal(),
+        max_nesting: Some(MAX_NESTING),
+        max_depth: Some(MAX_DEPTH),
+    };
 
     match args.len() {
         length if length > 2 => {
-            println!("Usage: loxrs [script]");
+            println!("Usage: loxrs [--no-color] [--vm] [--dump-tokens=json] [--time] [--check] [--check=json] [--fmt] [--optimize] [script]");
         } 
-        length if length == 2  => Lox::run_file(&args[1]).unwrap(),
-        _ => Lox::run_prompt().unwrap(),
+        2 if check => {
+            // exits like a failed compile, so editors and scripts can tell the file has problems
+            if !Lox::check_file(&args[1]).unwrap() {
+                process::exit(65);
+            }
+        }
+        2 if check_json => {
+            if !Lox::check_file_json(&args[1]).unwrap() {
+                process::exit(65);
+            }
+        }
+        2 if fmt => {
+            if !Lox::format_file(&args[1], options.color).unwrap() {
+                process::exit(65);
+            }
+        }
+        2 if dump_tokens => Lox::dump_tokens(&args[1], options.color).unwrap(),
+        2 if use_vm => exit_with(Lox::run_file_vm(&args[1], options).unwrap()),
+        2 if time => exit_with(Lox::run_file_timed(&args[1], options).unwrap()),
+        2 => exit_with(Lox::run_file(&args[1], options).unwrap()),
+        _ => exit_with(Lox::run_prompt(options).unwrap()),
     }
 
 }
 
+// codes outside an i32 are truncated, the same as a C program's exit status
+fn exit_with(code: Option<i64>) {
+    if let Some(code) = code {
+        process::exit(code as i32);
+    }
+}
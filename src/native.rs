@@ -0,0 +1,468 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f64::consts;
+use std::fs;
+use std::rc::Rc;
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use crate::ast::{ AstPrinter, Value };
+use crate::class::{ LoxClass, LoxInstance };
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::function::{ Arity, NativeFunction };
+use crate::interpreter::{ Interpreter, MAX_SEQUENCE_LEN };
+use crate::symbol::Symbol;
+
+pub fn define_globals(globals: &mut Environment, allow_io: bool) {
+    let mut natives = vec![
+        NativeFunction::new("type", Arity::Fixed(1), type_of),
+        NativeFunction::new("assert", Arity::Range(1, 2), assert),
+        NativeFunction::new("range", Arity::Range(2, 3), range),
+        NativeFunction::new("sqrt", Arity::Fixed(1), sqrt),
+        NativeFunction::new("pow", Arity::Fixed(2), pow),
+        NativeFunction::new("sin", Arity::Fixed(1), sin),
+        NativeFunction::new("cos", Arity::Fixed(1), cos),
+        NativeFunction::new("tan", Arity::Fixed(1), tan),
+        NativeFunction::new("log", Arity::Fixed(1), log),
+        NativeFunction::new("exp", Arity::Fixed(1), exp),
+        NativeFunction::new("min", Arity::AtLeast(1), min),
+        NativeFunction::new("max", Arity::AtLeast(1), max),
+        NativeFunction::new("clamp", Arity::Fixed(3), clamp),
+        NativeFunction::new("split", Arity::Fixed(2), split),
+        NativeFunction::new("join", Arity::Fixed(2), join),
+        NativeFunction::new("contains", Arity::Fixed(2), contains),
+        NativeFunction::new("startsWith", Arity::Fixed(2), starts_with),
+        NativeFunction::new("endsWith", Arity::Fixed(2), ends_with),
+        NativeFunction::new("replace", Arity::Fixed(3), replace),
+        NativeFunction::new("trim", Arity::Fixed(1), trim),
+        NativeFunction::new("len", Arity::Fixed(1), len),
+        NativeFunction::new("ord", Arity::Fixed(1), ord),
+        NativeFunction::new("chr", Arity::Fixed(1), chr),
+        NativeFunction::new("parseInt", Arity::Range(1, 2), parse_int),
+        NativeFunction::new("stats", Arity::Fixed(0), stats),
+        NativeFunction::new("random", Arity::Fixed(0), random),
+        NativeFunction::new("randInt", Arity::Fixed(2), rand_int),
+        NativeFunction::new("seed", Arity::Fixed(1), seed),
+        NativeFunction::new("clock", Arity::Fixed(0), clock),
+        NativeFunction::new("time", Arity::Fixed(0), time),
+    ];
+    // a sandbox can't read stdin, touch files or end the host's process
+    if allow_io {
+        natives.push(NativeFunction::new("input", Arity::Range(0, 1), input));
+        natives.push(NativeFunction::new("exit", Arity::Range(0, 1), exit));
+        natives.push(NativeFunction::new("readFile", Arity::Fixed(1), read_file));
+        natives.push(NativeFunction::new("writeFile", Arity::Fixed(2), write_file));
+    }
+    #[cfg(test)]
+    natives.push(NativeFunction::new("sideEffect", Arity::Fixed(1), tests::side_effect));
+
+    for native in natives {
+        globals.define(Symbol::intern(&native.name), Value::NativeFunction(Rc::new(native)));
+    }
+    globals.define_constant(Symbol::intern("pi"), Value::Number(consts::PI));
+    globals.define_constant(Symbol::intern("e"), Value::Number(consts::E));
+}
+
+fn type_of(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::String(arguments[0].type_name().to_string()))
+}
+
+fn assert(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    if interpreter.is_truthy(&arguments[0]) {
+        return Ok(Value::Nil);
+    }
+    match arguments.get(1) {
+        Some(message) => Err(RuntimeError::without_token(&format!("Assertion failed: {}", message.print()))),
+        None => Err(RuntimeError::without_token("Assertion failed.")),
+    }
+}
+
+fn input(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    if let Some(prompt) = arguments.first() {
+        interpreter.write(&prompt.print())?;
+    }
+    match interpreter.read_line() {
+        Ok(Some(line)) => Ok(Value::String(line)),
+        Ok(None) => Ok(Value::Nil),
+        Err(error) => Err(RuntimeError::without_token(&format!("Could not read input: {}", error))),
+    }
+}
+
+// counts from start towards end, stopping before it, like Python's range
+fn range(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    if let Some(ints) = arguments.iter().map(|argument| match argument {
+        Value::Int(int) => Some(*int),
+        _ => None,
+    }).collect::<Option<Vec<i64>>>() {
+        return int_range(ints);
+    }
+
+    let mut numbers = Vec::new();
+    for argument in &arguments {
+        match argument {
+            Value::Number(num) if num.is_finite() => numbers.push(*num),
+            Value::Int(int) => numbers.push(*int as f64),
+            _ => return Err(RuntimeError::without_token("Range arguments must be finite numbers.")),
+        }
+    }
+    let (start, end) = (numbers[0], numbers[1]);
+    let step = numbers.get(2).copied().unwrap_or(1.0);
+    if step == 0.0 {
+        return Err(RuntimeError::without_token("Range step can't be zero."));
+    }
+
+    // counting up front, rather than adding step until end, can't get stuck once step is too small to
+    // change start, and keeps a huge range from being built at all
+    let count = ((end - start) / step).ceil().max(0.0);
+    if count > MAX_SEQUENCE_LEN as f64 {
+        return Err(RuntimeError::without_token("Range is too large."));
+    }
+    let elements = (0..count as usize).map(|i| Value::Number(start + i as f64 * step)).collect();
+    Ok(Value::List(Rc::new(elements)))
+}
+
+// a range of Ints when every argument is one
+fn int_range(ints: Vec<i64>) -> Result<Value, RuntimeError> {
+    let (start, end) = (ints[0], ints[1]);
+    let step = ints.get(2).copied().unwrap_or(1);
+    if step == 0 {
+        return Err(RuntimeError::without_token("Range step can't be zero."));
+    }
+
+    let (start, end, step) = (start as i128, end as i128, step as i128);
+    let count = match step > 0 {
+        true => (end - start + step - 1).div_euclid(step),
+        false => (start - end - step - 1).div_euclid(-step),
+    }.max(0);
+    if count > MAX_SEQUENCE_LEN as i128 {
+        return Err(RuntimeError::without_token("Range is too large."));
+    }
+    // every element lies between start and end, so it fits in an i64
+    let elements = (0..count).map(|i| Value::Int((start + i * step) as i64)).collect();
+    Ok(Value::List(Rc::new(elements)))
+}
+
+// an instance with `exprs` and `calls` fields, the calls including this one to stats itself
+fn stats(interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let stats = interpreter.stats();
+    let class = LoxClass::new(String::from("Stats"), None, HashMap::new(), HashMap::new());
+    let mut instance = LoxInstance::new(Rc::new(class));
+    instance.set_field("exprs", Value::Int(stats.exprs as i64));
+    instance.set_field("calls", Value::Int(stats.calls as i64));
+    Ok(Value::Instance(Rc::new(RefCell::new(instance))))
+}
+
+// uniform in [0, 1), from the top 53 bits so every result is exactly representable
+fn random(interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::Number((interpreter.next_random() >> 11) as f64 / (1u64 << 53) as f64))
+}
+
+// both bounds are included, as with dice
+fn rand_int(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (lo, hi) = match (&arguments[0], &arguments[1]) {
+        (Value::Int(lo), Value::Int(hi)) => (*lo, *hi),
+        _ => return Err(RuntimeError::without_token("randInt bounds must be ints.")),
+    };
+    if lo > hi {
+        return Err(RuntimeError::without_token("randInt lower bound can't be greater than the upper bound."));
+    }
+    let span = (hi as i128 - lo as i128 + 1) as u128;
+    let offset = interpreter.next_random() as u128 % span;
+    Ok(Value::Int((lo as i128 + offset as i128) as i64))
+}
+
+fn seed(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    match arguments[0] {
+        Value::Int(int) => interpreter.seed_random(int as u64),
+        Value::Number(num) => interpreter.seed_random(num.to_bits()),
+        _ => return Err(RuntimeError::without_token("Seed must be a number.")),
+    }
+    Ok(Value::Nil)
+}
+
+// seconds since the interpreter started, for benchmarking
+fn clock(interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(interpreter.elapsed().as_secs_f64()))
+}
+
+// seconds since the Unix epoch, which can jump if the system clock is changed
+fn time(_interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => Ok(Value::Number(elapsed.as_secs_f64())),
+        Err(_) => Err(RuntimeError::without_token("System clock is set before the Unix epoch.")),
+    }
+}
+
+// stops the whole program, passing the code to whoever is running it
+fn exit(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    match arguments.first() {
+        None => Err(RuntimeError::exit(0)),
+        Some(Value::Int(code)) => Err(RuntimeError::exit(*code)),
+        Some(_) => Err(RuntimeError::without_token("Exit code must be an int.")),
+    }
+}
+
+fn number(value: &Value) -> Result<f64, RuntimeError> {
+    value.as_number().ok_or_else(|| RuntimeError::without_token("Math arguments must be numbers."))
+}
+
+// out-of-domain inputs, like sqrt(-1) or log(-1), give nan rather than an error
+fn math(arguments: &[Value], function: fn(f64) -> f64) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(function(number(&arguments[0])?)))
+}
+
+fn sqrt(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    math(&arguments, f64::sqrt)
+}
+
+fn pow(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(number(&arguments[0])?.powf(number(&arguments[1])?)))
+}
+
+fn sin(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    math(&arguments, f64::sin)
+}
+
+fn cos(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    math(&arguments, f64::cos)
+}
+
+fn tan(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    math(&arguments, f64::tan)
+}
+
+fn log(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    math(&arguments, f64::ln)
+}
+
+fn exp(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    math(&arguments, f64::exp)
+}
+
+// min, max and clamp hand back one of their arguments, so Ints stay Ints
+fn min(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut smallest = &arguments[0];
+    number(smallest)?;
+    for argument in &arguments[1..] {
+        if number(argument)? < number(smallest)? {
+            smallest = argument;
+        }
+    }
+    Ok(smallest.clone())
+}
+
+fn max(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut largest = &arguments[0];
+    number(largest)?;
+    for argument in &arguments[1..] {
+        if number(argument)? > number(largest)? {
+            largest = argument;
+        }
+    }
+    Ok(largest.clone())
+}
+
+fn clamp(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (x, lo, hi) = (number(&arguments[0])?, number(&arguments[1])?, number(&arguments[2])?);
+    if lo > hi || lo.is_nan() || hi.is_nan() {
+        return Err(RuntimeError::without_token("Clamp lower bound can't be greater than the upper bound."));
+    }
+    if x < lo {
+        return Ok(arguments[1].clone());
+    }
+    if x > hi {
+        return Ok(arguments[2].clone());
+    }
+    Ok(arguments[0].clone())
+}
+
+// `what` names the argument in the error, as in "Path must be a string."
+fn string<'a>(value: &'a Value, what: &str) -> Result<&'a str, RuntimeError> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(RuntimeError::without_token(&format!("{} must be a string.", what))),
+    }
+}
+
+// an empty separator splits into characters
+fn split(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (text, separator) = (string(&arguments[0], "Text")?, string(&arguments[1], "Separator")?);
+    let parts: Vec<Value> = if separator.is_empty() {
+        text.chars().map(|c| Value::String(c.to_string())).collect()
+    } else {
+        text.split(separator).map(|part| Value::String(part.to_string())).collect()
+    };
+    Ok(Value::List(Rc::new(parts)))
+}
+
+// elements that aren't strings are joined the way print would show them, `__str__` included
+fn join(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let separator = string(&arguments[1], "Separator")?;
+    match &arguments[0] {
+        Value::List(elements) => {
+            let parts = elements.iter().map(|element| interpreter.stringify(element)).collect::<Result<Vec<String>, RuntimeError>>()?;
+            Ok(Value::String(parts.join(separator)))
+        }
+        _ => Err(RuntimeError::without_token("Can only join a list.")),
+    }
+}
+
+fn contains(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (text, part) = (string(&arguments[0], "Text")?, string(&arguments[1], "Substring")?);
+    Ok(Value::Boolean(text.contains(part)))
+}
+
+fn starts_with(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (text, prefix) = (string(&arguments[0], "Text")?, string(&arguments[1], "Prefix")?);
+    Ok(Value::Boolean(text.starts_with(prefix)))
+}
+
+fn ends_with(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (text, suffix) = (string(&arguments[0], "Text")?, string(&arguments[1], "Suffix")?);
+    Ok(Value::Boolean(text.ends_with(suffix)))
+}
+
+// replaces every occurrence; an empty pattern would match between every character, so it's rejected
+fn replace(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let text = string(&arguments[0], "Text")?;
+    let (from, to) = (string(&arguments[1], "Pattern")?, string(&arguments[2], "Replacement")?);
+    if from.is_empty() {
+        return Err(RuntimeError::without_token("Can't replace an empty string."));
+    }
+    Ok(Value::String(text.replace(from, to)))
+}
+
+fn trim(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::String(string(&arguments[0], "Text")?.trim().to_string()))
+}
+
+// a string's length is in characters, like ord and chr, rather than bytes
+fn len(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let len = match &arguments[0] {
+        Value::String(string) => string.chars().count(),
+        Value::List(elements) | Value::Tuple(elements) => elements.len(),
+        Value::Bytes(bytes) => bytes.len(),
+        _ => return Err(RuntimeError::without_token("Can only take len of a string, list, tuple or byte string.")),
+    };
+    Ok(Value::Int(len as i64))
+}
+
+fn ord(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut chars = string(&arguments[0], "Character")?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Value::Int(c as i64)),
+        _ => Err(RuntimeError::without_token("Can only take ord of a single character.")),
+    }
+}
+
+// surrogates and anything past U+10FFFF aren't characters
+fn chr(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let code = match arguments[0] {
+        Value::Int(int) => u32::try_from(int).ok(),
+        Value::Number(num) if num.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&num) => Some(num as u32),
+        _ => None,
+    };
+    match code.and_then(char::from_u32) {
+        Some(c) => Ok(Value::String(c.to_string())),
+        None => Err(RuntimeError::without_token("Invalid code point.")),
+    }
+}
+
+// digits past 9 are letters in either case, and a leading `+` or `-` is allowed
+fn parse_int(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let text = string(&arguments[0], "Text")?;
+    let radix = match arguments.get(1) {
+        None => 10,
+        Some(Value::Int(radix)) if (2..=36).contains(radix) => *radix as u32,
+        Some(_) => return Err(RuntimeError::without_token("Radix must be an int from 2 to 36.")),
+    };
+    match i64::from_str_radix(text, radix) {
+        Ok(int) => Ok(Value::Int(int)),
+        Err(_) => Err(RuntimeError::without_token(&format!("Can't parse '{}' as an int in base {}.", text, radix))),
+    }
+}
+
+fn read_file(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let path = string(&arguments[0], "Path")?;
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Value::String(contents)),
+        Err(error) => Err(RuntimeError::without_token(&format!("Could not read file '{}': {}", path, error))),
+    }
+}
+
+fn write_file(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let path = string(&arguments[0], "Path")?;
+    match fs::write(path, arguments[1].print()) {
+        Ok(()) => Ok(Value::Nil),
+        Err(error) => Err(RuntimeError::without_token(&format!("Could not write file '{}': {}", path, error))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::Lox;
+    use crate::ast::{ AstPrinter, Value };
+    use crate::error::RuntimeError;
+    use crate::interpreter::Interpreter;
+
+    thread_local! {
+        // the tags sideEffect was called with on this test's thread, in order
+        static CALLS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    // returns its tag, so a test can see both whether it ran and what the expression gave back
+    pub fn side_effect(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        CALLS.with(|calls| calls.borrow_mut().push(arguments[0].print()));
+        Ok(arguments[0].clone())
+    }
+
+    // the program's output and the side effects it caused
+    fn run(source: &str) -> (String, Vec<String>) {
+        CALLS.with(|calls| calls.borrow_mut().clear());
+        let result = Lox::run_capture(source);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        (result.output, CALLS.with(|calls| calls.take()))
+    }
+
+    #[test]
+    fn and_skips_its_right_side_after_a_falsey_left() {
+        let (output, calls) = run("print false and sideEffect(\"x\");\nprint nil and sideEffect(\"y\");");
+        assert_eq!(output, "false\nnil\n");
+        assert!(calls.is_empty(), "{:?}", calls);
+    }
+
+    #[test]
+    fn and_evaluates_its_right_side_after_a_truthy_left() {
+        let (output, calls) = run("print true and sideEffect(\"x\");");
+        assert_eq!(output, "x\n");
+        assert_eq!(calls, vec!["x"]);
+    }
+
+    #[test]
+    fn or_skips_its_right_side_after_a_truthy_left() {
+        let (output, calls) = run("print true or sideEffect(\"x\");\nprint 0 or sideEffect(\"y\");");
+        assert_eq!(output, "true\n0\n");
+        assert!(calls.is_empty(), "{:?}", calls);
+    }
+
+    #[test]
+    fn or_evaluates_its_right_side_after_a_falsey_left() {
+        let (output, calls) = run("print false or sideEffect(\"x\");\nprint nil or sideEffect(\"y\");");
+        assert_eq!(output, "x\ny\n");
+        assert_eq!(calls, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn chains_stop_at_the_first_deciding_operand() {
+        let (_, calls) = run("sideEffect(\"a\") and sideEffect(false) and sideEffect(\"c\");\nsideEffect(nil) or sideEffect(\"e\") or sideEffect(\"f\");");
+        assert_eq!(calls, vec!["a", "false", "nil", "e"]);
+    }
+
+    #[test]
+    fn logical_operators_return_an_operand() {
+        let (output, _) = run("print 1 and 2, nil or \"d\", false or nil, \"s\" and 0, 1 or 2;");
+        assert_eq!(output, "2 d nil 0 1\n");
+    }
+}
@@ -0,0 +1,211 @@
+use std::rc::Rc;
+
+use crate::ast::{ Expr, Stmt, Value };
+use crate::interpreter::Interpreter;
+use crate::scanner::TokenType;
+
+// replaces operators whose operands are all literals with the literal they evaluate to
+pub fn fold_program(statements: &mut [Stmt]) {
+    let mut folder = Folder { interpreter: Interpreter::new() };
+    for stmt in statements {
+        folder.stmt(stmt);
+    }
+}
+
+// the same folding for a single expression, leaving anything that mentions a variable alone
+pub fn fold_constants(mut expr: Expr) -> Expr {
+    let mut folder = Folder { interpreter: Interpreter::new() };
+    folder.fold_constants(&mut expr);
+    expr
+}
+
+// folding runs the real operators on a scratch interpreter, so a folded result always matches
+// what running the code would give, and anything that would raise an error is left for run time
+struct Folder {
+    interpreter: Interpreter,
+}
+
+impl Folder {
+    fn stmts(&mut self, statements: &mut [Stmt]) {
+        for stmt in statements {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Block { statements } => self.stmts(statements),
+            Stmt::Class { superclass, methods, static_methods, .. } => {
+                if let Some(superclass) = superclass {
+                    self.fold_constants(superclass);
+                }
+                self.stmts(methods);
+                self.stmts(static_methods);
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => (),
+            Stmt::Const { initializer, .. } => self.fold_constants(initializer),
+            Stmt::DoWhile { body, condition, .. } => {
+                self.stmt(body);
+                self.fold_constants(condition);
+            }
+            Stmt::Expression { expression } => self.fold_constants(expression),
+            Stmt::ForEach { iterable, body, .. } => {
+                self.fold_constants(iterable);
+                self.stmt(body);
+            }
+            Stmt::Function { body, .. } => self.stmts(Rc::<Vec<Stmt>>::make_mut(body)),
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.fold_constants(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+                // a literal condition has no side effects, so only the branch it picks needs to stay
+                if let Expr::Literal { value } = condition {
+                    let taken = if self.interpreter.is_truthy(value) {
+                        Some(std::mem::replace(then_branch, Box::new(empty())))
+                    } else {
+                        else_branch.take()
+                    };
+                    *stmt = taken.map_or_else(empty, |branch| *branch);
+                }
+            }
+            Stmt::Match { subject, arms, .. } => {
+                self.fold_constants(subject);
+                for (_, body) in arms {
+                    self.stmt(body);
+                }
+            }
+            Stmt::Print { expressions } => expressions.iter_mut().for_each(|expr| self.fold_constants(expr)),
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.fold_constants(value);
+                }
+            }
+            Stmt::Switch { subject, cases, default } => {
+                self.fold_constants(subject);
+                for (value, body) in cases {
+                    self.fold_constants(value);
+                    self.stmts(body);
+                }
+                if let Some(default) = default {
+                    self.stmts(default);
+                }
+            }
+            Stmt::Throw { value, .. } => self.fold_constants(value),
+            Stmt::Try { body, handler, .. } => {
+                self.stmts(body);
+                self.stmts(handler);
+            }
+            Stmt::Var { initializer, .. } => {
+                if let Some(initializer) = initializer {
+                    self.fold_constants(initializer);
+                }
+            }
+            Stmt::VarUnpack { initializer, .. } => self.fold_constants(initializer),
+            Stmt::While { condition, body, increment, .. } => {
+                self.fold_constants(condition);
+                if let Expr::Literal { value } = condition {
+                    if !self.interpreter.is_truthy(value) {
+                        *stmt = empty();
+                        return;
+                    }
+                }
+                self.stmt(body);
+                if let Some(increment) = increment {
+                    self.fold_constants(increment);
+                }
+            }
+        }
+    }
+
+    fn fold_constants(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Assign { value, .. } => self.fold_constants(value),
+            Expr::Binary { left, right, .. } => {
+                self.fold_constants(left);
+                self.fold_constants(right);
+                if is_literal(left) && is_literal(right) {
+                    self.fold(expr);
+                }
+            }
+            Expr::Block { statements, final_expr } => {
+                self.stmts(statements);
+                self.fold_constants(final_expr);
+            }
+            Expr::Call { callee, arguments, keywords, .. } => {
+                self.fold_constants(callee);
+                arguments.iter_mut().for_each(|argument| self.fold_constants(argument));
+                keywords.iter_mut().for_each(|(_, argument)| self.fold_constants(argument));
+            }
+            Expr::Comma { exprs } => exprs.iter_mut().for_each(|expr| self.fold_constants(expr)),
+            Expr::Get { object, .. } | Expr::GetOptional { object, .. } => self.fold_constants(object),
+            Expr::Grouping { expression } => {
+                self.fold_constants(expression);
+                if is_literal(expression) {
+                    let literal = std::mem::replace(expression.as_mut(), Expr::Literal { value: Value::Nil });
+                    *expr = literal;
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.fold_constants(object);
+                self.fold_constants(index);
+            }
+            Expr::List { elements, .. } => elements.iter_mut().for_each(|element| self.fold_constants(element)),
+            Expr::Logical { left, right, .. } => {
+                self.fold_constants(left);
+                self.fold_constants(right);
+            }
+            Expr::Set { object, value, .. } => {
+                self.fold_constants(object);
+                self.fold_constants(value);
+            }
+            Expr::Tuple { elements, .. } => elements.iter_mut().for_each(|element| self.fold_constants(element)),
+            Expr::Unpack { value, .. } => self.fold_constants(value),
+            Expr::Unary { right, .. } => {
+                self.fold_constants(right);
+                if is_literal(right) {
+                    self.fold(expr);
+                }
+            }
+            Expr::Literal { .. } | Expr::Super { .. } | Expr::This { .. } | Expr::Update { .. } | Expr::Variable { .. } => (),
+        }
+    }
+
+    // repeating a string is left to run time, since the result could be far larger than the source,
+    // and so is dividing by zero, whether that would fail or give an infinity
+    fn fold(&mut self, expr: &mut Expr) {
+        if is_division_by_zero(expr) {
+            return;
+        }
+        match self.interpreter.evaluate(expr) {
+            Ok(Value::String(string)) if !is_repetition(expr) => *expr = Expr::Literal { value: Value::String(string) },
+            Ok(value @ (Value::Number(_) | Value::Int(_) | Value::Boolean(_) | Value::Nil)) => {
+                *expr = Expr::Literal { value };
+            }
+            _ => (),
+        }
+    }
+}
+
+fn empty() -> Stmt {
+    Stmt::Block { statements: Vec::new() }
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal { .. })
+}
+
+fn is_repetition(expr: &Expr) -> bool {
+    matches!(expr, Expr::Binary { operator, .. } if operator.token_type == TokenType::Star)
+}
+
+fn is_division_by_zero(expr: &Expr) -> bool {
+    match expr {
+        Expr::Binary { operator, right, .. } => {
+            matches!(operator.token_type, TokenType::Slash | TokenType::TildeSlash | TokenType::Percent)
+                && matches!(right.as_ref(), Expr::Literal { value: Value::Int(0) } | Expr::Literal { value: Value::Number(0.0) })
+        }
+        _ => false,
+    }
+}
@@ -1,43 +1,234 @@
+use std::fmt;
+use std::rc::Rc;
+
 use crate::Lox;
-use crate::scanner::{Token, TokenType};
-use crate::ast::{ Expr, Value, Stmt, AstPrinter };
+use crate::error::Diagnostic;
+use crate::scanner::{self, Token, TokenType};
+use crate::ast::{ Expr, Pattern, Span, Value, Stmt };
+
+// the default limit, counting statements and expressions together: deep enough that ordinary nested blocks,
+// ifs and expressions don't reach it, shallow enough that a debug build can't overflow an 8 MB stack; the
+// command line raises it for the bigger stack it runs on, and `with_max_nesting` sets it for any other caller
+const MAX_NESTING: usize = 128;
+
+// a syntax error, both as plain fields and rendered with the offending source line
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub diagnostic: Diagnostic,
+    // the source line and a caret under the token, or empty when the line isn't known
+    snippet: String,
+}
+
+impl ParseError {
+    pub fn render(&self, color: bool) -> String {
+        format!("{}{}", self.diagnostic.render(color), self.snippet)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
 
 pub struct Parser {
     tokens: Vec<Token>,
+    lines: Vec<String>,
     current: usize,
-    pub had_error: bool,
+    in_static_method: bool,
+    incomplete: bool,
+    depth: usize,
+    // how deeply statements and expressions may nest before parsing gives up
+    max_nesting: usize,
+    // labels of the loops enclosing the statement being parsed, innermost last
+    loops: Vec<Option<String>>,
+    // one for each statement parse returns
+    spans: Vec<Span>,
+    // the tab width the tokens' columns were counted with, for finding where to put the caret
+    tab_width: u32,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(mut tokens: Vec<Token>, source: &str) -> Self {
+        // tokens that don't come from the scanner may be missing the EoF everything else relies on
+        if tokens.last().is_none_or(|token| token.token_type != TokenType::EoF) {
+            let (line, column) = tokens.last().map_or((1, 1), |token| (token.line, token.column + token.lexeme.chars().count() as u32));
+            tokens.push(Token::new(TokenType::EoF, String::new(), line, column));
+        }
         Self {
             tokens,
+            lines: scanner::source_lines(source),
             current: 0,
-            had_error: false,
+            in_static_method: false,
+            incomplete: false,
+            depth: 0,
+            max_nesting: MAX_NESTING,
+            loops: Vec::new(),
+            spans: Vec::new(),
+            tab_width: 1,
         }
-    } 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, String> {
+    }
+
+    // should match the width given to the scanner that produced the tokens
+    pub fn with_tab_width(mut self, width: u32) -> Self {
+        self.tab_width = width;
+        self
+    }
+
+    // a deeper limit needs a bigger stack, both here and for running what was parsed
+    pub fn with_max_nesting(mut self, max_nesting: usize) -> Self {
+        self.max_nesting = max_nesting;
+        self
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let (statements, errors) = self.parse_recovering();
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
+    }
+
+    // the statements that parsed alongside the errors for the ones that didn't, so the rest of a
+    // broken program can still be checked
+    pub fn parse_recovering(&mut self) -> (Vec<Stmt>, Vec<ParseError>) {
         let mut statements = Vec::new();
+        let mut errors = Vec::new();
         while !self.is_at_end() {
+            let start_line = self.peek().line;
             match self.declaration() {
-                Ok(decl) => statements.push(decl),
-                Err(_) => {
-                    self.had_error = true;
+                Ok(decl) => {
+                    self.spans.push(Span { start_line, end_line: self.previous().line });
+                    statements.push(decl);
+                }
+                Err(err) => {
+                    // more input could still fix a first error that ran into the end
+                    if errors.is_empty() && self.is_at_end() {
+                        self.incomplete = true;
+                    }
+                    errors.push(err);
                     self.synchronize();
                 }
             }
         }
-        Ok(statements)
+        (statements, errors)
+    }
+
+    // a single expression with nothing after it, for evaluating input that isn't a whole statement
+    pub fn parse_expression(&mut self) -> Result<Expr, ParseError> {
+        let result = self.expression().and_then(|expr| match self.is_at_end() {
+            true => Ok(expr),
+            false => Err(self.parse_error(self.peek(), String::from("Expect end of expression."))),
+        });
+        if result.is_err() && self.is_at_end() {
+            self.incomplete = true;
+        }
+        result
+    }
+
+    // where the statements parse returned sit in the source
+    pub fn into_spans(self) -> Vec<Span> {
+        self.spans
     }
 
-    fn declaration(&mut self) -> Result<Stmt, String> {
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    // blocks, function bodies and block expressions all recurse through here, so they count towards the nesting limit
+    fn declaration(&mut self) -> Result<Stmt, ParseError> {
+        self.nested(Parser::unguarded_declaration)
+    }
+
+    fn unguarded_declaration(&mut self) -> Result<Stmt, ParseError> {
+        if self.match_token(vec![TokenType::Class]) {
+            return self.class_declaration();
+        }
+        if self.match_token(vec![TokenType::Fun]) {
+            return self.function("function");
+        }
+        if self.match_token(vec![TokenType::Const]) {
+            return self.const_declaration();
+        }
         if self.match_token(vec![TokenType::Var]) {
             return self.var_declaration();
         }
-        self.statement()
+        self.unguarded_statement()
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, String> {
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.consume(TokenType::Identifier, String::from("Expect class name."))?.clone();
+
+        let superclass = if self.match_token(vec![TokenType::Less]) {
+            self.consume(TokenType::Identifier, String::from("Expect superclass name."))?;
+            Some(Expr::Variable { name: self.previous().clone() })
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, String::from("Expect '{' before class body."))?;
+
+        let mut methods = Vec::new();
+        let mut static_methods = Vec::new();
+        let enclosing_static = self.in_static_method;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            // a leading `class` marks a method that lives on the class object itself
+            let is_static = self.match_token(vec![TokenType::Class]);
+            self.in_static_method = is_static;
+            let method = self.function("method");
+            self.in_static_method = enclosing_static;
+            if is_static {
+                static_methods.push(method?);
+            } else {
+                methods.push(method?);
+            }
+        }
+
+        self.consume(TokenType::RightBrace, String::from("Expect '}' after class body."))?;
+        Ok(Stmt::Class { name, superclass, methods, static_methods })
+    }
+
+    fn function(&mut self, kind: &str) -> Result<Stmt, ParseError> {
+        let name = self.consume(TokenType::Identifier, format!("Expect {} name.", kind))?.clone();
+        self.consume(TokenType::LeftParen, format!("Expect '(' after {} name.", kind))?;
+
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= 255 {
+                    return Err(self.parse_error(self.peek(), String::from("Can't have more than 255 parameters.")));
+                }
+                params.push(self.consume(TokenType::Identifier, String::from("Expect parameter name."))?.clone());
+                if !self.match_token(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, String::from("Expect ')' after parameters."))?;
+
+        self.consume(TokenType::LeftBrace, format!("Expect '{{' before {} body.", kind))?;
+        // a function body can't break out of loops around the declaration
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        let body = self.block();
+        self.loops = enclosing_loops;
+        let body = Rc::new(body?);
+        Ok(Stmt::Function { name, params, body })
+    }
+
+    fn const_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.consume(TokenType::Identifier, String::from("Expect constant name."))?.clone();
+        self.consume(TokenType::Equal, String::from("Expect '=' after constant name."))?;
+        let initializer = self.expression()?;
+
+        self.consume(TokenType::Semicolon, String::from("Expect ';' after constant declaration."))?;
+        Ok(Stmt::Const { name, initializer })
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        if self.match_token(vec![TokenType::LeftParen]) {
+            return self.var_unpack();
+        }
         let name = self.consume(TokenType::Identifier, String::from("Expect variable name."))?.clone();
 
         let initializer = if self.match_token(vec![TokenType::Equal]) {
@@ -50,56 +241,487 @@ impl Parser {
         Ok(Stmt::Var { name, initializer })
     }
 
-    fn statement(&mut self) -> Result<Stmt, String> {
+    // nested patterns aren't supported, so every element is a plain name
+    fn var_unpack(&mut self) -> Result<Stmt, ParseError> {
+        let paren = self.previous().clone();
+        let mut names = Vec::new();
+        loop {
+            names.push(self.consume(TokenType::Identifier, String::from("Expect variable name."))?.clone());
+            if !self.match_token(vec![TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightParen, String::from("Expect ')' after variable names."))?;
+        self.consume(TokenType::Equal, String::from("Expect '=' after destructuring pattern."))?;
+        let initializer = self.expression()?;
+        self.consume(TokenType::Semicolon, String::from("Expect ';' after variable declaration."))?;
+        Ok(Stmt::VarUnpack { paren, names, initializer })
+    }
+
+    // if, loops and blocks all recurse through here, so statements count towards the nesting limit too
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        self.nested(Parser::unguarded_statement)
+    }
+
+    fn unguarded_statement(&mut self) -> Result<Stmt, ParseError> {
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::Colon) {
+            let label = self.advance().clone();
+            self.advance();
+            return self.loop_statement(Some(label));
+        }
+        if self.match_token(vec![TokenType::Break, TokenType::Continue]) {
+            return self.jump_statement();
+        }
+        if self.match_token(vec![TokenType::Do]) {
+            return self.do_while_statement(None);
+        }
+        if self.match_token(vec![TokenType::For]) {
+            return self.for_statement(None);
+        }
+        if self.match_token(vec![TokenType::If]) {
+            return self.if_statement();
+        }
+        if self.match_token(vec![TokenType::Unless]) {
+            return self.unless_statement();
+        }
         if self.match_token(vec![TokenType::Print]) {
             return self.print_statement();
         }
+        if self.match_token(vec![TokenType::Return]) {
+            return self.return_statement();
+        }
+        if self.match_token(vec![TokenType::Match]) {
+            return self.match_statement();
+        }
+        if self.match_token(vec![TokenType::Switch]) {
+            return self.switch_statement();
+        }
+        if self.match_token(vec![TokenType::Throw]) {
+            return self.throw_statement();
+        }
+        if self.match_token(vec![TokenType::Try]) {
+            return self.try_statement();
+        }
+        if self.match_token(vec![TokenType::While]) {
+            return self.while_statement(None);
+        }
+        if self.match_token(vec![TokenType::LeftBrace]) {
+            return Ok(Stmt::Block { statements: self.block()? });
+        }
         self.expression_statement()
     }
 
-    fn print_statement(&mut self) -> Result<Stmt, String> {
-        let value = self.expression();
-        self.consume(TokenType::Semicolon, String::from("Expect ';' after value."))?;
-        match value {
-            Ok(val) => Ok(Stmt::Print { expression: val }),
-            Err(_) => Err(String::from("Error printing statement.")) 
+    fn loop_statement(&mut self, label: Option<Token>) -> Result<Stmt, ParseError> {
+        if self.match_token(vec![TokenType::Do]) {
+            return self.do_while_statement(label);
+        }
+        if self.match_token(vec![TokenType::For]) {
+            return self.for_statement(label);
+        }
+        if self.match_token(vec![TokenType::While]) {
+            return self.while_statement(label);
+        }
+        Err(self.parse_error(self.peek(), String::from("Expect a loop after label.")))
+    }
+
+    fn loop_body(&mut self, label: &Option<Token>) -> Result<Stmt, ParseError> {
+        self.loops.push(label.as_ref().map(|label| label.lexeme.clone()));
+        let body = self.statement();
+        self.loops.pop();
+        body
+    }
+
+    // `break` and `continue` share everything but the node they build
+    fn jump_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let label = if self.match_token(vec![TokenType::Identifier]) {
+            Some(self.previous().clone())
+        } else {
+            None
+        };
+
+        if self.loops.is_empty() {
+            return Err(self.parse_error(&keyword, format!("Can't use '{}' outside of a loop.", keyword.lexeme)));
+        }
+        if let Some(label) = &label {
+            if !self.loops.iter().any(|name| name.as_deref() == Some(label.lexeme.as_str())) {
+                return Err(self.parse_error(label, format!("Undefined label '{}'.", label.lexeme)));
+            }
         }
+        self.consume(TokenType::Semicolon, format!("Expect ';' after '{}'.", keyword.lexeme))?;
+
+        if keyword.token_type == TokenType::Break {
+            Ok(Stmt::Break { keyword, label })
+        } else {
+            Ok(Stmt::Continue { keyword, label })
+        }
+    }
+
+    fn do_while_statement(&mut self, label: Option<Token>) -> Result<Stmt, ParseError> {
+        let body = Box::new(self.loop_body(&label)?);
+        self.consume(TokenType::While, String::from("Expect 'while' after do body."))?;
+        self.consume(TokenType::LeftParen, String::from("Expect '(' after 'while'."))?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, String::from("Expect ')' after condition."))?;
+        self.consume(TokenType::Semicolon, String::from("Expect ';' after do-while condition."))?;
+
+        Ok(Stmt::DoWhile { body, condition, label })
     }
 
-    fn expression_statement(&mut self) -> Result<Stmt, String> {
-        let value = self.expression();
+    fn for_each_statement(&mut self, label: Option<Token>) -> Result<Stmt, ParseError> {
+        let var_name = self.advance().clone();
+        self.advance();
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, String::from("Expect ')' after for-in clause."))?;
+        let body = Box::new(self.loop_body(&label)?);
+
+        Ok(Stmt::ForEach { var_name, iterable, body, label })
+    }
+
+    // desugars into a while loop wrapped in blocks for the initializer and increment
+    fn for_statement(&mut self, label: Option<Token>) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, String::from("Expect '(' after 'for'."))?;
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::In) {
+            return self.for_each_statement(label);
+        }
+
+        let initializer = if self.match_token(vec![TokenType::Semicolon]) {
+            None
+        } else if self.match_token(vec![TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if !self.check(TokenType::Semicolon) {
+            self.expression()?
+        } else {
+            Expr::Literal { value: Value::Boolean(true) }
+        };
+        self.consume(TokenType::Semicolon, String::from("Expect ';' after loop condition."))?;
+
+        let increment = if !self.check(TokenType::RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::RightParen, String::from("Expect ')' after for clauses."))?;
+
+        let body = self.loop_body(&label)?;
+        let mut body = Stmt::While { condition, body: Box::new(body), increment, label };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block { statements: vec![initializer, body] };
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, String::from("Expect '(' after 'if'."))?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, String::from("Expect ')' after if condition."))?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_token(vec![TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If { condition, then_branch, else_branch })
+    }
+
+    // desugars into an if statement with the condition negated
+    fn unless_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, String::from("Expect '(' after 'unless'."))?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, String::from("Expect ')' after unless condition."))?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_token(vec![TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        let operator = Token::new(TokenType::Bang, String::from("!"), keyword.line, keyword.column);
+        let condition = Expr::Unary { operator, right: Box::new(condition) };
+        Ok(Stmt::If { condition, then_branch, else_branch })
+    }
+
+    // commas here separate values, and `print (a, b);` prints a tuple
+    fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let mut expressions = vec![self.assignment()?];
+        while self.match_token(vec![TokenType::Comma]) {
+            expressions.push(self.assignment()?);
+        }
         self.consume(TokenType::Semicolon, String::from("Expect ';' after value."))?;
-        match value {
-            Ok(val) => Ok(Stmt::Expression { expression: val }),
-            Err(_) => Err(String::from("Error evaluating expression statement."))?,
+        Ok(Stmt::Print { expressions })
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let value = if !self.check(TokenType::Semicolon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Semicolon, String::from("Expect ';' after return value."))?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn match_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let subject = self.expression()?;
+        self.consume(TokenType::LeftBrace, String::from("Expect '{' after match subject."))?;
+
+        let mut arms = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some((Pattern::Wildcard, _)) = arms.last() {
+                return Err(self.parse_error(self.peek(), String::from("The '_' arm must come last.")));
+            }
+            let pattern = self.pattern()?;
+            self.consume(TokenType::FatArrow, String::from("Expect '=>' after pattern."))?;
+            arms.push((pattern, self.statement()?));
         }
+
+        self.consume(TokenType::RightBrace, String::from("Expect '}' after match arms."))?;
+        Ok(Stmt::Match { keyword, subject, arms })
     }
 
-    fn expression(&mut self) -> Result<Expr, String> {
-        self.assignment()
+    fn pattern(&mut self) -> Result<Pattern, ParseError> {
+        if self.check(TokenType::Identifier) && self.peek().lexeme == "_" {
+            self.advance();
+            return Ok(Pattern::Wildcard);
+        }
+
+        let negate = self.match_token(vec![TokenType::Minus]);
+        match self.primary()? {
+            Expr::Literal { value: Value::Number(num) } if negate => Ok(Pattern::Literal(Value::Number(-num))),
+            Expr::Literal { value: Value::Int(int) } if negate => Ok(Pattern::Literal(Value::Int(-int))),
+            Expr::Literal { value } if !negate => Ok(Pattern::Literal(value)),
+            _ => Err(self.parse_error(self.previous(), String::from("Expect a literal or '_' as a pattern."))),
+        }
+    }
+
+    // cases never fall through, so each arm just runs until the next label
+    fn switch_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, String::from("Expect '(' after 'switch'."))?;
+        let subject = self.expression()?;
+        self.consume(TokenType::RightParen, String::from("Expect ')' after switch subject."))?;
+        self.consume(TokenType::LeftBrace, String::from("Expect '{' before switch body."))?;
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_token(vec![TokenType::Case]) {
+                let value = self.expression()?;
+                self.consume(TokenType::Colon, String::from("Expect ':' after case value."))?;
+                cases.push((value, self.case_body()?));
+            } else if self.match_token(vec![TokenType::Default]) {
+                if default.is_some() {
+                    return Err(self.parse_error(self.previous(), String::from("Switch can only have one default case.")));
+                }
+                self.consume(TokenType::Colon, String::from("Expect ':' after 'default'."))?;
+                default = Some(self.case_body()?);
+            } else {
+                return Err(self.parse_error(self.peek(), String::from("Expect 'case' or 'default' in switch body.")));
+            }
+        }
+
+        self.consume(TokenType::RightBrace, String::from("Expect '}' after switch body."))?;
+        Ok(Stmt::Switch { subject, cases, default })
+    }
+
+    fn case_body(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = Vec::new();
+        while !self.check(TokenType::Case) && !self.check(TokenType::Default)
+            && !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+        Ok(statements)
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, String::from("Expect ';' after thrown value."))?;
+        Ok(Stmt::Throw { keyword, value })
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftBrace, String::from("Expect '{' after 'try'."))?;
+        let body = self.block()?;
+
+        self.consume(TokenType::Catch, String::from("Expect 'catch' after try block."))?;
+        self.consume(TokenType::LeftParen, String::from("Expect '(' after 'catch'."))?;
+        let name = self.consume(TokenType::Identifier, String::from("Expect error variable name."))?.clone();
+        self.consume(TokenType::RightParen, String::from("Expect ')' after error variable."))?;
+        self.consume(TokenType::LeftBrace, String::from("Expect '{' before catch body."))?;
+        let handler = self.block()?;
+
+        Ok(Stmt::Try { keyword, body, name, handler })
     }
 
-    fn assignment(&mut self) -> Result<Expr, String> {
-        let expr = self.equality()?;
+    fn while_statement(&mut self, label: Option<Token>) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, String::from("Expect '(' after 'while'."))?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, String::from("Expect ')' after condition."))?;
+        let body = Box::new(self.loop_body(&label)?);
+
+        Ok(Stmt::While { condition, body, increment: None, label })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = Vec::new();
+
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+
+        self.consume(TokenType::RightBrace, String::from("Expect '}' after block."))?;
+        Ok(statements)
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, String::from("Expect ';' after value."))?;
+        Ok(Stmt::Expression { expression: value })
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.comma()
+    }
+
+    // argument lists parse assignments directly, so commas there still separate arguments
+    fn comma(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.assignment()?;
+        if !self.check(TokenType::Comma) {
+            return Ok(expr);
+        }
+
+        let mut exprs = vec![expr];
+        while self.match_token(vec![TokenType::Comma]) {
+            exprs.push(self.assignment()?);
+        }
+        Ok(Expr::Comma { exprs })
+    }
+
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.coalesce()?;
 
         if self.match_token(vec![TokenType::Equal]) {
             let equals = self.previous().clone();
-            let value = self.assignment()?;
+            let value = self.nested(Parser::assignment)?;
 
-            match expr.clone() {
+            match expr {
                 Expr::Variable { name } => {
                     return Ok(Expr::Assign { 
                         name, 
                         value: Box::new(value) 
                     });
                 }
-                _ => return Err(String::from("Invalid assignment target."))
+                Expr::Get { object, name } => {
+                    return Ok(Expr::Set {
+                        object,
+                        name,
+                        value: Box::new(value)
+                    });
+                }
+                Expr::Tuple { paren, elements } => {
+                    let mut names = Vec::new();
+                    for element in elements {
+                        match element {
+                            Expr::Variable { name } => names.push(name),
+                            _ => return Err(self.parse_error(&equals, String::from("Invalid assignment target."))),
+                        }
+                    }
+                    return Ok(Expr::Unpack { paren, names, value: Box::new(value) });
+                }
+                _ => return Err(self.parse_error(&equals, String::from("Invalid assignment target.")))
+            };
+        }
+
+        // `x += e` desugars into `x = x + e`
+        if self.match_token(vec![TokenType::PlusEqual, TokenType::MinusEqual, TokenType::StarEqual, TokenType::SlashEqual]) {
+            let compound = self.previous().clone();
+            let value = self.nested(Parser::assignment)?;
+
+            let operator_type = match compound.token_type {
+                TokenType::PlusEqual => TokenType::Plus,
+                TokenType::MinusEqual => TokenType::Minus,
+                TokenType::StarEqual => TokenType::Star,
+                _ => TokenType::Slash,
+            };
+            let operator = Token::new(operator_type, compound.lexeme[..1].to_string(), compound.line, compound.column);
+
+            match expr {
+                Expr::Variable { name } => {
+                    return Ok(Expr::Assign {
+                        name: name.clone(),
+                        value: Box::new(Expr::Binary {
+                            left: Box::new(Expr::Variable { name }),
+                            operator,
+                            right: Box::new(value),
+                        })
+                    });
+                }
+                _ => return Err(self.parse_error(&compound, String::from("Invalid assignment target.")))
             };
         }
         Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, String> {
+    fn coalesce(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.or()?;
+
+        while self.match_token(vec![TokenType::QuestionQuestion]) {
+            let operator = self.previous().clone();
+            let right = self.or()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+
+        while self.match_token(vec![TokenType::Or]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.equality()?;
+
+        while self.match_token(vec![TokenType::And]) {
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.comparison()?;
         
         while self.match_token(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
@@ -116,14 +738,21 @@ impl Parser {
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, String> {
+    // `1 < x < 10` would compare a boolean with a number, so a second operator is rejected outright
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let comparisons = vec![TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual];
         let mut expr = self.term()?;
 
-        while self.match_token(
-            vec![TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual]
-        ) {
+        if self.match_token(comparisons.clone()) {
             let operator = self.previous().clone();
             let right = self.term()?;
+            if comparisons.contains(&self.peek().token_type) {
+                let message = format!(
+                    "Comparisons can't be chained. Use 'and' instead, as in 'a {} b and b {} c'.",
+                    operator.lexeme, self.peek().lexeme,
+                );
+                return Err(self.parse_error(self.peek(), message));
+            }
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
@@ -133,7 +762,7 @@ impl Parser {
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, String> {
+    fn term(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.factor()?;
 
         while self.match_token(
@@ -150,11 +779,11 @@ impl Parser {
         Ok(expr)
     }
 
-    fn factor(&mut self) -> Result<Expr, String> {
+    fn factor(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.unary()?;
 
         while self.match_token(
-            vec![TokenType::Slash, TokenType::Star]
+            vec![TokenType::Slash, TokenType::TildeSlash, TokenType::Percent, TokenType::Star]
         ) {
             let operator = self.previous().clone();
             let right = self.unary()?;
@@ -167,19 +796,154 @@ impl Parser {
         Ok(expr)
     } 
 
-    fn unary(&mut self) -> Result<Expr, String> {
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.match_token(vec![TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+            let target = self.nested(Parser::unary)?;
+            // `--5` can't be a decrement, so it reads as two negations the way `- -5` does
+            if operator.token_type == TokenType::MinusMinus && !matches!(target, Expr::Variable { .. }) {
+                let minus = |column| Token::new(TokenType::Minus, String::from("-"), operator.line, column);
+                let inner = Expr::Unary { operator: minus(operator.column + 1), right: Box::new(target) };
+                return Ok(Expr::Unary { operator: minus(operator.column), right: Box::new(inner) });
+            }
+            return self.update(target, operator, true);
+        }
         if self.match_token(vec![TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous().clone();
-            let right = self.unary()?;
+            let right = self.nested(Parser::unary)?;
             return Ok(Expr::Unary {
                 operator,
                 right: Box::new(right)
             })
         }
-        self.primary()
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.primary()?;
+        self.postfix(expr)
+    }
+
+    fn postfix(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
+        loop {
+            if self.match_token(vec![TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(vec![TokenType::Dot]) {
+                let name = self.consume(TokenType::Identifier, String::from("Expect property name after '.'."))?.clone();
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else if self.match_token(vec![TokenType::LeftBracket]) {
+                let index = self.nested(Parser::assignment)?;
+                let bracket = self.consume(TokenType::RightBracket, String::from("Expect ']' after index."))?.clone();
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                };
+            } else if self.match_token(vec![TokenType::QuestionDot]) {
+                let name = self.consume(TokenType::Identifier, String::from("Expect property name after '?.'."))?.clone();
+                expr = Expr::GetOptional {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else {
+                break;
+            }
+        }
+
+        if self.match_token(vec![TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+            return self.update(expr, operator, false);
+        }
+        Ok(expr)
+    }
+
+    // wraps every place an expression or statement recurses into another one
+    fn nested<T>(&mut self, parse: fn(&mut Parser) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        if self.depth >= self.max_nesting {
+            return Err(self.parse_error(self.peek(), String::from("Nesting too deep.")));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn update(&self, target: Expr, operator: Token, prefix: bool) -> Result<Expr, ParseError> {
+        match target {
+            Expr::Variable { name } => Ok(Expr::Update { name, operator, prefix }),
+            _ => Err(self.parse_error(&operator, String::from("Invalid increment target."))),
+        }
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let mut arguments = Vec::new();
+        let mut keywords = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() + keywords.len() >= 255 {
+                    return Err(self.parse_error(self.peek(), String::from("Can't have more than 255 arguments.")));
+                }
+                if self.check(TokenType::Identifier) && self.check_next(TokenType::Colon) {
+                    let label = self.advance().clone();
+                    self.advance();
+                    keywords.push((label, self.nested(Parser::assignment)?));
+                } else if !keywords.is_empty() {
+                    return Err(self.parse_error(self.peek(), String::from("Positional arguments must come before keyword arguments.")));
+                } else {
+                    arguments.push(self.nested(Parser::assignment)?);
+                }
+                // one trailing comma is allowed before the ')'
+                if !self.match_token(vec![TokenType::Comma]) || self.check(TokenType::RightParen) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParen, String::from("Expect ')' after arguments."))?.clone();
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+            keywords,
+        })
+    }
+
+    // statements must end in ';', so the first expression left before the '}' is the block's value
+    fn block_expression(&mut self) -> Result<Expr, ParseError> {
+        let mut statements = Vec::new();
+        loop {
+            let starts_statement = matches!(self.peek().token_type,
+                TokenType::Class | TokenType::Fun | TokenType::Var | TokenType::Const | TokenType::For
+                | TokenType::If | TokenType::Unless | TokenType::Print | TokenType::Return | TokenType::While | TokenType::Do
+                | TokenType::Switch | TokenType::Match | TokenType::Throw | TokenType::Try | TokenType::Break | TokenType::Continue | TokenType::LeftBrace);
+            if starts_statement {
+                statements.push(self.declaration()?);
+                continue;
+            }
+
+            let expression = self.expression()?;
+            if self.match_token(vec![TokenType::RightBrace]) {
+                return Ok(Expr::Block { statements, final_expr: Box::new(expression) });
+            }
+            self.consume(TokenType::Semicolon, String::from("Expect ';' or '}' after expression."))?;
+            statements.push(Stmt::Expression { expression });
+        }
     }
 
-    fn primary(&mut self) -> Result<Expr, String> {
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        if self.match_token(vec![TokenType::LeftBracket]) {
+            return self.list();
+        }
+        if self.match_token(vec![TokenType::LeftBrace]) {
+            // a value is still owed once the block starts, so jumping out of it isn't allowed
+            let enclosing_loops = std::mem::take(&mut self.loops);
+            let block = self.block_expression();
+            self.loops = enclosing_loops;
+            return block;
+        }
         if self.match_token(vec![TokenType::False]) {
             return Ok(Expr::Literal { value: Value::Boolean(false) })
         }
@@ -190,26 +954,81 @@ impl Parser {
             return Ok(Expr::Literal { value: Value::Nil })
         }
         if self.match_token(vec![TokenType::NumberLiteral]) {
-            return Ok(Expr::Literal { 
-                value: Value::Number(self.previous().lexeme.parse::<f64>().unwrap())
-            })
+            // whole literals too large for an Int fall back to a Number
+            let lexeme = &self.previous().lexeme;
+            let value = match lexeme.parse::<i64>() {
+                Ok(int) if !lexeme.contains('.') => Value::Int(int),
+                _ => Value::Number(lexeme.parse::<f64>().unwrap()),
+            };
+            return Ok(Expr::Literal { value })
         }
         if self.match_token(vec![TokenType::StringLiteral]) {
+            // the lexeme still carries its surrounding quotes and escapes
+            return Ok(Expr::Literal {
+                value: Value::String(scanner::string_value(&self.previous().lexeme))
+            })
+        }
+        if self.match_token(vec![TokenType::BytesLiteral]) {
             return Ok(Expr::Literal {
-                value: Value::String(self.previous().lexeme.clone())
+                value: Value::Bytes(Rc::new(scanner::bytes_value(&self.previous().lexeme)))
             })
         }
+        if self.match_token(vec![TokenType::Super]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::Dot, String::from("Expect '.' after 'super'."))?;
+            let method = self.consume(TokenType::Identifier, String::from("Expect superclass method name."))?.clone();
+            return Ok(Expr::Super { keyword, method })
+        }
+        if self.match_token(vec![TokenType::This]) {
+            if self.in_static_method {
+                return Err(self.parse_error(self.previous(), String::from("Can't use 'this' in a static method.")));
+            }
+            return Ok(Expr::This { keyword: self.previous().clone() })
+        }
         if self.match_token(vec![TokenType::Identifier]) {
             return Ok(Expr::Variable { name: self.previous().clone() })
         }
         if self.match_token(vec![TokenType::LeftParen]) {
-            let expr = self.expression()?;  
-            self.consume(TokenType::RightParen, String::from("Expect ')' after expression."))?;
-            return Ok(Expr::Grouping { expression: Box::new(expr) });
+            return self.grouping();
         };
         Err(self.parse_error(self.peek(), "Expect expression.".to_string()))
     }
 
+    // kept out of `primary` so the frame every nested paren passes through stays small
+    fn grouping(&mut self) -> Result<Expr, ParseError> {
+        let paren = self.previous().clone();
+        let expr = self.nested(Parser::assignment)?;
+        // a comma after the first element makes this a tuple rather than a grouping
+        if self.match_token(vec![TokenType::Comma]) {
+            let mut elements = vec![expr];
+            while !self.check(TokenType::RightParen) {
+                elements.push(self.nested(Parser::assignment)?);
+                if !self.match_token(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, String::from("Expect ')' after tuple elements."))?;
+            return Ok(Expr::Tuple { paren, elements });
+        }
+        self.consume(TokenType::RightParen, String::from("Expect ')' after expression."))?;
+        Ok(Expr::Grouping { expression: Box::new(expr) })
+    }
+
+    fn list(&mut self) -> Result<Expr, ParseError> {
+        let bracket = self.previous().clone();
+        let mut elements = Vec::new();
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                elements.push(self.nested(Parser::assignment)?);
+                if !self.match_token(vec![TokenType::Comma]) || self.check(TokenType::RightBracket) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightBracket, String::from("Expect ']' after list elements."))?;
+        Ok(Expr::List { bracket, elements })
+    }
+
     fn match_token(&mut self, types: Vec<TokenType>) -> bool {
        for t in types {
             if self.check(t) {
@@ -220,18 +1039,40 @@ impl Parser {
         false
     }
 
-    fn consume(&mut self, t: TokenType, message: String) -> Result<&Token, String> {
+    fn consume(&mut self, t: TokenType, message: String) -> Result<&Token, ParseError> {
         match self.check(t) {
             true => Ok(self.advance()),
             false => {
-                println!("{}", self.parse_error(self.peek(), message.clone()));
+                let found = match self.peek().token_type {
+                    TokenType::EoF => TokenType::EoF.to_string(),
+                    _ => format!("'{}'", self.peek().lexeme),
+                };
+                let message = format!("{}, found {}.", message.trim_end_matches('.'), found);
                 Err(self.parse_error(self.peek(), message))
             }
         }
     }
 
-    fn parse_error(&self, token: &Token, message: String) -> String {
-        Lox::error(token, message)
+    // renders the offending source line with a caret under the token
+    fn parse_error(&self, token: &Token, message: String) -> ParseError {
+        let diagnostic = Lox::error(token, message);
+        let mut snippet = String::new();
+        // a hand-built token can claim line 0, which has no source line to show
+        let line = (token.line as usize).checked_sub(1).and_then(|index| self.lines.get(index));
+        if let Some(line) = line {
+            // keep tabs so the caret lines up however the terminal renders them
+            let mut indent = String::new();
+            let mut column = 1;
+            for c in line.chars() {
+                if column >= token.column {
+                    break;
+                }
+                indent.push(if c == '\t' { '\t' } else { ' ' });
+                column += if c == '\t' { self.tab_width } else { 1 };
+            }
+            snippet.push_str(&format!("\n    {}\n    {}^", line, indent));
+        }
+        ParseError { diagnostic, snippet }
     }
 
     fn synchronize(&mut self) {
@@ -244,8 +1085,15 @@ impl Parser {
                 TokenType::Class => return,
                 TokenType::Fun => return,
                 TokenType::Var => return,
+                TokenType::Const => return,
                 TokenType::For => return,
+                TokenType::Do => return,
                 TokenType::If => return,
+                TokenType::Unless => return,
+                TokenType::Switch => return,
+                TokenType::Throw => return,
+                TokenType::Try => return,
+                TokenType::Match => return,
                 TokenType::While => return,
                 TokenType::Print => return,
                 TokenType::Return => return,
@@ -261,6 +1109,13 @@ impl Parser {
         self.peek().token_type == t
     }
 
+    fn check_next(&self, t: TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => token.token_type == t,
+            None => false,
+        }
+    }
+
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
@@ -276,11 +1131,20 @@ impl Parser {
     fn peek(&self) -> &Token {
         match self.tokens.get(self.current) {
             Some(token) => token,
-            None => self.tokens.last().unwrap(),
+            None => self.end(),
         }
     }
 
+    // before anything has been consumed there's no previous token, so the EoF stands in for it
     fn previous(&self) -> &Token {
-        self.tokens.get(self.current - 1).unwrap()
+        match self.current.checked_sub(1) {
+            Some(index) => &self.tokens[index],
+            None => self.end(),
+        }
+    }
+
+    // Parser::new makes sure the stream ends in an EoF, so there's always a last token
+    fn end(&self) -> &Token {
+        &self.tokens[self.tokens.len() - 1]
     }
 }
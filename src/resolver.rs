@@ -0,0 +1,352 @@
+use crate::Lox;
+use crate::ast::{ Expr, Stmt, Value };
+use crate::environment::Environment;
+use crate::error::{ Diagnostic, Severity };
+use crate::native;
+use crate::scanner::Token;
+
+// walks a parsed program without running it, reporting what can be known from its scopes alone
+pub fn resolve(statements: &[Stmt]) -> Vec<Diagnostic> {
+    let mut resolver = Resolver::new(None);
+    resolver.stmts(statements);
+    resolver.diagnostics
+}
+
+// like resolve, but also reports names that nothing declares; only a whole program can be checked
+// this way, since the REPL and define_native can add globals after parsing
+pub fn check(statements: &[Stmt]) -> Vec<Diagnostic> {
+    // every top-level declaration counts, since a function can use a global declared after it
+    let mut globals = Environment::new();
+    native::define_globals(&mut globals, true);
+    for name in statements.iter().flat_map(declared_names) {
+        globals.define(name.symbol(), Value::Nil);
+    }
+
+    let mut resolver = Resolver::new(Some(globals));
+    resolver.stmts(statements);
+    for (name, _) in std::mem::take(&mut resolver.pending) {
+        resolver.undefined(&name);
+    }
+    resolver.diagnostics
+}
+
+// the names a statement declares in the scope it's in
+fn declared_names(stmt: &Stmt) -> Vec<&Token> {
+    match stmt {
+        Stmt::Class { name, .. } | Stmt::Const { name, .. } | Stmt::Function { name, .. } | Stmt::Var { name, .. } => vec![name],
+        Stmt::VarUnpack { names, .. } => names.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+struct Local {
+    name: Token,
+    // only `var` and `const` are warned about; parameters and loop variables are often unused on purpose
+    declared_by_var: bool,
+    read: bool,
+}
+
+// the global scope isn't tracked, since any later line or REPL entry could still read its names
+struct Resolver {
+    scopes: Vec<Vec<Local>>,
+    // the kind of function whose body is being resolved
+    function: FunctionType,
+    // the kind of class whose methods are being resolved, for checking `this` and `super`
+    class: ClassType,
+    diagnostics: Vec<Diagnostic>,
+    // the names a checked program can use without declaring them, or None when resolving for a run
+    globals: Option<Environment>,
+    // names a function uses before any open scope declares them, with how many of those scopes
+    // could still declare them before the function is called
+    pending: Vec<(Token, usize)>,
+}
+
+impl Resolver {
+    fn new(globals: Option<Environment>) -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            function: FunctionType::None,
+            class: ClassType::None,
+            diagnostics: Vec::new(),
+            globals,
+            pending: Vec::new(),
+        }
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements } => self.scoped(|resolver| resolver.stmts(statements)),
+            Stmt::Class { name, superclass, methods, static_methods } => {
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable { name: superclass_name } = superclass {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.error(superclass_name, String::from("A class can't inherit from itself."));
+                        }
+                    }
+                    self.expr(superclass);
+                }
+                self.declare(name, false);
+                let class = if superclass.is_some() { ClassType::Subclass } else { ClassType::Class };
+                let enclosing = std::mem::replace(&mut self.class, class);
+                for method in methods.iter().chain(static_methods) {
+                    if let Stmt::Function { params, body, .. } = method {
+                        self.function(params, body);
+                    }
+                }
+                self.class = enclosing;
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => (),
+            Stmt::Const { name, initializer } => {
+                self.expr(initializer);
+                self.declare(name, true);
+            }
+            Stmt::DoWhile { body, condition, .. } => {
+                self.stmt(body);
+                self.expr(condition);
+            }
+            Stmt::Expression { expression } => self.expr(expression),
+            Stmt::ForEach { var_name, iterable, body, .. } => {
+                self.expr(iterable);
+                self.scoped(|resolver| {
+                    resolver.declare(var_name, false);
+                    resolver.stmt(body);
+                });
+            }
+            Stmt::Function { name, params, body } => {
+                // declared first so the function can call itself
+                self.declare(name, false);
+                self.function(params, body);
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            Stmt::Match { subject, arms, .. } => {
+                self.expr(subject);
+                for (_, body) in arms {
+                    self.stmt(body);
+                }
+            }
+            Stmt::Print { expressions } => expressions.iter().for_each(|expr| self.expr(expr)),
+            Stmt::Return { keyword, value } => {
+                if self.function == FunctionType::None {
+                    self.error(keyword, String::from("Can't return from top-level code."));
+                }
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Switch { subject, cases, default } => {
+                self.expr(subject);
+                for (value, body) in cases {
+                    self.expr(value);
+                    self.scoped(|resolver| resolver.stmts(body));
+                }
+                if let Some(default) = default {
+                    self.scoped(|resolver| resolver.stmts(default));
+                }
+            }
+            Stmt::Throw { value, .. } => self.expr(value),
+            Stmt::Try { body, name, handler, .. } => {
+                self.scoped(|resolver| resolver.stmts(body));
+                self.scoped(|resolver| {
+                    resolver.declare(name, false);
+                    resolver.stmts(handler);
+                });
+            }
+            Stmt::Var { name, initializer } => {
+                // the initializer runs before the name exists, so it can only see an outer variable
+                if let Some(initializer) = initializer {
+                    self.expr(initializer);
+                }
+                self.declare(name, true);
+            }
+            Stmt::VarUnpack { names, initializer, .. } => {
+                self.expr(initializer);
+                for name in names {
+                    self.declare(name, true);
+                }
+            }
+            Stmt::While { condition, body, increment, .. } => {
+                self.expr(condition);
+                self.stmt(body);
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign { name, value } => {
+                self.expr(value);
+                self.assign(name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Block { statements, final_expr } => self.scoped(|resolver| {
+                resolver.stmts(statements);
+                resolver.expr(final_expr);
+            }),
+            Expr::Call { callee, arguments, keywords, .. } => {
+                self.expr(callee);
+                arguments.iter().for_each(|argument| self.expr(argument));
+                keywords.iter().for_each(|(_, argument)| self.expr(argument));
+            }
+            Expr::Comma { exprs } => exprs.iter().for_each(|expr| self.expr(expr)),
+            Expr::Get { object, .. } | Expr::GetOptional { object, .. } => self.expr(object),
+            Expr::Grouping { expression } => self.expr(expression),
+            Expr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            Expr::List { elements, .. } => elements.iter().for_each(|element| self.expr(element)),
+            Expr::Set { object, value, .. } => {
+                self.expr(object);
+                self.expr(value);
+            }
+            Expr::Tuple { elements, .. } => elements.iter().for_each(|element| self.expr(element)),
+            Expr::Unary { right, .. } => self.expr(right),
+            Expr::Unpack { names, value, .. } => {
+                self.expr(value);
+                names.iter().for_each(|name| self.assign(name));
+            }
+            // `++` and `--` read the old value, so they count as a use
+            Expr::Update { name, .. } | Expr::Variable { name } => self.read(name),
+            Expr::Super { keyword, .. } => match self.class {
+                ClassType::None => self.error(keyword, String::from("Can't use 'super' outside of a class.")),
+                ClassType::Class => self.error(keyword, String::from("Can't use 'super' in a class with no superclass.")),
+                ClassType::Subclass => (),
+            },
+            Expr::This { keyword } => {
+                if self.class == ClassType::None {
+                    self.error(keyword, String::from("Can't use 'this' outside of a class."));
+                }
+            }
+            Expr::Literal { .. } => (),
+        }
+    }
+
+    // parameters share a scope with the body, just as a call binds them in one environment
+    fn function(&mut self, params: &[Token], body: &[Stmt]) {
+        let enclosing = std::mem::replace(&mut self.function, FunctionType::Function);
+        self.scoped(|resolver| {
+            for param in params {
+                resolver.declare(param, false);
+            }
+            resolver.stmts(body);
+        });
+        self.function = enclosing;
+    }
+
+    fn scoped<F: FnOnce(&mut Resolver)>(&mut self, resolve: F) {
+        self.scopes.push(Vec::new());
+        resolve(self);
+        let mut scope = self.scopes.pop().unwrap_or_default();
+        // a function called after the scope's later declarations sees them too
+        let depth = self.scopes.len();
+        self.pending.retain_mut(|(name, open)| {
+            if *open <= depth {
+                return true;
+            }
+            *open = depth;
+            match scope.iter_mut().rev().find(|local| local.name.symbol() == name.symbol()) {
+                Some(local) => {
+                    local.read = true;
+                    false
+                }
+                None => true,
+            }
+        });
+        for local in scope {
+            if local.declared_by_var && !local.read && local.name.lexeme != "_" {
+                self.warning(&local.name, format!("Unused variable '{}'.", local.name.lexeme));
+            }
+        }
+    }
+
+    // shadowing an outer scope's name is fine, but a second declaration in the same scope is a mistake
+    fn declare(&mut self, name: &Token, declared_by_var: bool) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.iter().any(|local| local.name.symbol() == name.symbol()) {
+            self.error(name, String::from("Already a variable with this name in this scope."));
+            return;
+        }
+        scope.push(Local { name: name.clone(), declared_by_var, read: false });
+    }
+
+    // marks the innermost declaration of the name, leaving globals alone
+    fn read(&mut self, name: &Token) {
+        match self.local(name) {
+            Some(local) => local.read = true,
+            None => self.global(name),
+        }
+    }
+
+    // assigning doesn't count as a use, but the variable still has to exist
+    fn assign(&mut self, name: &Token) {
+        if self.local(name).is_none() {
+            self.global(name);
+        }
+    }
+
+    fn local(&mut self, name: &Token) -> Option<&mut Local> {
+        let symbol = name.symbol();
+        self.scopes.iter_mut().rev().flat_map(|scope| scope.iter_mut().rev()).find(|local| local.name.symbol() == symbol)
+    }
+
+    // a function body only runs once it's called, so its names wait until the scopes around it close
+    fn global(&mut self, name: &Token) {
+        if self.globals.is_none() {
+            return;
+        }
+        match self.function {
+            FunctionType::None => self.undefined(name),
+            FunctionType::Function => self.pending.push((name.clone(), self.scopes.len())),
+        }
+    }
+
+    fn undefined(&mut self, name: &Token) {
+        let Some(globals) = &self.globals else {
+            return;
+        };
+        if globals.get(name).is_err() {
+            self.error(name, format!("Undefined variable '{}'.", name.lexeme));
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: String) {
+        self.diagnostics.push(Lox::error(token, message));
+    }
+
+    fn warning(&mut self, token: &Token, message: String) {
+        self.diagnostics.push(Diagnostic { severity: Severity::Warning, ..Lox::error(token, message) });
+    }
+}
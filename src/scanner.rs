@@ -1,6 +1,11 @@
+use std::fmt;
+use std::io::{ self, BufRead };
 use std::iter::Peekable;
 use std::str::CharIndices;
 
+use crate::error::{ Diagnostic, Severity };
+use crate::symbol::Symbol;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TokenType {
     // Single-character tokens
@@ -8,66 +13,225 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
     Plus,
     Semicolon,
+    Colon,
     Slash,
     Star,
+    Percent,
     // One or two character tokens
+    TildeSlash,
+    Arrow,
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
+    FatArrow,
     Greater,
     GreaterEqual,
     Less,
     LessEqual,
+    PlusEqual,
+    MinusEqual,
+    PlusPlus,
+    MinusMinus,
+    StarEqual,
+    SlashEqual,
+    QuestionQuestion,
+    QuestionDot,
     // Literals
     NumberLiteral,
     StringLiteral,
+    BytesLiteral,
     Identifier,
     // Keywords
     And,
+    Break,
+    Case,
+    Catch,
     Class,
+    Const,
+    Continue,
+    Default,
+    Do,
     Else,
     False,
     For,
     Fun,
     If,
+    In,
+    Match,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
     True,
+    Try,
+    Unless,
     Var,
     While,
     EoF,
-
-    UnexpectedCharacterError,
-    UnterminatedStringError,
 }
 
 impl TokenType {
-    pub fn error_message(t: TokenType) -> Option<&'static str> {
-        match t {
-            Self::UnexpectedCharacterError => Some("Unexpected character."),
-            Self::UnterminatedStringError => Some("Unterminated string."),
-            _ => None,
+    // stable names for tooling, spelled like the book's token types
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LeftParen => "LEFT_PAREN",
+            Self::RightParen => "RIGHT_PAREN",
+            Self::LeftBrace => "LEFT_BRACE",
+            Self::RightBrace => "RIGHT_BRACE",
+            Self::LeftBracket => "LEFT_BRACKET",
+            Self::RightBracket => "RIGHT_BRACKET",
+            Self::Comma => "COMMA",
+            Self::Dot => "DOT",
+            Self::Minus => "MINUS",
+            Self::Plus => "PLUS",
+            Self::Semicolon => "SEMICOLON",
+            Self::Colon => "COLON",
+            Self::Slash => "SLASH",
+            Self::Percent => "PERCENT",
+            Self::TildeSlash => "TILDE_SLASH",
+            Self::Arrow => "ARROW",
+            Self::Star => "STAR",
+            Self::Bang => "BANG",
+            Self::BangEqual => "BANG_EQUAL",
+            Self::Equal => "EQUAL",
+            Self::EqualEqual => "EQUAL_EQUAL",
+            Self::FatArrow => "FAT_ARROW",
+            Self::Greater => "GREATER",
+            Self::GreaterEqual => "GREATER_EQUAL",
+            Self::Less => "LESS",
+            Self::LessEqual => "LESS_EQUAL",
+            Self::PlusEqual => "PLUS_EQUAL",
+            Self::MinusEqual => "MINUS_EQUAL",
+            Self::PlusPlus => "PLUS_PLUS",
+            Self::MinusMinus => "MINUS_MINUS",
+            Self::StarEqual => "STAR_EQUAL",
+            Self::SlashEqual => "SLASH_EQUAL",
+            Self::QuestionQuestion => "QUESTION_QUESTION",
+            Self::QuestionDot => "QUESTION_DOT",
+            Self::NumberLiteral => "NUMBER",
+            Self::StringLiteral => "STRING",
+            Self::BytesLiteral => "BYTES",
+            Self::Identifier => "IDENTIFIER",
+            Self::And => "AND",
+            Self::Break => "BREAK",
+            Self::Case => "CASE",
+            Self::Catch => "CATCH",
+            Self::Class => "CLASS",
+            Self::Const => "CONST",
+            Self::Continue => "CONTINUE",
+            Self::Default => "DEFAULT",
+            Self::Do => "DO",
+            Self::Else => "ELSE",
+            Self::False => "FALSE",
+            Self::For => "FOR",
+            Self::Fun => "FUN",
+            Self::If => "IF",
+            Self::In => "IN",
+            Self::Match => "MATCH",
+            Self::Nil => "NIL",
+            Self::Or => "OR",
+            Self::Print => "PRINT",
+            Self::Return => "RETURN",
+            Self::Super => "SUPER",
+            Self::Switch => "SWITCH",
+            Self::This => "THIS",
+            Self::Throw => "THROW",
+            Self::True => "TRUE",
+            Self::Try => "TRY",
+            Self::Unless => "UNLESS",
+            Self::Var => "VAR",
+            Self::While => "WHILE",
+            Self::EoF => "EOF",
         }
     }
-    pub fn report(line: u32, _where: String, message: &str) {
-        println!("[line {line}] Error {_where} {message}");
-    }
+}
 
-    pub fn error(t: TokenType, line: u32) {
-        let error_message = TokenType::error_message(t); 
-        if let Some(error_message) = error_message {
-            TokenType::report(line, "".to_string(), error_message);
-        }
+// how a token type reads in messages: punctuation and keywords as written, everything else by kind
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TokenType::LeftParen => "(",
+            TokenType::RightParen => ")",
+            TokenType::LeftBrace => "{",
+            TokenType::RightBrace => "}",
+            TokenType::LeftBracket => "[",
+            TokenType::RightBracket => "]",
+            TokenType::Comma => ",",
+            TokenType::Dot => ".",
+            TokenType::Minus => "-",
+            TokenType::Plus => "+",
+            TokenType::Semicolon => ";",
+            TokenType::Colon => ":",
+            TokenType::Slash => "/",
+            TokenType::Percent => "%",
+            TokenType::TildeSlash => "~/",
+            TokenType::Arrow => "->",
+            TokenType::Star => "*",
+            TokenType::Bang => "!",
+            TokenType::BangEqual => "!=",
+            TokenType::Equal => "=",
+            TokenType::EqualEqual => "==",
+            TokenType::FatArrow => "=>",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::PlusEqual => "+=",
+            TokenType::MinusEqual => "-=",
+            TokenType::PlusPlus => "++",
+            TokenType::MinusMinus => "--",
+            TokenType::StarEqual => "*=",
+            TokenType::SlashEqual => "/=",
+            TokenType::QuestionQuestion => "??",
+            TokenType::QuestionDot => "?.",
+            TokenType::NumberLiteral => "number",
+            TokenType::StringLiteral => "string",
+            TokenType::BytesLiteral => "byte string",
+            TokenType::Identifier => "identifier",
+            TokenType::And => "and",
+            TokenType::Break => "break",
+            TokenType::Case => "case",
+            TokenType::Catch => "catch",
+            TokenType::Class => "class",
+            TokenType::Const => "const",
+            TokenType::Continue => "continue",
+            TokenType::Default => "default",
+            TokenType::Do => "do",
+            TokenType::Else => "else",
+            TokenType::False => "false",
+            TokenType::For => "for",
+            TokenType::Fun => "fun",
+            TokenType::If => "if",
+            TokenType::In => "in",
+            TokenType::Match => "match",
+            TokenType::Nil => "nil",
+            TokenType::Or => "or",
+            TokenType::Print => "print",
+            TokenType::Return => "return",
+            TokenType::Super => "super",
+            TokenType::Switch => "switch",
+            TokenType::This => "this",
+            TokenType::Throw => "throw",
+            TokenType::True => "true",
+            TokenType::Try => "try",
+            TokenType::Unless => "unless",
+            TokenType::Var => "var",
+            TokenType::While => "while",
+            TokenType::EoF => "end of input",
+        };
+        write!(f, "{}", name)
     }
 }
 
@@ -76,28 +240,97 @@ pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub line: u32,
+    pub column: u32,
+    symbol: Option<Symbol>,
+}
+
+// positions are left out, so the same code parsed from different places compares equal
+impl PartialEq for Token {
+    fn eq(&self, other: &Token) -> bool {
+        self.token_type == other.token_type && self.lexeme == other.lexeme
+    }
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, line: u32) -> Self {
+    pub fn new(token_type: TokenType, lexeme: String, line: u32, column: u32) -> Self {
+        // only names that are looked up in an environment get interned
+        let symbol = match token_type {
+            TokenType::Identifier | TokenType::This | TokenType::Super => Some(Symbol::intern(&lexeme)),
+            _ => None,
+        };
         Self {
             token_type,
             lexeme,
             line,
+            column,
+            symbol,
+        }
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        self.symbol.unwrap_or_else(|| Symbol::intern(&self.lexeme))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanErrorKind {
+    InvalidEscape,
+    UnexpectedCharacter,
+    UnterminatedString,
+}
+
+impl ScanErrorKind {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ScanErrorKind::InvalidEscape => "Invalid escape sequence.",
+            ScanErrorKind::UnexpectedCharacter => "Unexpected character.",
+            ScanErrorKind::UnterminatedString => "Unterminated string.",
+        }
+    }
+}
+
+// input the scanner skipped because it couldn't be made into a token
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    pub line: u32,
+    pub column: u32,
+    // for an unterminated string, only its opening quotes
+    pub lexeme: String,
+}
+
+impl ScanError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            line: self.line,
+            column: Some(self.column),
+            lexeme: self.lexeme.clone(),
+            message: String::from(self.kind.message()),
+            severity: Severity::Error,
         }
     }
+
+    pub fn render(&self, color: bool) -> String {
+        self.diagnostic().render(color)
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.diagnostic())
+    }
 }
 
 fn is_digit(c: Option<char>) -> bool {
     if let Some(c) = c {
-        return ('0'..='9').contains(&c)
+        return c.is_ascii_digit()
     }
     false
 }
 
 fn is_ident(c: Option<char>) -> bool {
     if let Some(c) = c {
-        return ('A'..='Z').contains(&c) || ('a'..='z').contains(&c) || c == '_';
+        return c.is_ascii_alphabetic() || c == '_';
     }
     false
 }
@@ -110,24 +343,83 @@ fn check_keyword(word: &str, kw: &str, pos: usize, tt: TokenType) -> TokenType {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum StringKind {
+    String { raw: bool },
+    Bytes,
+}
+
 pub struct Scanner<'a> {
     source: &'a str,
     token_start: usize,
     chars: Peekable<CharIndices<'a>>,
     line: u32,
+    line_start: usize,
+    // how wide the line is up to `measured`, so each token only measures what came since the last one
+    measured: usize,
+    measured_width: u32,
+    token_column: u32,
+    // where a token starts, which a string literal can have moved past by the time it fails
+    token_line: u32,
+    errors: Vec<ScanError>,
+    // how many columns a tab counts as
+    tab_width: u32,
+    // where a string literal still open at the end of the source starts, so a ReaderScanner can carry it on
+    unterminated: Option<usize>,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Self {
-        let mut chars = source.char_indices().peekable();
+        // the first token starts at byte 0 whether or not there is one, and empty input goes straight to EoF
         Self {
             source,
-            token_start: chars.peek().map(|(index, _c)| *index).unwrap_or(0),
-            chars,
+            token_start: 0,
+            chars: source.char_indices().peekable(),
             line: 1,
+            line_start: 0,
+            measured: 0,
+            measured_width: 0,
+            token_column: 1,
+            token_line: 1,
+            errors: Vec::new(),
+            tab_width: 1,
+            unterminated: None,
         }
     }
 
+    // for scanning a piece cut out of a larger source, so tokens keep the line numbers they have there
+    pub fn starting_at_line(mut self, line: u32) -> Self {
+        self.line = line;
+        self.token_line = line;
+        self
+    }
+
+    // for a piece that starts partway along its first line
+    fn starting_at_column(mut self, column: u32) -> Self {
+        self.measured_width = column - 1;
+        self
+    }
+
+    // counts each tab as `width` columns, to match an editor that displays them that wide
+    pub fn with_tab_width(mut self, width: u32) -> Self {
+        self.tab_width = width;
+        self
+    }
+
+    // whether every character has been consumed; whitespace and comments can still be left before the EoF
+    pub fn is_at_end(&mut self) -> bool {
+        self.chars.peek().is_none()
+    }
+
+    // the problems found by scan_tokens, in source order
+    pub fn into_errors(self) -> Vec<ScanError> {
+        self.errors
+    }
+
+    fn error(&mut self, kind: ScanErrorKind, lexeme: &str) {
+        self.errors.push(ScanError { kind, line: self.token_line, column: self.token_column, lexeme: lexeme.to_string() });
+    }
+
     fn advance(&mut self) -> Option<char> {
         self.chars.next().map(|(_index, c)| c)
     }
@@ -146,16 +438,12 @@ impl<'a> Scanner<'a> {
     }
 
     fn maybe_match_str(&mut self, expected: &str) -> bool {
-        let strlen = expected.len();
         let byte: usize = match self.chars.peek() {
             None => return false,
             Some((index, _c)) => *index,
         };
-        let end_offset = byte + strlen;
-        if end_offset > self.source.len() {
-            return false;
-        }
-        if expected == &self.source[byte..end_offset] {
+        // comparing by prefix rather than slicing, which could split a multi-byte character
+        if self.source[byte..].starts_with(expected) {
             for _ in 0..expected.chars().count() {
                 self.chars.next();
             }
@@ -173,24 +461,19 @@ impl<'a> Scanner<'a> {
 
     fn skip_whitespace(&mut self) {
         loop {
-            match self.chars.peek() {
-                Some((_, ' ')) | Some((_, '\r')) | Some((_, '\t')) => {
+            match self.chars.peek().copied() {
+                Some((_, ' ')) | Some((_, '\t')) => {
                     self.advance();
                 }
-                Some((_, '\n')) => {
-                    self.line += 1;
-                    self.advance();
+                Some((_, '\r')) | Some((_, '\n')) => {
+                    self.newline();
                 }
-                Some((_, '/')) => {
-                    if self.maybe_match_str("//") {
-                        while let Some((_, c)) = self.chars.peek() {
-                            if *c == '\n' {
-                                break;
-                            }
-                            self.advance();
+                Some((_, '/')) if self.maybe_match_str("//") => {
+                    while let Some((_, c)) = self.chars.peek() {
+                        if *c == '\n' || *c == '\r' {
+                            break;
                         }
-                    } else {
-                        return;
+                        self.advance();
                     }
                 }
                 _ => return,
@@ -198,29 +481,76 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    // \r\n, a lone \r and \n each end exactly one line
+    fn newline(&mut self) {
+        if self.advance() == Some('\r') {
+            self.maybe_match('\n');
+        }
+        self.line += 1;
+        self.line_start = self.current();
+    }
+
     fn content(&mut self) -> &'a str {
         let current = self.current();
         &self.source[self.token_start..current]
     }
 
     fn make_token(&mut self, token_type: TokenType) -> Token {
-        Token::new(token_type, self.content().to_string(), self.line)
+        Token::new(token_type, self.content().to_string(), self.line, self.token_column)
+    }
+
+    // raw strings keep every backslash as written
+    // `delimiter` is `"` or, for strings spanning several lines, `"""`
+    // a malformed string is recorded as an error and gives no token
+    fn string_literal(&mut self, raw: bool, delimiter: &str) -> Option<Token> {
+        self.quoted(StringKind::String { raw }, delimiter)
     }
 
-    fn string_literal(&mut self) -> Token {
+    // `b"`, whose escapes are decoded into bytes rather than characters
+    fn bytes_literal(&mut self) -> Option<Token> {
+        self.quoted(StringKind::Bytes, "\"")
+    }
+
+    fn quoted(&mut self, kind: StringKind, delimiter: &str) -> Option<Token> {
+        let raw = kind == StringKind::String { raw: true };
         loop {
-            match self.chars.peek() {
-                Some((_, '"')) => {
-                    self.advance();
-                    return self.make_token(TokenType::StringLiteral);
+            match self.chars.peek().copied() {
+                Some((_, '"')) if self.maybe_match_str(delimiter) => {
+                    let content = self.content();
+                    let prefix = if kind == StringKind::Bytes { 1 } else { 0 };
+                    let body = &content[prefix + delimiter.len()..content.len() - delimiter.len()];
+                    if !raw && unescape_bytes(body, kind == StringKind::Bytes).is_none() {
+                        self.error(ScanErrorKind::InvalidEscape, content);
+                        return None;
+                    }
+                    let token_type = if kind == StringKind::Bytes { TokenType::BytesLiteral } else { TokenType::StringLiteral };
+                    return Some(self.make_token(token_type));
+                }
+                Some((_, '\r')) | Some((_, '\n')) => {
+                    self.newline();
                 }
-                Some((_, c)) => {
-                    if *c == '\n' {
-                        self.line += 1;
+                // the escaped character can't end the string, but a line break still counts as one
+                Some((_, '\\')) if !raw => {
+                    self.advance();
+                    if let Some((_, c)) = self.chars.peek() {
+                        if *c != '\r' && *c != '\n' {
+                            self.advance();
+                        }
                     }
+                }
+                Some(_) => {
                     self.advance();
                 }
-                None => return self.make_token(TokenType::UnterminatedStringError),
+                None => {
+                    let opening = match kind {
+                        StringKind::String { raw: true } => "r\"",
+                        StringKind::String { raw: false } => delimiter,
+                        StringKind::Bytes => "b\"",
+                    };
+                    self.error(ScanErrorKind::UnterminatedString, opening);
+                    self.unterminated = Some(self.token_start);
+                    return None;
+                }
             }
         }
     }
@@ -236,6 +566,7 @@ impl<'a> Scanner<'a> {
 
     fn number_literal(&mut self) -> Token {
         self.consume_integers();
+        // the dot only belongs to the number when a digit follows, so `3.` and `3.foo` leave it as a Dot token
         let mut ch = self.chars.clone();
         if let Some((_, '.')) = ch.next() {
             if let Some((_, c)) = ch.next() {
@@ -266,7 +597,46 @@ impl<'a> Scanner<'a> {
         }
         match &word[..1] {
             "a" => check_keyword(word, "and", 1, TokenType::And),
-            "c" => check_keyword(word, "class", 1, TokenType::Class),
+            "b" => check_keyword(word, "break", 1, TokenType::Break),
+            "c" => {
+                if word.len() < 2 {
+                    return TokenType::Identifier;
+                }
+                match &word[1..2] {
+                    "a" => {
+                        if word.len() < 3 {
+                            return TokenType::Identifier;
+                        }
+                        match &word[2..3] {
+                            "s" => check_keyword(word, "case", 2, TokenType::Case),
+                            "t" => check_keyword(word, "catch", 2, TokenType::Catch),
+                            _ => TokenType::Identifier,
+                        }
+                    }
+                    "l" => check_keyword(word, "class", 2, TokenType::Class),
+                    "o" => {
+                        if word.len() < 4 {
+                            return TokenType::Identifier;
+                        }
+                        match &word[3..4] {
+                            "s" => check_keyword(word, "const", 2, TokenType::Const),
+                            "t" => check_keyword(word, "continue", 2, TokenType::Continue),
+                            _ => TokenType::Identifier,
+                        }
+                    }
+                    _ => TokenType::Identifier,
+                }
+            }
+            "d" => {
+                if word.len() < 2 {
+                    return TokenType::Identifier;
+                }
+                match &word[1..2] {
+                    "e" => check_keyword(word, "default", 2, TokenType::Default),
+                    "o" => check_keyword(word, "do", 2, TokenType::Do),
+                    _ => TokenType::Identifier,
+                }
+            }
             "e" => check_keyword(word, "else", 1, TokenType::Else),
             "f" => {
                 if word.len() < 2 {
@@ -279,66 +649,169 @@ impl<'a> Scanner<'a> {
                     _ => TokenType::Identifier,
                 }
             }
-            "i" => check_keyword(word, "if", 1, TokenType::If),
+            "i" => {
+                if word.len() < 2 {
+                    return TokenType::Identifier;
+                }
+                match &word[1..2] {
+                    "f" => check_keyword(word, "if", 2, TokenType::If),
+                    "n" => check_keyword(word, "in", 2, TokenType::In),
+                    _ => TokenType::Identifier,
+                }
+            }
+            "m" => check_keyword(word, "match", 1, TokenType::Match),
             "n" => check_keyword(word, "nil", 1, TokenType::Nil),
             "o" => check_keyword(word, "or", 1, TokenType::Or),
             "p" => check_keyword(word, "print", 1, TokenType::Print),
             "r" => check_keyword(word, "return", 1, TokenType::Return),
-            "s" => check_keyword(word, "super", 1, TokenType::Super),
+            "s" => {
+                if word.len() < 2 {
+                    return TokenType::Identifier;
+                }
+                match &word[1..2] {
+                    "u" => check_keyword(word, "super", 2, TokenType::Super),
+                    "w" => check_keyword(word, "switch", 2, TokenType::Switch),
+                    _ => TokenType::Identifier,
+                }
+            }
             "t" => {
                 if word.len() < 2 {
                     return TokenType::Identifier;
                 }
                 match &word[1..2] {
-                    "h" => check_keyword(word, "this", 2, TokenType::This),
-                    "r" => check_keyword(word, "true", 2, TokenType::True),
+                    "h" => {
+                        if word.len() < 3 {
+                            return TokenType::Identifier;
+                        }
+                        match &word[2..3] {
+                            "i" => check_keyword(word, "this", 2, TokenType::This),
+                            "r" => check_keyword(word, "throw", 2, TokenType::Throw),
+                            _ => TokenType::Identifier,
+                        }
+                    }
+                    "r" => {
+                        if word.len() < 3 {
+                            return TokenType::Identifier;
+                        }
+                        match &word[2..3] {
+                            "u" => check_keyword(word, "true", 2, TokenType::True),
+                            "y" => check_keyword(word, "try", 2, TokenType::Try),
+                            _ => TokenType::Identifier,
+                        }
+                    }
                     _ => TokenType::Identifier,
                 }
             }
+            "u" => check_keyword(word, "unless", 1, TokenType::Unless),
             "v" => check_keyword(word, "var", 1, TokenType::Var),
             "w" => check_keyword(word, "while", 1, TokenType::While),
             _ => TokenType::Identifier,
         }
     }
 
-    fn is_at_end(&mut self) -> bool {
-        self.current() >= self.source.len()
-    }
-
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         let mut tokens = vec![];
 
-        while !self.is_at_end() {
-            tokens.push(self.scan_token());
+        // always end with exactly one EoF, even when the source doesn't end in whitespace
+        loop {
+            let token = self.scan_token();
+            let at_end = token.token_type == TokenType::EoF;
+            tokens.push(token);
+            if at_end {
+                break;
+            }
         }
 
         tokens
     }
 
+    // bad input is recorded in `errors` and skipped, so only real tokens come out
     pub fn scan_token(&mut self) -> Token {
+        loop {
+            if let Some(token) = self.try_scan_token() {
+                return token;
+            }
+        }
+    }
+
+    fn try_scan_token(&mut self) -> Option<Token> {
         self.skip_whitespace();
         self.token_start = self.current();
+        self.token_line = self.line;
+        // columns count characters, not bytes, from the start of the line
+        let tab_width = self.tab_width;
+        if self.measured < self.line_start {
+            self.measured = self.line_start;
+            self.measured_width = 0;
+        }
+        let width: u32 = self.source[self.measured..self.token_start].chars().map(|c| if c == '\t' { tab_width } else { 1 }).sum();
+        self.measured = self.token_start;
+        self.measured_width += width;
+        self.token_column = self.measured_width + 1;
         let c = self.advance();
+        // `r"` opens a raw string rather than naming a variable `r`
+        if c == Some('r') && self.maybe_match('"') {
+            return self.string_literal(true, "\"");
+        }
+        if c == Some('b') && self.maybe_match('"') {
+            return self.bytes_literal();
+        }
         if is_ident(c) {
-            return self.identifier();
+            return Some(self.identifier());
         }
         if is_digit(c) {
-            return self.number_literal();
+            return Some(self.number_literal());
         }
-        match c {
-            None => Token::new(TokenType::EoF, "".to_string(), self.line),
+        let token = match c {
+            None => Token::new(TokenType::EoF, "".to_string(), self.line, self.token_column),
             Some(c) => match c {
                 '(' => self.make_token(TokenType::LeftParen),
                 ')' => self.make_token(TokenType::RightParen),
+                '[' => self.make_token(TokenType::LeftBracket),
+                ']' => self.make_token(TokenType::RightBracket),
                 '{' => self.make_token(TokenType::LeftBrace),
                 '}' => self.make_token(TokenType::RightBrace),
                 ',' => self.make_token(TokenType::Comma),
                 '.' => self.make_token(TokenType::Dot),
-                '-' => self.make_token(TokenType::Minus),
-                '+' => self.make_token(TokenType::Plus),
+                '-' => {
+                    if self.maybe_match('=') {
+                        self.make_token(TokenType::MinusEqual)
+                    } else if self.maybe_match('-') {
+                        self.make_token(TokenType::MinusMinus)
+                    } else if self.maybe_match('>') {
+                        self.make_token(TokenType::Arrow)
+                    } else {
+                        self.make_token(TokenType::Minus)
+                    }
+                }
+                '+' => {
+                    if self.maybe_match('=') {
+                        self.make_token(TokenType::PlusEqual)
+                    } else if self.maybe_match('+') {
+                        self.make_token(TokenType::PlusPlus)
+                    } else {
+                        self.make_token(TokenType::Plus)
+                    }
+                }
                 ';' => self.make_token(TokenType::Semicolon),
-                '/' => self.make_token(TokenType::Slash),
-                '*' => self.make_token(TokenType::Star),
+                ':' => self.make_token(TokenType::Colon),
+                '%' => self.make_token(TokenType::Percent),
+                // `//` already starts a comment, so integer division is spelled `~/`
+                '~' if self.maybe_match('/') => self.make_token(TokenType::TildeSlash),
+                '/' => {
+                    if self.maybe_match('=') {
+                        self.make_token(TokenType::SlashEqual)
+                    } else {
+                        self.make_token(TokenType::Slash)
+                    }
+                }
+                '*' => {
+                    if self.maybe_match('=') {
+                        self.make_token(TokenType::StarEqual)
+                    } else {
+                        self.make_token(TokenType::Star)
+                    }
+                }
                 '!' => {
                     if self.maybe_match('=') {
                         self.make_token(TokenType::BangEqual)
@@ -349,6 +822,8 @@ impl<'a> Scanner<'a> {
                 '=' => {
                     if self.maybe_match('=') {
                         self.make_token(TokenType::EqualEqual)
+                    } else if self.maybe_match('>') {
+                        self.make_token(TokenType::FatArrow)
                     } else {
                         self.make_token(TokenType::Equal)
                     }
@@ -367,13 +842,267 @@ impl<'a> Scanner<'a> {
                         self.make_token(TokenType::Greater)
                     }
                 }
-                '"' => self.string_literal(),
+                '?' if self.maybe_match('?') => self.make_token(TokenType::QuestionQuestion),
+                '?' if self.maybe_match('.') => self.make_token(TokenType::QuestionDot),
+                '"' if self.maybe_match_str("\"\"") => return self.string_literal(false, "\"\"\""),
+                '"' => return self.string_literal(false, "\""),
                 _ => {
-                    TokenType::error(TokenType::UnexpectedCharacterError, self.line);
-                    self.make_token(TokenType::UnexpectedCharacterError)
+                    let content = self.content();
+                    self.error(ScanErrorKind::UnexpectedCharacter, content);
+                    return None;
                 }
             },
+        };
+        Some(token)
+    }
+
+}
+
+// scans a reader a line at a time, so a large file never has to be held in memory all at once
+// only a string literal spanning several lines keeps more than one line buffered
+pub struct ReaderScanner<R: BufRead> {
+    reader: R,
+    // where the input scanned so far ends, the line being the one the next piece starts on
+    line: u32,
+    column: u32,
+    tab_width: u32,
+    errors: Vec<ScanError>,
+    at_end: bool,
+}
+
+// a string literal left open at the end of a line, kept from its opening quote on
+struct OpenLiteral {
+    text: String,
+    line: u32,
+    column: u32,
+    raw: bool,
+    delimiter: &'static str,
+}
+
+impl OpenLiteral {
+    // looks for the closing quote in just the newest line; a line break can't be escaped, so no
+    // backslash carries over from the line before
+    fn closes(&self, line: &str) -> bool {
+        let mut chars = line.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' if line[index..].starts_with(self.delimiter) => return true,
+                '\\' if !self.raw => {
+                    chars.next();
+                }
+                _ => (),
+            }
+        }
+        false
+    }
+}
+
+impl<R: BufRead> ReaderScanner<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, line: 1, column: 1, tab_width: 1, errors: Vec::new(), at_end: false }
+    }
+
+    pub fn with_tab_width(mut self, width: u32) -> Self {
+        self.tab_width = width;
+        self
+    }
+
+    // the problems found so far, in source order
+    pub fn into_errors(self) -> Vec<ScanError> {
+        self.errors
+    }
+
+    // the same tokens Scanner::scan_tokens gives for the whole input, ending in one EoF
+    pub fn scan_tokens(&mut self) -> io::Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        while let Some(line_tokens) = self.next_tokens()? {
+            tokens.extend(line_tokens);
+        }
+        tokens.push(Token::new(TokenType::EoF, String::new(), self.line, self.column));
+        Ok(tokens)
+    }
+
+    // the tokens of the next line, or of several if a string literal runs on past the first
+    // gives None once the reader is exhausted
+    pub fn next_tokens(&mut self) -> io::Result<Option<Vec<Token>>> {
+        if self.at_end {
+            return Ok(None);
+        }
+        let mut tokens = Vec::new();
+        let mut open: Option<OpenLiteral> = None;
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                self.at_end = true;
+                if open.is_none() {
+                    return Ok(None);
+                }
+            }
+            // an open literal is only scanned again once it has closed, so a long one costs no more than a short one
+            let (text, start_line, start_column) = match open.take() {
+                Some(mut literal) => {
+                    let closed = literal.closes(&line);
+                    literal.text.push_str(&line);
+                    if !closed && !self.at_end {
+                        open = Some(literal);
+                        continue;
+                    }
+                    (literal.text, literal.line, literal.column)
+                }
+                None => (line, self.line, self.column),
+            };
+
+            let mut scanner = Scanner::new(&text)
+                .starting_at_line(start_line)
+                .starting_at_column(start_column)
+                .with_tab_width(self.tab_width);
+            let mut scanned = scanner.scan_tokens();
+            let unterminated = scanner.unterminated;
+            let mut errors = scanner.into_errors();
+            // the EoF only marks the end of the text, and where it is is where the next piece starts
+            let eof = scanned.pop();
+            tokens.extend(scanned);
+
+            // a string left open at the end of the line may still be closed by the lines after it
+            if let (Some(start), false) = (unterminated, self.at_end) {
+                let error = errors.pop().expect("an unterminated string is recorded as an error");
+                self.errors.extend(errors);
+                open = Some(OpenLiteral {
+                    text: text[start..].to_string(),
+                    line: error.line,
+                    column: error.column,
+                    raw: error.lexeme == "r\"",
+                    delimiter: if error.lexeme == "\"\"\"" { "\"\"\"" } else { "\"" },
+                });
+                continue;
+            }
+            if let Some(eof) = eof {
+                self.line = eof.line;
+                self.column = eof.column;
+            }
+            self.errors.extend(errors);
+            return Ok(Some(tokens));
         }
     }
+}
+
+// the value of a string literal token, which the scanner has already checked for bad escapes
+pub fn string_value(lexeme: &str) -> String {
+    if let Some(raw) = lexeme.strip_prefix('r') {
+        return raw[1..raw.len() - 1].to_string();
+    }
+    if lexeme.len() >= 6 && lexeme.starts_with("\"\"\"") {
+        return unescape(&lexeme[3..lexeme.len() - 3]).expect("the scanner rejects invalid escapes");
+    }
+    unescape(&lexeme[1..lexeme.len() - 1]).expect("the scanner rejects invalid escapes")
+}
+
+// the value of a byte string literal token, `b` prefix and all
+pub fn bytes_value(lexeme: &str) -> Vec<u8> {
+    unescape_bytes(&lexeme[2..lexeme.len() - 1], true).expect("the scanner rejects invalid escapes")
+}
 
+fn unescape(body: &str) -> Option<String> {
+    // with only ASCII allowed for \xNN, the bytes are always valid UTF-8
+    unescape_bytes(body, false).and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+// decodes \n, \t, \r, \0, \\, \", \xNN and \u{NNNNNN}, or none if any escape is malformed;
+// \xNN must be ASCII unless `any_byte` is set, as it is for byte strings
+fn unescape_bytes(body: &str, any_byte: bool) -> Option<Vec<u8>> {
+    let mut chars = body.chars();
+    let mut decoded = Vec::with_capacity(body.len());
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        let escaped = match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+                if digits.len() != 2 || !digits.chars().all(|d| d.is_ascii_hexdigit()) {
+                    return None;
+                }
+                let byte = u8::from_str_radix(&digits, 16).ok().filter(|byte| any_byte || byte.is_ascii())?;
+                decoded.push(byte);
+                continue;
+            }
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+                let mut digits = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        d if d.is_ascii_hexdigit() && digits.len() < 6 => digits.push(d),
+                        _ => return None,
+                    }
+                }
+                char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?
+            }
+            _ => return None,
+        };
+        decoded.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+    Some(decoded)
+}
+
+// splits lines the same way the scanner counts them, so line numbers index into the result
+pub fn source_lines(source: &str) -> Vec<String> {
+    source.split("\r\n").flat_map(|chunk| chunk.split(['\r', '\n'])).map(String::from).collect()
+}
+
+// the byte offset a line starts at, or the end of the source for lines past it
+pub fn line_offset(source: &str, line: u32) -> usize {
+    let mut chars = source.char_indices().peekable();
+    let mut current = 1;
+    while current < line {
+        match chars.next() {
+            Some((_, '\r')) => {
+                chars.next_if(|(_, c)| *c == '\n');
+                current += 1;
+            }
+            Some((_, '\n')) => current += 1,
+            Some(_) => (),
+            None => return source.len(),
+        }
+    }
+    chars.peek().map_or(source.len(), |(index, _)| *index)
+}
+
+// one object per source token, leaving out the synthetic end of file
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+    let objects: Vec<String> = tokens.iter()
+        .filter(|token| token.token_type != TokenType::EoF)
+        .map(|token| format!(
+            "{{ \"type\": \"{}\", \"lexeme\": \"{}\", \"line\": {} }}",
+            token.token_type.name(),
+            json_escape(&token.lexeme),
+            token.line,
+        ))
+        .collect();
+    format!("[{}]", objects.join(", "))
+}
+
+pub fn json_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
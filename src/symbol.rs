@@ -0,0 +1,24 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// interned identifier, so environments hash a u32 instead of a fresh String
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+thread_local! {
+    static INTERNER: RefCell<HashMap<String, Symbol>> = RefCell::new(HashMap::new());
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(symbol) = interner.get(name) {
+                return *symbol;
+            }
+            let symbol = Symbol(interner.len() as u32);
+            interner.insert(name.to_string(), symbol);
+            symbol
+        })
+    }
+}
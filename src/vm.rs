@@ -0,0 +1,1069 @@
+use std::collections::{ HashMap, HashSet };
+use std::rc::Rc;
+
+use crate::Lox;
+use crate::ast::{ format_number, Expr, Pattern, Stmt, Value };
+use crate::error::{ Diagnostic, RuntimeError };
+use crate::function::NativeFunction;
+use crate::interpreter::{ self, Interpreter };
+use crate::scanner::{ Token, TokenType };
+
+const FRAMES_MAX: usize = 1024;
+
+#[derive(Clone, Copy, Debug)]
+pub enum OpCode {
+    Constant(usize),
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal(usize),
+    SetLocal(usize),
+    GetGlobal(usize),
+    DefineGlobal(usize),
+    DefineConstant(usize),
+    SetGlobal(usize),
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    FloorDivide,
+    Modulo,
+    Not,
+    Negate,
+    Increment,
+    Decrement,
+    Print(usize),
+    NoMatch,
+    // jump targets are absolute offsets into the chunk
+    Jump(usize),
+    JumpIfFalse(usize),
+    JumpIfNotNil(usize),
+    Loop(usize),
+    Call(usize),
+    Return,
+}
+
+#[derive(Clone)]
+enum VmValue {
+    Number(f64),
+    Int(i64),
+    String(Rc<str>),
+    Boolean(bool),
+    Nil,
+    Function(Rc<VmFunction>),
+    Native(Rc<NativeFunction>),
+}
+
+impl VmValue {
+    // must stay in step with the tree-walking interpreter's printing
+    fn print(&self) -> String {
+        match self {
+            VmValue::Number(n) => format_number(*n),
+            VmValue::Int(n) => n.to_string(),
+            VmValue::String(s) => s.to_string(),
+            VmValue::Boolean(b) => b.to_string(),
+            VmValue::Nil => String::from("nil"),
+            VmValue::Function(function) => format!("<fn {}>", function.name),
+            VmValue::Native(_) => String::from("<native fn>"),
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            VmValue::Number(n) => Some(*n),
+            VmValue::Int(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    // natives take and return the interpreter's values, so only what both sides can hold crosses over
+    fn from_value(value: Value) -> Result<VmValue, String> {
+        match value {
+            Value::Number(n) => Ok(VmValue::Number(n)),
+            Value::Int(n) => Ok(VmValue::Int(n)),
+            Value::String(s) => Ok(VmValue::String(Rc::from(s))),
+            Value::Boolean(b) => Ok(VmValue::Boolean(b)),
+            Value::Nil => Ok(VmValue::Nil),
+            Value::NativeFunction(native) => Ok(VmValue::Native(native)),
+            value => Err(format!("The VM can't hold a value of type {}.", value.type_name())),
+        }
+    }
+
+    fn to_value(&self) -> Result<Value, String> {
+        match self {
+            VmValue::Number(n) => Ok(Value::Number(*n)),
+            VmValue::Int(n) => Ok(Value::Int(*n)),
+            VmValue::String(s) => Ok(Value::String(s.to_string())),
+            VmValue::Boolean(b) => Ok(Value::Boolean(*b)),
+            VmValue::Nil => Ok(Value::Nil),
+            VmValue::Native(native) => Ok(Value::NativeFunction(Rc::clone(native))),
+            VmValue::Function(_) => Err(String::from("Functions compiled by the VM can't be passed to natives.")),
+        }
+    }
+
+    // must agree with Interpreter::is_truthy
+    fn is_truthy(&self) -> bool {
+        match self {
+            VmValue::Nil => false,
+            VmValue::Boolean(boolean) => *boolean,
+            _ => true,
+        }
+    }
+
+    fn is_equal(&self, other: &VmValue) -> bool {
+        match (self, other) {
+            (VmValue::String(lstr), VmValue::String(rstr)) => lstr == rstr,
+            (VmValue::Number(lnum), VmValue::Number(rnum)) => lnum == rnum,
+            (VmValue::Int(lint), VmValue::Int(rint)) => lint == rint,
+            (VmValue::Int(int), VmValue::Number(num)) | (VmValue::Number(num), VmValue::Int(int)) => *int as f64 == *num,
+            (VmValue::Boolean(lbool), VmValue::Boolean(rbool)) => lbool == rbool,
+            (VmValue::Nil, VmValue::Nil) => true,
+            (VmValue::Function(lfun), VmValue::Function(rfun)) => Rc::ptr_eq(lfun, rfun),
+            (VmValue::Native(lfun), VmValue::Native(rfun)) => Rc::ptr_eq(lfun, rfun),
+            (_, _) => false,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Chunk {
+    code: Vec<OpCode>,
+    lines: Vec<u32>,
+    constants: Vec<VmValue>,
+}
+
+impl Chunk {
+    fn write(&mut self, op: OpCode, line: u32) -> usize {
+        self.code.push(op);
+        self.lines.push(line);
+        self.code.len() - 1
+    }
+
+    fn add_constant(&mut self, value: VmValue) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+}
+
+pub struct VmFunction {
+    name: String,
+    arity: usize,
+    chunk: Chunk,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    is_const: bool,
+}
+
+// jumps out of a loop are patched once the loop's end and continue point are known
+struct Loop {
+    label: Option<String>,
+    local_count: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct FunctionState {
+    function: VmFunction,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+}
+
+impl FunctionState {
+    fn new(name: String, arity: usize) -> Self {
+        Self {
+            function: VmFunction {
+                name,
+                arity,
+                chunk: Chunk::default(),
+            },
+            // slot zero holds the function being called
+            locals: vec![Local { name: String::new(), depth: 0, is_const: false }],
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+struct Compiler {
+    states: Vec<FunctionState>,
+    line: u32,
+}
+
+// compiles a single top-level statement into a script function
+pub fn compile(stmt: &Stmt) -> Result<Rc<VmFunction>, Diagnostic> {
+    let mut compiler = Compiler {
+        states: vec![FunctionState::new(String::from("script"), 0)],
+        line: 0,
+    };
+    compiler.statement(stmt)?;
+    compiler.emit(OpCode::Nil);
+    compiler.emit(OpCode::Return);
+    let state = compiler.states.pop().unwrap();
+    Ok(Rc::new(state.function))
+}
+
+impl Compiler {
+    fn current(&mut self) -> &mut FunctionState {
+        self.states.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().function.chunk
+    }
+
+    fn emit(&mut self, op: OpCode) -> usize {
+        let line = self.line;
+        self.chunk().write(op, line)
+    }
+
+    fn emit_at(&mut self, op: OpCode, token: &Token) -> usize {
+        self.line = token.line;
+        self.emit(op)
+    }
+
+    fn emit_constant(&mut self, value: VmValue) {
+        let index = self.chunk().add_constant(value);
+        self.emit(OpCode::Constant(index));
+    }
+
+    fn identifier_constant(&mut self, name: &Token) -> usize {
+        self.chunk().add_constant(VmValue::String(Rc::from(name.lexeme.as_str())))
+    }
+
+    fn patch_jump(&mut self, index: usize) {
+        let target = self.chunk().code.len();
+        match &mut self.chunk().code[index] {
+            OpCode::Jump(offset) | OpCode::JumpIfFalse(offset) | OpCode::JumpIfNotNil(offset) => *offset = target,
+            _ => unreachable!("only jumps can be patched"),
+        }
+    }
+
+    fn loop_body(&mut self, body: &Stmt, label: &Option<Token>) -> Result<Loop, Diagnostic> {
+        let local_count = self.current().locals.len();
+        let label = label.as_ref().map(|label| label.lexeme.clone());
+        self.current().loops.push(Loop { label, local_count, breaks: Vec::new(), continues: Vec::new() });
+        let result = self.statement(body);
+        let state = self.current().loops.pop().unwrap();
+        result.map(|()| state)
+    }
+
+    // pops the locals declared inside the targeted loop, then jumps to a spot patched later
+    fn jump(&mut self, keyword: &Token, label: &Option<Token>) -> Result<(), Diagnostic> {
+        let target = label.as_ref().map(|label| label.lexeme.as_str());
+        let index = self.current().loops.iter()
+            .rposition(|lp| target.is_none() || lp.label.as_deref() == target)
+            .ok_or_else(|| Lox::error(keyword, format!("Can't use '{}' outside of a loop.", keyword.lexeme)))?;
+
+        let pops = self.current().locals.len() - self.current().loops[index].local_count;
+        for _ in 0..pops {
+            self.emit_at(OpCode::Pop, keyword);
+        }
+        let jump = self.emit_at(OpCode::Jump(0), keyword);
+        let lp = &mut self.current().loops[index];
+        if keyword.token_type == TokenType::Break {
+            lp.breaks.push(jump);
+        } else {
+            lp.continues.push(jump);
+        }
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.current();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        while self.current().locals.last().is_some_and(|local| local.depth > depth) {
+            self.current().locals.pop();
+            self.emit(OpCode::Pop);
+        }
+    }
+
+    fn add_local(&mut self, name: &Token) {
+        let depth = self.current().scope_depth;
+        self.current().locals.push(Local { name: name.lexeme.clone(), depth, is_const: false });
+    }
+
+    // constant locals are caught while compiling, constant globals by the VM
+    fn resolve_assignment(&mut self, name: &Token) -> Result<Option<usize>, Diagnostic> {
+        let slot = self.resolve_local(name)?;
+        if slot.is_some_and(|slot| self.current().locals[slot].is_const) {
+            return Err(Lox::error(name, format!("Cannot assign to constant '{}'.", name.lexeme)));
+        }
+        Ok(slot)
+    }
+
+    fn resolve_local(&mut self, name: &Token) -> Result<Option<usize>, Diagnostic> {
+        if let Some(slot) = self.current().locals.iter().rposition(|local| local.name == name.lexeme) {
+            return Ok(Some(slot));
+        }
+
+        let enclosing = &self.states[..self.states.len() - 1];
+        if enclosing.iter().any(|state| state.locals.iter().any(|local| local.name == name.lexeme)) {
+            return Err(Lox::error(name, String::from("Closures are not supported by the VM.")));
+        }
+        Ok(None)
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), Diagnostic> {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.block(statements)?;
+            }
+            Stmt::Class { name, .. } => {
+                return Err(Lox::error(name, String::from("Classes are not supported by the VM.")));
+            }
+            Stmt::Const { name, initializer } => {
+                self.expression(initializer)?;
+                if self.current().scope_depth > 0 {
+                    let depth = self.current().scope_depth;
+                    self.current().locals.push(Local { name: name.lexeme.clone(), depth, is_const: true });
+                } else {
+                    let index = self.identifier_constant(name);
+                    self.emit_at(OpCode::DefineConstant(index), name);
+                }
+            }
+            Stmt::Break { keyword, label } | Stmt::Continue { keyword, label } => {
+                self.jump(keyword, label)?;
+            }
+            Stmt::DoWhile { body, condition, label } => {
+                let loop_start = self.chunk().code.len();
+                let lp = self.loop_body(body, label)?;
+                lp.continues.into_iter().for_each(|jump| self.patch_jump(jump));
+                self.expression(condition)?;
+                let exit_jump = self.emit(OpCode::JumpIfFalse(0));
+                self.emit(OpCode::Pop);
+                self.emit(OpCode::Loop(loop_start));
+
+                self.patch_jump(exit_jump);
+                self.emit(OpCode::Pop);
+                lp.breaks.into_iter().for_each(|jump| self.patch_jump(jump));
+            }
+            Stmt::Expression { expression } => {
+                self.expression(expression)?;
+                self.emit(OpCode::Pop);
+            }
+            Stmt::ForEach { var_name, .. } => {
+                return Err(Lox::error(var_name, String::from("For-in loops are not supported by the VM.")));
+            }
+            Stmt::Function { name, params, body } => {
+                let function = self.function(name, params, body)?;
+                self.emit_constant(VmValue::Function(function));
+                self.define_variable(name);
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.expression(condition)?;
+                let then_jump = self.emit(OpCode::JumpIfFalse(0));
+                self.emit(OpCode::Pop);
+                self.statement(then_branch)?;
+
+                let else_jump = self.emit(OpCode::Jump(0));
+                self.patch_jump(then_jump);
+                self.emit(OpCode::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(else_jump);
+            }
+            Stmt::Match { keyword, subject, arms } => {
+                self.begin_scope();
+                self.expression(subject)?;
+                let slot = self.current().locals.len();
+                self.add_local(&Token::new(TokenType::Match, String::from("match"), keyword.line, 0));
+
+                let mut end_jumps = Vec::new();
+                let mut exhaustive = false;
+                for (pattern, body) in arms {
+                    match pattern {
+                        Pattern::Literal(value) => {
+                            self.emit(OpCode::GetLocal(slot));
+                            self.expression(&Expr::Literal { value: value.clone() })?;
+                            self.emit(OpCode::Equal);
+                            let next_jump = self.emit(OpCode::JumpIfFalse(0));
+                            self.emit(OpCode::Pop);
+                            self.statement(body)?;
+                            end_jumps.push(self.emit(OpCode::Jump(0)));
+
+                            self.patch_jump(next_jump);
+                            self.emit(OpCode::Pop);
+                        }
+                        Pattern::Wildcard => {
+                            self.statement(body)?;
+                            exhaustive = true;
+                        }
+                    }
+                }
+                if !exhaustive {
+                    self.emit(OpCode::GetLocal(slot));
+                    self.emit_at(OpCode::NoMatch, keyword);
+                }
+
+                for jump in end_jumps {
+                    self.patch_jump(jump);
+                }
+                self.end_scope();
+            }
+            Stmt::Print { expressions } => {
+                for expression in expressions {
+                    self.expression(expression)?;
+                }
+                self.emit(OpCode::Print(expressions.len()));
+            }
+            Stmt::Return { keyword, value } => {
+                match value {
+                    Some(expr) => self.expression(expr)?,
+                    None => {
+                        self.emit_at(OpCode::Nil, keyword);
+                    }
+                }
+                self.emit(OpCode::Return);
+            }
+            Stmt::Var { name, initializer } => {
+                match initializer {
+                    Some(expr) => self.expression(expr)?,
+                    None => {
+                        self.emit_at(OpCode::Nil, name);
+                    }
+                }
+                self.define_variable(name);
+            }
+            Stmt::VarUnpack { paren, .. } => {
+                return Err(Lox::error(paren, String::from("Tuples are not supported by the VM.")));
+            }
+            Stmt::Switch { subject, cases, default } => {
+                // the subject lives in a hidden local so each case can compare against it
+                self.begin_scope();
+                self.expression(subject)?;
+                let slot = self.current().locals.len();
+                self.add_local(&Token::new(TokenType::Switch, String::from("switch"), self.line, 0));
+
+                let mut end_jumps = Vec::new();
+                for (value, body) in cases {
+                    self.emit(OpCode::GetLocal(slot));
+                    self.expression(value)?;
+                    self.emit(OpCode::Equal);
+                    let next_jump = self.emit(OpCode::JumpIfFalse(0));
+                    self.emit(OpCode::Pop);
+                    self.block(body)?;
+                    end_jumps.push(self.emit(OpCode::Jump(0)));
+
+                    self.patch_jump(next_jump);
+                    self.emit(OpCode::Pop);
+                }
+                if let Some(body) = default {
+                    self.block(body)?;
+                }
+
+                for jump in end_jumps {
+                    self.patch_jump(jump);
+                }
+                self.end_scope();
+            }
+            Stmt::Throw { keyword, .. } => {
+                return Err(Lox::error(keyword, String::from("Throw statements are not supported by the VM.")));
+            }
+            Stmt::Try { keyword, .. } => {
+                return Err(Lox::error(keyword, String::from("Try statements are not supported by the VM.")));
+            }
+            Stmt::While { condition, body, increment, label } => {
+                let loop_start = self.chunk().code.len();
+                self.expression(condition)?;
+                let exit_jump = self.emit(OpCode::JumpIfFalse(0));
+                self.emit(OpCode::Pop);
+                let lp = self.loop_body(body, label)?;
+                lp.continues.into_iter().for_each(|jump| self.patch_jump(jump));
+                if let Some(increment) = increment {
+                    self.expression(increment)?;
+                    self.emit(OpCode::Pop);
+                }
+                self.emit(OpCode::Loop(loop_start));
+
+                self.patch_jump(exit_jump);
+                self.emit(OpCode::Pop);
+                lp.breaks.into_iter().for_each(|jump| self.patch_jump(jump));
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> Result<(), Diagnostic> {
+        self.begin_scope();
+        for stmt in statements {
+            self.statement(stmt)?;
+        }
+        self.end_scope();
+        Ok(())
+    }
+
+    // the initializer is already on the stack, so locals simply claim its slot
+    fn define_variable(&mut self, name: &Token) {
+        if self.current().scope_depth > 0 {
+            self.add_local(name);
+            return;
+        }
+        let index = self.identifier_constant(name);
+        self.emit_at(OpCode::DefineGlobal(index), name);
+    }
+
+    fn function(&mut self, name: &Token, params: &[Token], body: &[Stmt]) -> Result<Rc<VmFunction>, Diagnostic> {
+        self.states.push(FunctionState::new(name.lexeme.clone(), params.len()));
+        self.begin_scope();
+        for param in params {
+            self.add_local(param);
+        }
+
+        for stmt in body {
+            self.statement(stmt)?;
+        }
+        self.emit(OpCode::Nil);
+        self.emit(OpCode::Return);
+
+        let state = self.states.pop().unwrap();
+        Ok(Rc::new(state.function))
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), Diagnostic> {
+        match expr {
+            Expr::Assign { name, value } => {
+                self.expression(value)?;
+                match self.resolve_assignment(name)? {
+                    Some(slot) => self.emit_at(OpCode::SetLocal(slot), name),
+                    None => {
+                        let index = self.identifier_constant(name);
+                        self.emit_at(OpCode::SetGlobal(index), name)
+                    }
+                };
+            }
+            Expr::Binary { left, operator, right } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                match operator.token_type {
+                    TokenType::BangEqual => {
+                        self.emit_at(OpCode::Equal, operator);
+                        self.emit(OpCode::Not);
+                    }
+                    TokenType::EqualEqual => {
+                        self.emit_at(OpCode::Equal, operator);
+                    }
+                    TokenType::Greater => {
+                        self.emit_at(OpCode::Greater, operator);
+                    }
+                    TokenType::GreaterEqual => {
+                        self.emit_at(OpCode::GreaterEqual, operator);
+                    }
+                    TokenType::Less => {
+                        self.emit_at(OpCode::Less, operator);
+                    }
+                    TokenType::LessEqual => {
+                        self.emit_at(OpCode::LessEqual, operator);
+                    }
+                    TokenType::Plus => {
+                        self.emit_at(OpCode::Add, operator);
+                    }
+                    TokenType::Minus => {
+                        self.emit_at(OpCode::Subtract, operator);
+                    }
+                    TokenType::Star => {
+                        self.emit_at(OpCode::Multiply, operator);
+                    }
+                    TokenType::Slash => {
+                        self.emit_at(OpCode::Divide, operator);
+                    }
+                    TokenType::TildeSlash => {
+                        self.emit_at(OpCode::FloorDivide, operator);
+                    }
+                    TokenType::Percent => {
+                        self.emit_at(OpCode::Modulo, operator);
+                    }
+                    _ => return Err(Lox::error(operator, String::from("Unkown binary operator"))),
+                }
+            }
+            Expr::Block { statements, final_expr } => {
+                self.begin_scope();
+                let base = self.current().locals.len();
+                for stmt in statements {
+                    self.statement(stmt)?;
+                }
+                self.expression(final_expr)?;
+
+                // the value takes over the block's first slot and everything above it is popped
+                self.current().scope_depth -= 1;
+                let count = self.current().locals.len() - base;
+                self.current().locals.truncate(base);
+                if count > 0 {
+                    self.emit(OpCode::SetLocal(base));
+                    for _ in 0..count {
+                        self.emit(OpCode::Pop);
+                    }
+                }
+            }
+            Expr::Call { callee, paren, arguments, keywords } => {
+                if let Some((label, _)) = keywords.first() {
+                    return Err(Lox::error(label, String::from("Keyword arguments are not supported by the VM.")));
+                }
+                self.expression(callee)?;
+                for argument in arguments {
+                    self.expression(argument)?;
+                }
+                self.emit_at(OpCode::Call(arguments.len()), paren);
+            }
+            Expr::Comma { exprs } => {
+                for (index, expr) in exprs.iter().enumerate() {
+                    if index > 0 {
+                        self.emit(OpCode::Pop);
+                    }
+                    self.expression(expr)?;
+                }
+            }
+            Expr::Grouping { expression } => self.expression(expression)?,
+            Expr::Literal { value } => match value {
+                Value::Number(n) => self.emit_constant(VmValue::Number(*n)),
+                Value::Int(n) => self.emit_constant(VmValue::Int(*n)),
+                Value::String(s) => self.emit_constant(VmValue::String(Rc::from(s.as_str()))),
+                Value::Boolean(true) => {
+                    self.emit(OpCode::True);
+                }
+                Value::Boolean(false) => {
+                    self.emit(OpCode::False);
+                }
+                Value::Nil => {
+                    self.emit(OpCode::Nil);
+                }
+                // literals carry no token, so the error points at the line being compiled
+                _ => {
+                    let token = Token::new(TokenType::BytesLiteral, String::new(), self.line, 0);
+                    return Err(Lox::error(&token, String::from("Byte strings are not supported by the VM.")));
+                }
+            },
+            Expr::Logical { left, operator, right } => {
+                self.expression(left)?;
+                match operator.token_type {
+                    TokenType::And => {
+                        let end_jump = self.emit_at(OpCode::JumpIfFalse(0), operator);
+                        self.emit(OpCode::Pop);
+                        self.expression(right)?;
+                        self.patch_jump(end_jump);
+                    }
+                    TokenType::Or => {
+                        let else_jump = self.emit_at(OpCode::JumpIfFalse(0), operator);
+                        let end_jump = self.emit(OpCode::Jump(0));
+                        self.patch_jump(else_jump);
+                        self.emit(OpCode::Pop);
+                        self.expression(right)?;
+                        self.patch_jump(end_jump);
+                    }
+                    _ => {
+                        let end_jump = self.emit_at(OpCode::JumpIfNotNil(0), operator);
+                        self.emit(OpCode::Pop);
+                        self.expression(right)?;
+                        self.patch_jump(end_jump);
+                    }
+                }
+            }
+            Expr::Unary { operator, right } => {
+                self.expression(right)?;
+                match operator.token_type {
+                    TokenType::Bang => self.emit_at(OpCode::Not, operator),
+                    TokenType::Minus => self.emit_at(OpCode::Negate, operator),
+                    _ => return Err(Lox::error(operator, String::from("Unknown unary operator."))),
+                };
+            }
+            Expr::Update { name, operator, prefix } => {
+                let (get, set) = match self.resolve_assignment(name)? {
+                    Some(slot) => (OpCode::GetLocal(slot), OpCode::SetLocal(slot)),
+                    None => {
+                        let index = self.identifier_constant(name);
+                        (OpCode::GetGlobal(index), OpCode::SetGlobal(index))
+                    }
+                };
+                let adjust = match operator.token_type {
+                    TokenType::PlusPlus => OpCode::Increment,
+                    _ => OpCode::Decrement,
+                };
+
+                // postfix keeps a copy of the old value underneath the one written back
+                self.emit_at(get, name);
+                if !prefix {
+                    self.emit(get);
+                }
+                self.emit_at(adjust, operator);
+                self.emit_at(set, name);
+                if !prefix {
+                    self.emit(OpCode::Pop);
+                }
+            }
+            Expr::List { bracket, .. } => {
+                return Err(Lox::error(bracket, String::from("Lists are not supported by the VM.")));
+            }
+            Expr::Index { bracket, .. } => {
+                return Err(Lox::error(bracket, String::from("Indexing is not supported by the VM.")));
+            }
+            Expr::Tuple { paren, .. } | Expr::Unpack { paren, .. } => {
+                return Err(Lox::error(paren, String::from("Tuples are not supported by the VM.")));
+            }
+            Expr::Variable { name } => {
+                match self.resolve_local(name)? {
+                    Some(slot) => self.emit_at(OpCode::GetLocal(slot), name),
+                    None => {
+                        let index = self.identifier_constant(name);
+                        self.emit_at(OpCode::GetGlobal(index), name)
+                    }
+                };
+            }
+            Expr::Get { name, .. } | Expr::GetOptional { name, .. } | Expr::Set { name, .. } => {
+                return Err(Lox::error(name, String::from("Classes are not supported by the VM.")));
+            }
+            Expr::Super { keyword, .. } | Expr::This { keyword } => {
+                return Err(Lox::error(keyword, String::from("Classes are not supported by the VM.")));
+            }
+        }
+        Ok(())
+    }
+}
+
+struct CallFrame {
+    function: Rc<VmFunction>,
+    ip: usize,
+    // index of the frame's slot zero on the value stack
+    slots: usize,
+}
+
+pub struct Vm {
+    stack: Vec<VmValue>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<String, VmValue>,
+    constants: HashSet<String>,
+    // supplies the natives and the output, so both behave exactly as they do for the interpreter
+    host: Interpreter,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm::with_host(Interpreter::new())
+    }
+
+    pub fn with_host(host: Interpreter) -> Self {
+        Self {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            constants: HashSet::new(),
+            host,
+        }
+    }
+
+    // the code given to `exit()`, like Interpreter::run
+    pub fn run(&mut self, function: Rc<VmFunction>) -> Result<Option<i64>, RuntimeError> {
+        self.stack.push(VmValue::Function(Rc::clone(&function)));
+        self.frames.push(CallFrame { function, ip: 0, slots: 0 });
+
+        let result = self.execute();
+        self.stack.clear();
+        self.frames.clear();
+        match result {
+            Err(RuntimeError { exit_code: Some(code), .. }) => Ok(Some(code)),
+            result => result.map(|()| None),
+        }
+    }
+
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let op = frame.function.chunk.code[frame.ip];
+            let line = frame.function.chunk.lines[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                OpCode::Constant(index) => {
+                    let constant = self.frame().function.chunk.constants[index].clone();
+                    self.stack.push(constant);
+                }
+                OpCode::Nil => self.stack.push(VmValue::Nil),
+                OpCode::True => self.stack.push(VmValue::Boolean(true)),
+                OpCode::False => self.stack.push(VmValue::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal(slot) => {
+                    let value = self.stack[self.frame().slots + slot].clone();
+                    self.stack.push(value);
+                }
+                OpCode::SetLocal(slot) => {
+                    let index = self.frame().slots + slot;
+                    self.stack[index] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal(index) => {
+                    let name = self.constant_name(index);
+                    // anything the program didn't define is looked up among the natives
+                    let value = match self.globals.get(&*name) {
+                        Some(value) => value.clone(),
+                        None => {
+                            let token = Token::new(TokenType::Identifier, name.to_string(), line, 0);
+                            let value = self.host.environment.borrow().get(&token)?;
+                            VmValue::from_value(value).map_err(|message| self.error(line, &message))?
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::DefineGlobal(index) => {
+                    let name = self.constant_name(index);
+                    let value = self.pop();
+                    self.constants.remove(&*name);
+                    self.globals.insert(name.to_string(), value);
+                }
+                OpCode::DefineConstant(index) => {
+                    let name = self.constant_name(index);
+                    let value = self.pop();
+                    self.constants.insert(name.to_string());
+                    self.globals.insert(name.to_string(), value);
+                }
+                OpCode::SetGlobal(index) => {
+                    let name = self.constant_name(index);
+                    // a native can be assigned to like the interpreter allows, and the new value shadows it
+                    if !self.globals.contains_key(&*name) {
+                        let token = Token::new(TokenType::Identifier, name.to_string(), line, 0);
+                        if self.host.environment.borrow().get(&token).is_err() {
+                            return Err(self.error(line, &format!("Undefined variable {}.", name)));
+                        }
+                    }
+                    if self.constants.contains(&*name) {
+                        return Err(self.error(line, &format!("Cannot assign to constant '{}'.", name)));
+                    }
+                    let value = self.peek(0).clone();
+                    self.globals.insert(name.to_string(), value);
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(VmValue::Boolean(left.is_equal(&right)));
+                }
+                OpCode::Greater => self.compare(line, i64::gt, f64::gt)?,
+                OpCode::GreaterEqual => self.compare(line, i64::ge, f64::ge)?,
+                OpCode::Less => self.compare(line, i64::lt, f64::lt)?,
+                OpCode::LessEqual => self.compare(line, i64::le, f64::le)?,
+                OpCode::Add => {
+                    if let (VmValue::String(lstr), VmValue::String(rstr)) = (self.peek(1), self.peek(0)) {
+                        let string = VmValue::String(Rc::from(format!("{lstr}{rstr}")));
+                        self.stack.truncate(self.stack.len() - 2);
+                        self.stack.push(string);
+                    } else if self.peek(1).as_number().is_some() && self.peek(0).as_number().is_some() {
+                        self.arithmetic(line, i64::checked_add, |a, b| a + b)?;
+                    } else {
+                        return Err(self.error(line, "Invalid operator for operands"));
+                    }
+                }
+                OpCode::Subtract => self.arithmetic(line, i64::checked_sub, |a, b| a - b)?,
+                OpCode::Multiply => {
+                    if let VmValue::String(string) = self.peek(1) {
+                        let string = Rc::clone(string);
+                        let count = self.pop();
+                        self.pop();
+                        let count = self.repeat_count(line, &count, string.len())?;
+                        self.stack.push(VmValue::String(Rc::from(string.repeat(count))));
+                    } else {
+                        self.arithmetic(line, i64::checked_mul, |a, b| a * b)?;
+                    }
+                }
+                // `/` always divides exactly, even between two Ints
+                OpCode::Divide => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    match (left.as_number(), right.as_number()) {
+                        (Some(lnum), Some(rnum)) => self.stack.push(VmValue::Number(lnum / rnum)),
+                        (_, _) => return Err(self.error(line, "Operands must be numbers.")),
+                    }
+                }
+                OpCode::FloorDivide => {
+                    self.check_int_divisor(line)?;
+                    self.arithmetic(line, interpreter::floor_divide, |a, b| (a / b).floor())?;
+                }
+                // f64's % is fmod, matching the interpreter
+                OpCode::Modulo => {
+                    self.check_int_divisor(line)?;
+                    self.arithmetic(line, i64::checked_rem, |a, b| a % b)?;
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(VmValue::Boolean(!value.is_truthy()));
+                }
+                OpCode::Negate => match self.pop() {
+                    VmValue::Number(num) => self.stack.push(VmValue::Number(-num)),
+                    VmValue::Int(int) => match int.checked_neg() {
+                        Some(int) => self.stack.push(VmValue::Int(int)),
+                        None => return Err(self.error(line, "Integer overflow.")),
+                    },
+                    _ => return Err(self.error(line, "Operand must be a number.")),
+                },
+                OpCode::Increment | OpCode::Decrement => {
+                    if self.peek(0).as_number().is_none() {
+                        return Err(self.error(line, "Operand must be a number."));
+                    }
+                    let step = if matches!(op, OpCode::Increment) { 1 } else { -1 };
+                    self.stack.push(VmValue::Int(step));
+                    self.arithmetic(line, i64::checked_add, |a, b| a + b)?;
+                }
+                OpCode::NoMatch => {
+                    let subject = self.pop();
+                    return Err(self.error(line, &format!("No match arm for {}.", subject.print())));
+                }
+                OpCode::Print(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count);
+                    let values: Vec<String> = values.iter().map(VmValue::print).collect();
+                    self.host.write(&format!("{}\n", values.join(" ")))?;
+                }
+                OpCode::Jump(target) | OpCode::Loop(target) => self.frame_mut().ip = target,
+                OpCode::JumpIfFalse(target) => {
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip = target;
+                    }
+                }
+                OpCode::JumpIfNotNil(target) => {
+                    if !matches!(self.peek(0), VmValue::Nil) {
+                        self.frame_mut().ip = target;
+                    }
+                }
+                OpCode::Call(arg_count) => self.call(arg_count, line)?,
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.stack.truncate(frame.slots);
+                    self.stack.push(result);
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, arg_count: usize, line: u32) -> Result<(), RuntimeError> {
+        let function = match self.peek(arg_count) {
+            VmValue::Function(function) => Rc::clone(function),
+            VmValue::Native(native) => return self.call_native(Rc::clone(native), arg_count, line),
+            _ => return Err(self.error(line, "Can only call functions and classes.")),
+        };
+
+        if arg_count != function.arity {
+            let message = format!("Expected {} arguments but got {}.", function.arity, arg_count);
+            return Err(self.error(line, &message));
+        }
+        if self.frames.len() >= FRAMES_MAX {
+            return Err(self.error(line, "Stack overflow."));
+        }
+
+        let slots = self.stack.len() - arg_count - 1;
+        self.frames.push(CallFrame { function, ip: 0, slots });
+        Ok(())
+    }
+
+    // the host checks the arity and counts the call, the same as when the interpreter calls a native
+    fn call_native(&mut self, native: Rc<NativeFunction>, arg_count: usize, line: u32) -> Result<(), RuntimeError> {
+        let arguments = self.stack.split_off(self.stack.len() - arg_count);
+        self.pop();
+        let arguments = arguments.iter().map(VmValue::to_value).collect::<Result<Vec<Value>, String>>()
+            .map_err(|message| self.error(line, &message))?;
+        let paren = Token::new(TokenType::RightParen, String::from(")"), line, 0);
+        let result = self.host.invoke(native.as_ref(), arguments, &paren).map_err(|mut error| {
+            error.token.get_or_insert(paren);
+            error
+        })?;
+        let result = VmValue::from_value(result).map_err(|message| self.error(line, &message))?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn pop(&mut self) -> VmValue {
+        self.stack.pop().unwrap()
+    }
+
+    fn peek(&self, distance: usize) -> &VmValue {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn constant_name(&self, index: usize) -> Rc<str> {
+        match &self.frame().function.chunk.constants[index] {
+            VmValue::String(name) => Rc::clone(name),
+            _ => unreachable!("variable names are stored as string constants"),
+        }
+    }
+
+    // two Ints stay an Int unless the result overflows and any Number makes the result a Number,
+    // as in the interpreter
+    fn arithmetic(&mut self, line: u32, int_op: fn(i64, i64) -> Option<i64>, float_op: fn(f64, f64) -> f64) -> Result<(), RuntimeError> {
+        let right = self.pop();
+        let left = self.pop();
+        let result = match (&left, &right) {
+            (VmValue::Int(lint), VmValue::Int(rint)) => match int_op(*lint, *rint) {
+                Some(int) => VmValue::Int(int),
+                None => return Err(self.error(line, "Integer overflow.")),
+            },
+            _ => match (left.as_number(), right.as_number()) {
+                (Some(lnum), Some(rnum)) => VmValue::Number(float_op(lnum, rnum)),
+                (_, _) => return Err(self.error(line, "Operands must be numbers.")),
+            },
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    // Ints are compared exactly rather than through f64, which can't hold every i64
+    fn compare(&mut self, line: u32, int_cmp: fn(&i64, &i64) -> bool, float_cmp: fn(&f64, &f64) -> bool) -> Result<(), RuntimeError> {
+        let right = self.pop();
+        let left = self.pop();
+        let result = match (&left, &right) {
+            (VmValue::Int(lint), VmValue::Int(rint)) => int_cmp(lint, rint),
+            _ => match (left.as_number(), right.as_number()) {
+                (Some(lnum), Some(rnum)) => float_cmp(&lnum, &rnum),
+                (_, _) => return Err(self.error(line, "Operands must be numbers.")),
+            },
+        };
+        self.stack.push(VmValue::Boolean(result));
+        Ok(())
+    }
+
+    // only an Int divided by an Int zero fails; a Number divisor of zero gives an infinity or nan
+    fn check_int_divisor(&self, line: u32) -> Result<(), RuntimeError> {
+        match (self.peek(1), self.peek(0)) {
+            (VmValue::Int(_), VmValue::Int(0)) => Err(self.error(line, "Division by zero.")),
+            _ => Ok(()),
+        }
+    }
+
+    fn repeat_count(&self, line: u32, count: &VmValue, len: usize) -> Result<usize, RuntimeError> {
+        let count = match count {
+            VmValue::Int(count) if *count >= 0 => usize::try_from(*count).unwrap_or(usize::MAX),
+            VmValue::Number(count) if *count >= 0.0 && count.fract() == 0.0 => *count as usize,
+            _ => return Err(self.error(line, "Repeat count must be a non-negative whole number.")),
+        };
+        match len.checked_mul(count) {
+            Some(size) if size <= interpreter::MAX_SEQUENCE_LEN => Ok(count),
+            _ => Err(self.error(line, "Repetition result too large.")),
+        }
+    }
+
+    // the VM only keeps line numbers, so errors carry a synthetic token for that line
+    fn error(&self, line: u32, message: &str) -> RuntimeError {
+        RuntimeError::new(&Token::new(TokenType::EoF, String::new(), line, 0), message)
+    }
+}
@@ -0,0 +1,189 @@
+use lox::{ Lox, Severity };
+
+// `severity: message` for each problem, in source order
+fn check(source: &str) -> Vec<String> {
+    Lox::check(source).iter().map(|diagnostic| format!("{}: {}", diagnostic.severity, diagnostic.message)).collect()
+}
+
+#[test]
+fn a_clean_program_has_no_problems() {
+    assert_eq!(check("var a = 1;\nprint a + 2;"), Vec::<String>::new());
+}
+
+#[test]
+fn every_syntax_error_is_reported_with_its_position() {
+    let diagnostics = Lox::check("print 1 +;\nvar = 2;");
+    let positions: Vec<(u32, Option<u32>)> = diagnostics.iter().map(|diagnostic| (diagnostic.line, diagnostic.column)).collect();
+    assert_eq!(positions, vec![(1, Some(10)), (2, Some(5))]);
+    assert_eq!(diagnostics[1].message, "Expect variable name, found '='.");
+}
+
+#[test]
+fn checking_does_not_run_the_program() {
+    assert_eq!(check("print 1 ~/ 0;\nassert(false);"), Vec::<String>::new());
+}
+
+#[test]
+fn an_unread_local_is_a_warning() {
+    assert_eq!(check("{ var x = 1; }"), vec!["warning: Unused variable 'x'."]);
+    assert_eq!(check("fun f() { var y = 1; }"), vec!["warning: Unused variable 'y'."]);
+}
+
+#[test]
+fn a_read_local_is_not_a_warning() {
+    assert_eq!(check("{ var x = 1; print x; }"), Vec::<String>::new());
+}
+
+#[test]
+fn a_local_named_underscore_is_never_a_warning() {
+    assert_eq!(check("{ var _ = 1; }"), Vec::<String>::new());
+}
+
+#[test]
+fn an_unread_global_is_not_a_warning() {
+    assert_eq!(check("var x = 1;"), Vec::<String>::new());
+}
+
+#[test]
+fn a_parse_error_doesnt_hide_warnings_around_it() {
+    let problems = check("{ var a = 1; }\nprint 1 +;\n{ var b = 2; }");
+    assert_eq!(problems, vec!["warning: Unused variable 'a'.", "error: Expect expression.", "warning: Unused variable 'b'."]);
+}
+
+
+#[test]
+fn declaring_a_local_twice_in_one_scope_is_an_error() {
+    let problems = check("{ var a; var a; }");
+    assert!(problems.contains(&String::from("error: Already a variable with this name in this scope.")), "{:?}", problems);
+}
+
+#[test]
+fn shadowing_in_a_nested_scope_is_fine() {
+    assert_eq!(check("{ var a = 1; { var a = 2; print a; } print a; }"), Vec::<String>::new());
+}
+
+#[test]
+fn redeclaring_a_global_is_fine() {
+    assert_eq!(check("var a = 1;\nvar a = 2;\nprint a;"), Vec::<String>::new());
+}
+
+#[test]
+fn a_duplicate_local_stops_the_program_from_running() {
+    let result = Lox::run_capture("print \"before\";\n{ var a = 1; var a = 2; }");
+    assert_eq!(result.output, "");
+    assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+    assert!(result.errors[0].contains("Already a variable with this name in this scope."), "{:?}", result.errors);
+}
+
+#[test]
+fn a_parse_error_doesnt_hide_resolve_errors_before_it() {
+    let problems = check("{ var a; var a; } print 1 +;");
+    assert!(problems.contains(&String::from("error: Already a variable with this name in this scope.")), "{:?}", problems);
+    assert!(problems.contains(&String::from("error: Expect expression.")), "{:?}", problems);
+}
+
+#[test]
+fn a_top_level_return_is_an_error() {
+    assert_eq!(check("return;"), vec!["error: Can't return from top-level code."]);
+}
+
+#[test]
+fn a_return_inside_a_function_is_fine() {
+    assert_eq!(check("fun f() { return 1; }\nfun g() { { return; } }"), Vec::<String>::new());
+}
+
+#[test]
+fn a_parse_error_doesnt_hide_a_top_level_return() {
+    let problems = check("return 1; var x = (1");
+    assert_eq!(problems.len(), 2, "{:?}", problems);
+    assert_eq!(problems[0], "error: Can't return from top-level code.");
+    assert!(problems[1].starts_with("error: Expect ')' after expression"), "{:?}", problems);
+}
+
+#[test]
+fn an_undefined_variable_in_a_block_is_an_error() {
+    assert_eq!(check("{ print undefinedThing; }"), vec!["error: Undefined variable 'undefinedThing'."]);
+}
+
+#[test]
+fn an_undefined_variable_in_a_function_is_an_error() {
+    assert_eq!(check("fun f() { return nope; }"), vec!["error: Undefined variable 'nope'."]);
+}
+
+#[test]
+fn assigning_an_undefined_variable_is_an_error() {
+    assert_eq!(check("missing = 1;"), vec!["error: Undefined variable 'missing'."]);
+}
+
+#[test]
+fn natives_and_later_declarations_are_defined() {
+    let source = "fun a() { return b() + later + sqrt(4) + pi; }\nfun b() { return 1; }\nvar later = 2;\n{\n  fun f() { return g(); }\n  fun g() { return 1; }\n  print f();\n}\nclass A < B {}\nclass B {}\n";
+    assert_eq!(check(source), Vec::<String>::new());
+}
+
+#[test]
+fn problems_come_out_in_source_order() {
+    let diagnostics = Lox::check("fun f() { return nope; }\nprint 1 +;\n{ var unused = 1; }");
+    let lines: Vec<u32> = diagnostics.iter().map(|diagnostic| diagnostic.line).collect();
+    assert_eq!(lines, vec![1, 2, 3]);
+    assert_eq!(diagnostics[2].severity, Severity::Warning);
+}
+
+#[test]
+fn this_outside_a_class_is_an_error() {
+    assert_eq!(check("print this;"), vec!["error: Can't use 'this' outside of a class."]);
+    assert_eq!(check("fun f() { return this; }"), vec!["error: Can't use 'this' outside of a class."]);
+}
+
+#[test]
+fn this_in_a_method_is_fine() {
+    assert_eq!(check("class A { name() { return this; } }"), Vec::<String>::new());
+}
+
+#[test]
+fn super_in_a_class_without_a_superclass_is_an_error() {
+    assert_eq!(check("class A { f() { return super.f(); } }"), vec!["error: Can't use 'super' in a class with no superclass."]);
+    assert_eq!(check("print super.f;"), vec!["error: Can't use 'super' outside of a class."]);
+}
+
+#[test]
+fn super_in_a_subclass_method_is_fine() {
+    assert_eq!(check("class A { f() { return 1; } }\nclass B < A { f() { return super.f(); } }"), Vec::<String>::new());
+}
+
+#[test]
+fn a_class_inheriting_from_itself_is_an_error() {
+    assert_eq!(check("class A < A {}"), vec!["error: A class can't inherit from itself."]);
+}
+
+#[test]
+fn inheriting_from_another_class_is_fine() {
+    assert_eq!(check("class A {}\nclass B < A {}"), Vec::<String>::new());
+}
+
+#[test]
+fn destructured_names_are_declared_and_assigned() {
+    assert_eq!(check("var (a, b) = (1, 2);\nprint a + b;"), Vec::<String>::new());
+    assert_eq!(check("{ (missing, b) = (1, 2); }"), vec!["error: Undefined variable 'missing'.", "error: Undefined variable 'b'."]);
+}
+
+#[test]
+fn json_lists_a_parse_error_and_a_resolve_error() {
+    let json = Lox::check_json("{ var a = 1; var a = 2; print a; }\nprint 1 +;");
+    assert_eq!(json, concat!(
+        "[{ \"severity\": \"error\", \"line\": 1, \"column\": 18, \"message\": \"Already a variable with this name in this scope.\" }, ",
+        "{ \"severity\": \"error\", \"line\": 2, \"column\": 10, \"message\": \"Expect expression.\" }]",
+    ));
+}
+
+#[test]
+fn json_keeps_warnings_apart_from_errors() {
+    let json = Lox::check_json("{ var unused = 1; }\nprint 1 +;");
+    assert!(json.starts_with("[{ \"severity\": \"warning\", \"line\": 1, \"column\": 7,"), "{}", json);
+    assert!(json.contains("{ \"severity\": \"error\", \"line\": 2,"), "{}", json);
+}
+
+#[test]
+fn json_for_a_clean_program_is_an_empty_array() {
+    assert_eq!(Lox::check_json("print 1;"), "[]");
+}
@@ -0,0 +1,151 @@
+use lox::Lox;
+
+fn run(source: &str) -> (String, Vec<String>) {
+    let result = Lox::run_capture(source);
+    (result.output, result.errors)
+}
+
+#[test]
+fn init_sets_fields_that_methods_read_through_this() {
+    let (output, errors) = run("class Point {\n  init(x) { this.x = x; }\n  get() { return this.x; }\n}\nprint Point(3).get();");
+    assert_eq!(output, "3\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_subclass_reaches_the_superclass_through_super() {
+    let (output, _) = run("class A { value() { return 21; } }\nclass B < A { value() { return super.value() * 2; } }\nprint B().value();");
+    assert_eq!(output, "42\n");
+}
+
+#[test]
+fn a_class_only_has_its_static_methods() {
+    let (_, errors) = run("class A { x() {} }\nprint A.x;");
+    assert_eq!(errors, vec![String::from("[line 2] Undefined property 'x'.")]);
+}
+
+#[test]
+fn a_static_factory_method_returns_a_new_instance() {
+    let source = "class Point {\n  init(x, y) { this.x = x; this.y = y; }\n  class origin() { return Point(0, 0); }\n}\nvar p = Point.origin();\nprint p.x;\nprint p.y;\nprint Point.origin() == Point.origin();";
+    let (output, errors) = run(source);
+    assert_eq!(output, "0\n0\nfalse\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_static_method_cant_use_this() {
+    let (_, errors) = run("class A { class make() { return this; } }");
+    assert!(errors[0].contains("Can't use 'this' in a static method."), "{:?}", errors);
+}
+
+#[test]
+fn property_access_and_calls_chain_left_to_right() {
+    let source = "class Leaf { c() { return \"leaf\"; } }\nclass Branch { init() { this.b = Leaf(); } }\nclass Root { init() { this.a = Branch(); } }\nvar a = Root().a;\nprint a.b.c();\nprint Root().a.b.c();";
+    let (output, errors) = run(source);
+    assert_eq!(output, "leaf\nleaf\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_call_can_return_something_to_call_again() {
+    let source = "class Counter {\n  init() { this.n = 0; }\n  add() { this.n = this.n + 1; return this; }\n}\nprint Counter().add().add().add().n;";
+    let (output, _) = run(source);
+    assert_eq!(output, "3\n");
+}
+
+#[test]
+fn assignment_and_calls_reach_through_a_chain() {
+    let source = "class Box {}\nvar a = Box();\na.b = Box();\na.b.c = Box();\na.b.c.v = 1;\nprint a.b.c.v;\nfun outer() { fun inner() { return a; } return inner; }\nprint outer()().b.c.v;";
+    let (output, errors) = run(source);
+    assert_eq!(output, "1\n1\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn optional_access_on_nil_gives_nil() {
+    let (output, errors) = run("var n = nil;\nprint n?.name;");
+    assert_eq!(output, "nil\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn optional_access_on_an_instance_reads_the_property() {
+    let (output, _) = run("class P { init() { this.name = \"p\"; } }\nprint P()?.name;");
+    assert_eq!(output, "p\n");
+}
+
+#[test]
+fn a_chain_of_optional_accesses_stops_at_the_first_nil() {
+    let source = "class Node { init(next) { this.next = next; this.name = \"node\"; } }\nvar list = Node(Node(nil));\nprint list?.next?.name;\nprint list?.next?.next?.name;\nvar missing = nil;\nprint missing?.next?.name;";
+    let (output, errors) = run(source);
+    assert_eq!(output, "node\nnil\nnil\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+const VECTOR: &str = "class Vector {\n  init(x, y) { this.x = x; this.y = y; }\n  __add__(other) { return Vector(this.x + other.x, this.y + other.y); }\n  __eq__(other) { return this.x == other.x and this.y == other.y; }\n  __lt__(other) { return this.x < other.x; }\n  __index__(i) { if (i == 0) return this.x; return this.y; }\n}\n";
+
+#[test]
+fn plus_calls_add_on_the_instance() {
+    let (output, errors) = run(&format!("{}var v = Vector(1, 2) + Vector(3, 4);\nprint v.x, v.y;", VECTOR));
+    assert_eq!(output, "4 6\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn comparison_and_indexing_call_their_methods() {
+    let (output, errors) = run(&format!("{}var v = Vector(4, 6);\nprint v == Vector(4, 6), v == Vector(0, 0), v < Vector(5, 0), v[0], v[1];", VECTOR));
+    assert_eq!(output, "true false true 4 6\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn an_instance_without_the_method_is_still_a_type_error() {
+    let (_, errors) = run("class Plain {}\nprint Plain() + 1;");
+    assert_eq!(errors, vec![String::from("[line 2] Invalid operator for operands")]);
+}
+
+#[test]
+fn an_instance_without_a_string_method_prints_its_class() {
+    let (output, errors) = run("class Point {}\nprint Point();\nprint [Point()];");
+    assert_eq!(output, "<instance of Point>\n[<instance of Point>]\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn print_uses_to_string() {
+    let (output, errors) = run("class Point { init(name) { this.name = name; } toString() { return \"Point \" + this.name; } }\nprint Point(\"a\");");
+    assert_eq!(output, "Point a\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn str_wins_over_to_string() {
+    let (output, _) = run("class A { __str__() { return \"str\"; } toString() { return \"toString\"; } }\nprint A();");
+    assert_eq!(output, "str\n");
+}
+
+#[test]
+fn adding_an_instance_to_a_string_uses_its_string_method() {
+    let source = "class P { toString() { return \"P!\"; } }\nvar p = P();\nprint \"p = \" + p;\nprint p + \" after\";";
+    let (output, errors) = run(source);
+    assert_eq!(output, "p = P!\nP! after\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn adding_an_instance_without_a_string_method_uses_the_fallback() {
+    let (output, _) = run("class Q {}\nprint \"q = \" + Q();");
+    assert_eq!(output, "q = <instance of Q>\n");
+}
+
+#[test]
+fn add_on_the_instance_still_comes_first() {
+    let (output, _) = run("class V { __add__(other) { return \"added\"; } toString() { return \"V\"; } }\nprint V() + \"x\";");
+    assert_eq!(output, "added\n");
+}
+
+#[test]
+fn a_string_method_that_returns_something_else_is_an_error() {
+    let (_, errors) = run("class B { toString() { return 1; } }\nprint B();");
+    assert_eq!(errors, vec![String::from("[line 1] toString must return a string.")]);
+}
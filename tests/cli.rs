@@ -0,0 +1,73 @@
+use std::fs;
+use std::process::{ Command, Output };
+
+// runs the built binary on a script written to a temporary file
+fn lox(name: &str, source: &str, flags: &[&str]) -> Output {
+    let path = std::env::temp_dir().join(format!("lox-cli-{}-{}.lox", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox")).args(flags).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn timing_leaves_the_program_output_alone() {
+    let source = "var total = 0;\nfor (var i = 0; i < 10; i++) total += i;\nprint total;";
+    let plain = lox("untimed", source, &[]);
+    let timed = lox("timed", source, &["--time"]);
+    assert_eq!(String::from_utf8_lossy(&timed.stdout), "45\n");
+    assert_eq!(timed.stdout, plain.stdout);
+    assert!(plain.stderr.is_empty());
+}
+
+#[test]
+fn timing_reports_each_phase_on_stderr() {
+    let timed = lox("phases", "print 1;", &["--time"]);
+    let phases: Vec<String> = String::from_utf8_lossy(&timed.stderr).lines()
+        .map(|line| line.split_whitespace().next().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(phases, vec!["scan", "parse", "run"]);
+}
+
+#[test]
+fn optimizing_leaves_the_program_output_alone() {
+    let source = "print 2 + 3 * 4;\nprint \"a\" + \"b\";\nprint 1 < 2 == true;\nvar x = 10;\nprint x + 2 * 3;";
+    let plain = lox("unoptimized", source, &[]);
+    let optimized = lox("optimized", source, &["--optimize"]);
+    assert_eq!(String::from_utf8_lossy(&optimized.stdout), "14\nab\ntrue\n16\n");
+    assert_eq!(optimized.stdout, plain.stdout);
+}
+
+#[test]
+fn optimizing_leaves_division_by_zero_to_fail_at_run_time() {
+    let source = "print 1;\nprint 1 ~/ 0;\nprint 1 / 0;";
+    let plain = lox("zero-unoptimized", source, &[]);
+    let optimized = lox("zero-optimized", source, &["--optimize"]);
+    assert_eq!(String::from_utf8_lossy(&optimized.stdout), "1\n[line 2] Division by zero.\ninf\n");
+    assert_eq!(optimized.stdout, plain.stdout);
+}
+
+#[test]
+fn optimizing_keeps_the_branches_that_run() {
+    let source = "if (false) print 1; else print 2;\nif (1 < 2) print \"yes\"; else print \"no\";\nwhile (false) print 3;\nvar n = 0;\nwhile (n < 2) n++;\nprint n;";
+    let plain = lox("branches-unoptimized", source, &[]);
+    let optimized = lox("branches-optimized", source, &["--optimize"]);
+    assert_eq!(String::from_utf8_lossy(&optimized.stdout), "2\nyes\n2\n");
+    assert_eq!(optimized.stdout, plain.stdout);
+}
+
+#[test]
+fn check_prints_each_problem_and_fails_without_running() {
+    let output = lox("check", "print \"ran\";\nprint 1 +;", &["--check"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.ends_with(":2:10: error: Expect expression.\n"), "{:?}", stdout);
+    assert!(!stdout.contains("ran"), "{:?}", stdout);
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn check_passes_a_file_with_only_warnings() {
+    let output = lox("check-clean", "{ var unused = 1; }", &["--check"]);
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with(":1:7: warning: Unused variable 'unused'.\n"), "{:?}", output);
+    assert_eq!(output.status.code(), Some(0));
+}
@@ -0,0 +1,53 @@
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+use lox::{ Lox, LoxError, RunOptions, Token, TokenType };
+
+static SCRIPTS: AtomicUsize = AtomicUsize::new(0);
+
+// runs the built binary with the given flags on a script and returns what it printed
+fn run(source: &str, flags: &[&str]) -> String {
+    let name = format!("lox-color-{}-{}.lox", std::process::id(), SCRIPTS.fetch_add(1, Ordering::Relaxed));
+    let path = std::env::temp_dir().join(name);
+    fs::write(&path, format!("{}\n", source)).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox")).args(flags).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+// the tests' stdout is a pipe rather than a terminal, so it never gets colors
+#[test]
+fn diagnostics_sent_to_a_pipe_are_plain() {
+    let output = run("print 1 +;", &[]);
+    assert_eq!(output, "Error on line 1 at ';'. Expect expression.\n    print 1 +;\n             ^\nParser error.\n");
+    assert!(!output.contains('\x1b'));
+}
+
+#[test]
+fn no_color_is_accepted_before_the_script() {
+    assert_eq!(run("print nope;", &["--no-color"]), "[line 1] Undefined variable 'nope'.\n");
+}
+
+#[test]
+fn diagnostics_are_colored_only_when_asked() {
+    let token = Token::new(TokenType::Identifier, String::from("x"), 3, 5);
+    let diagnostic = Lox::error(&token, String::from("Something is wrong."));
+
+    let plain = diagnostic.render(false);
+    assert_eq!(plain, "Error on line 3 at 'x'. Something is wrong.");
+    assert_eq!(diagnostic.to_string(), plain);
+
+    let colored = diagnostic.render(true);
+    assert!(colored.contains('\x1b'), "{:?}", colored);
+    assert!(colored.contains("Something is wrong."));
+}
+
+#[test]
+fn compile_errors_are_plain_unless_the_options_ask_for_color() {
+    let Err(LoxError::Parse(plain)) = Lox::compile("print ;") else { panic!("expected a parse error") };
+    assert!(!plain[0].contains('\x1b'), "{:?}", plain);
+    let options = RunOptions { color: true, ..RunOptions::default() };
+    let Err(LoxError::Parse(colored)) = Lox::compile_with("print ;", options) else { panic!("expected a parse error") };
+    assert!(colored[0].contains('\x1b'), "{:?}", colored);
+}
@@ -0,0 +1,317 @@
+use std::cell::RefCell;
+use std::io::{ self, Write };
+use std::rc::Rc;
+
+use lox::{ Arity, Expr, Interpreter, InterpreterConfig, Lox, Program, Stmt, Token, TokenType, Value };
+
+// a writer the test can still read after handing it to the interpreter
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn run_capture_collects_what_the_program_prints() {
+    let result = Lox::run_capture("print 1; print 2;");
+    assert_eq!(result.output, "1\n2\n");
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+}
+
+#[test]
+fn run_capture_collects_errors_instead_of_printing_them() {
+    let result = Lox::run_capture("print 1;\nprint -\"a\";\nprint 3;");
+    assert_eq!(result.output, "1\n3\n");
+    assert_eq!(result.errors, vec![String::from("[line 2] Operand must be a number.")]);
+}
+
+#[test]
+fn a_compiled_program_runs_three_times_with_fresh_globals() {
+    let program = Lox::compile("var count = 0;\ncount = count + 1;\nprint count;").unwrap();
+    for _ in 0..3 {
+        let output = Capture::default();
+        let mut interpreter = Interpreter::new().with_output(output.clone());
+        interpreter.run(&program).unwrap();
+        assert_eq!(output.text(), "1\n");
+    }
+}
+
+#[test]
+fn a_runtime_error_comes_back_from_run() {
+    let program = Lox::compile("var a = 1;\nprint a + nil;").unwrap();
+    let error = Interpreter::new().with_output(Capture::default()).run(&program).expect_err("should fail at run time");
+    assert_eq!(error.to_string(), "[line 2] Invalid operator for operands");
+}
+
+#[test]
+fn a_parse_error_comes_back_from_compile() {
+    assert!(Lox::compile("print 1 +;").is_err());
+}
+
+#[test]
+fn input_reads_a_line_from_the_injected_reader() {
+    let output = Capture::default();
+    let mut interpreter = Interpreter::new().with_input(io::Cursor::new("Ada\nsecond line\n")).with_output(output.clone());
+    let program = Lox::compile("var name = input(\"name? \");\nprint \"hello \" + name;\nprint input();\nprint input();").unwrap();
+    interpreter.run(&program).unwrap();
+    assert_eq!(output.text(), "name? hello Ada\nsecond line\nnil\n");
+}
+
+fn sandboxed(config: InterpreterConfig, source: &str) -> Result<(), String> {
+    let mut interpreter = Interpreter::with_config(config).with_output(Capture::default());
+    interpreter.run(&Lox::compile(source).unwrap()).map(|_| ()).map_err(|error| error.to_string())
+}
+
+#[test]
+fn io_natives_are_missing_when_io_is_disabled() {
+    let config = InterpreterConfig { allow_io: false, ..InterpreterConfig::default() };
+    assert_eq!(sandboxed(config, "readFile(\"x\");"), Err(String::from("[line 1] Undefined variable 'readFile'.")));
+    let config = InterpreterConfig { allow_io: false, ..InterpreterConfig::default() };
+    assert_eq!(sandboxed(config, "writeFile(\"x\", 1);"), Err(String::from("[line 1] Undefined variable 'writeFile'.")));
+    let config = InterpreterConfig { allow_io: false, ..InterpreterConfig::default() };
+    assert_eq!(sandboxed(config, "input();"), Err(String::from("[line 1] Undefined variable 'input'.")));
+    let config = InterpreterConfig { allow_io: false, ..InterpreterConfig::default() };
+    assert_eq!(sandboxed(config, "exit(1);"), Err(String::from("[line 1] Undefined variable 'exit'.")));
+}
+
+#[test]
+fn a_sandboxed_infinite_loop_halts() {
+    let config = InterpreterConfig { allow_io: false, max_steps: Some(10_000), ..InterpreterConfig::default() };
+    assert_eq!(sandboxed(config, "while (true) {}"), Err(String::from("Execution step limit exceeded.")));
+}
+
+#[test]
+fn a_tight_loop_stops_at_a_small_step_limit() {
+    let config = InterpreterConfig { max_steps: Some(100), ..InterpreterConfig::default() };
+    assert_eq!(sandboxed(config, "var i = 0;\nwhile (true) i = i + 1;"), Err(String::from("Execution step limit exceeded.")));
+}
+
+#[test]
+fn a_program_under_the_step_limit_finishes() {
+    let config = InterpreterConfig { max_steps: Some(1_000), ..InterpreterConfig::default() };
+    assert_eq!(sandboxed(config, "var i = 0;\nwhile (i < 10) i = i + 1;"), Ok(()));
+}
+
+#[test]
+fn unbounded_recursion_is_a_runtime_error() {
+    let config = InterpreterConfig { max_call_depth: 50, ..InterpreterConfig::default() };
+    let result = sandboxed(config, "fun down(n) { return down(n + 1); }\ndown(0);");
+    assert_eq!(result, Err(String::from("[line 1] Stack overflow.")));
+}
+
+// these run on the test thread's own 2 MB stack, far smaller than the one the binary runs on
+#[test]
+fn the_default_depth_is_reached_before_the_native_stack_runs_out() {
+    let errors = Lox::run_capture("fun down(n) { return down(n + 1); }\ndown(0);").errors;
+    assert_eq!(errors, vec![String::from("[line 1] Stack overflow.")]);
+}
+
+#[test]
+fn deep_expressions_in_a_recursive_call_still_stop_cleanly() {
+    let source = "fun f(n) { { { { if (true) { return 1 + (1 + (1 + (1 + (1 + f(n + 1))))); } } } } }\nf(0);";
+    assert_eq!(Lox::run_capture(source).errors, vec![String::from("[line 1] Stack overflow.")]);
+}
+
+#[test]
+fn a_bigger_stack_can_allow_deeper_recursion() {
+    let source = "fun f(n) { if (n == 0) return 0; return f(n - 1); }\nf(500);";
+    let config = InterpreterConfig { max_depth: 5000, ..InterpreterConfig::default() };
+    assert_eq!(Lox::run_capture(source).errors, vec![String::from("[line 1] Stack overflow.")]);
+    let result = std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(move || sandboxed(config, source))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn a_program_built_from_constructors_runs() {
+    let sum = Expr::binary(Expr::number(1.0), TokenType::Plus, Expr::number(2.0));
+    let program = Program { statements: vec![Rc::new(Stmt::print(vec![sum]))], spans: Vec::new() };
+    let output = Capture::default();
+    Interpreter::new().with_output(output.clone()).run(&program).unwrap();
+    assert_eq!(output.text(), "3\n");
+}
+
+// the parser never lets a jump out of a function or block expression, but a hand-built program can hold one
+#[test]
+fn a_constructed_jump_out_of_a_function_is_a_runtime_error() {
+    let keyword = Token::new(TokenType::Break, String::from("break"), 1, 1);
+    let body = Rc::new(vec![Stmt::Break { keyword, label: None }]);
+    let function = Stmt::Function { name: Token::new(TokenType::Identifier, String::from("f"), 1, 1), params: Vec::new(), body };
+    let call = Stmt::expression(Expr::call(Expr::variable("f"), Vec::new()));
+    let program = Program { statements: vec![Rc::new(function), Rc::new(call)], spans: Vec::new() };
+    let result = Interpreter::new().run(&program).map_err(|error| error.message);
+    assert_eq!(result, Err(String::from("Can't use 'break' outside of a loop.")));
+}
+
+#[test]
+fn a_constructed_jump_out_of_a_block_expression_is_a_runtime_error() {
+    let keyword = Token::new(TokenType::Continue, String::from("continue"), 1, 1);
+    let block = Expr::Block { statements: vec![Stmt::Continue { keyword, label: None }], final_expr: Box::new(Expr::int(1)) };
+    let program = Program { statements: vec![Rc::new(Stmt::print(vec![block]))], spans: Vec::new() };
+    let result = Interpreter::new().run(&program).map_err(|error| error.message);
+    assert_eq!(result, Err(String::from("Can't use 'continue' outside of a loop.")));
+}
+
+#[test]
+fn constructed_variables_assignments_and_loops_run() {
+    let statements = vec![
+        Stmt::var("i", Some(Expr::int(0))),
+        Stmt::while_loop(
+            Expr::binary(Expr::variable("i"), TokenType::Less, Expr::int(3)),
+            Stmt::block(vec![
+                Stmt::print(vec![Expr::variable("i")]),
+                Stmt::expression(Expr::assign("i", Expr::binary(Expr::variable("i"), TokenType::Plus, Expr::int(1)))),
+            ]),
+        ),
+    ];
+    let program = Program { statements: statements.into_iter().map(Rc::new).collect(), spans: Vec::new() };
+    let output = Capture::default();
+    Interpreter::new().with_output(output.clone()).run(&program).unwrap();
+    assert_eq!(output.text(), "0\n1\n2\n");
+}
+
+#[test]
+fn a_host_native_can_be_called_from_lox() {
+    let output = Capture::default();
+    let mut interpreter = Interpreter::new().with_output(output.clone());
+    interpreter.define_native("double", Arity::Fixed(1), |_, arguments| match arguments[0] {
+        Value::Int(n) => Ok(Value::Int(n * 2)),
+        Value::Number(x) => Ok(Value::Number(x * 2.0)),
+        _ => Ok(Value::Nil),
+    });
+    interpreter.run(&Lox::compile("print double(21);\nprint double(1.5);").unwrap()).unwrap();
+    assert_eq!(output.text(), "42\n3\n");
+}
+
+#[test]
+fn a_host_native_checks_its_arity() {
+    let mut interpreter = Interpreter::new().with_output(Capture::default());
+    interpreter.define_native("double", Arity::Fixed(1), |_, arguments| Ok(arguments[0].clone()));
+    let error = interpreter.run(&Lox::compile("double(1, 2);").unwrap()).expect_err("two arguments is too many");
+    assert_eq!(error.to_string(), "[line 1] Expected 1 arguments but got 2.");
+}
+
+#[test]
+fn rust_values_round_trip_through_value() {
+    assert_eq!(f64::try_from(Value::from(1.5)).unwrap(), 1.5);
+    assert_eq!(i64::try_from(Value::from(7_i64)).unwrap(), 7);
+    assert!(bool::try_from(Value::from(true)).unwrap());
+    assert_eq!(String::try_from(Value::from(String::from("lox"))).unwrap(), "lox");
+    assert_eq!(String::try_from(Value::from("str")).unwrap(), "str");
+}
+
+#[test]
+fn an_int_converts_to_f64() {
+    assert_eq!(f64::try_from(Value::from(3_i64)).unwrap(), 3.0);
+}
+
+#[test]
+fn converting_the_wrong_kind_of_value_is_an_error() {
+    let error = f64::try_from(Value::from("1")).expect_err("a string isn't a number");
+    assert_eq!(error.message, "Can't convert string to f64.");
+    let error = bool::try_from(Value::Nil).expect_err("nil isn't a boolean");
+    assert_eq!(error.message, "Can't convert nil to bool.");
+}
+
+#[test]
+fn run_program_keeps_natives_and_globals_between_programs() {
+    let output = Capture::default();
+    let mut interpreter = Interpreter::new().with_output(output.clone());
+    interpreter.define_native("answer", Arity::Fixed(0), |_, _| Ok(Value::Int(42)));
+    interpreter.run_program(&Lox::compile("var setup = answer() + 1;").unwrap()).unwrap();
+    interpreter.run_program(&Lox::compile("print answer(), setup;").unwrap()).unwrap();
+    assert_eq!(output.text(), "42 43\n");
+}
+
+#[test]
+fn run_starts_over_but_keeps_host_natives() {
+    let output = Capture::default();
+    let mut interpreter = Interpreter::new().with_output(output.clone());
+    interpreter.define_native("answer", Arity::Fixed(0), |_, _| Ok(Value::Int(42)));
+    interpreter.run(&Lox::compile("var setup = 1;").unwrap()).unwrap();
+    let error = interpreter.run(&Lox::compile("print answer();\nprint setup;").unwrap()).expect_err("setup is gone");
+    assert_eq!(output.text(), "42\n");
+    assert_eq!(error.to_string(), "[line 2] Undefined variable 'setup'.");
+}
+
+#[test]
+fn values_compare_the_way_lox_does() {
+    assert!(Value::Int(1) == Value::Number(1.0));
+    assert!(Value::Int(1) != Value::Int(2));
+    assert!(Value::from("a") == Value::from("a"));
+    assert!(Value::Nil != Value::Boolean(false));
+}
+
+#[test]
+fn stats_start_over_for_each_program() {
+    let mut interpreter = Interpreter::new().with_output(Capture::default());
+    interpreter.run(&Lox::compile("fun f() {}\nf();\nf();").unwrap()).unwrap();
+    assert_eq!(interpreter.stats().calls, 2);
+    interpreter.run(&Lox::compile("fun g() {}\ng();").unwrap()).unwrap();
+    assert_eq!(interpreter.stats().calls, 1);
+}
+
+#[test]
+fn exit_stops_the_program_and_reports_its_code() {
+    let result = Lox::run_capture("print 1;\nexit(3);\nprint 2;");
+    assert_eq!(result.output, "1\n");
+    assert_eq!(result.exit_code, Some(3));
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+}
+
+#[test]
+fn exit_comes_back_to_an_embedder_instead_of_ending_the_process() {
+    let output = Capture::default();
+    let mut interpreter = Interpreter::new().with_output(output.clone());
+    let stopped = interpreter.run(&Lox::compile("fun quit() { exit(3); }\nprint 1;\nquit();\nprint 2;").unwrap());
+    assert_eq!(stopped.expect("exit isn't an error"), Some(3));
+    assert_eq!(output.text(), "1\n");
+}
+
+#[test]
+fn trace_logs_each_subexpression_with_its_value() {
+    let trace = Capture::default();
+    let config = InterpreterConfig { trace: true, ..InterpreterConfig::default() };
+    let mut interpreter = Interpreter::with_config(config).with_output(Capture::default()).with_trace_output(trace.clone());
+    interpreter.run(&Lox::compile("print 1 + 2 * 3;").unwrap()).unwrap();
+    assert_eq!(trace.text(), "  1 => 1\n    2 => 2\n    3 => 3\n  (* 2 3) => 6\n(+ 1 (* 2 3)) => 7\n");
+}
+
+#[test]
+fn trace_prints_every_kind_of_expression() {
+    let trace = Capture::default();
+    let config = InterpreterConfig { trace: true, ..InterpreterConfig::default() };
+    let mut interpreter = Interpreter::with_config(config).with_output(Capture::default()).with_trace_output(trace.clone());
+    let source = "class A { init() { this.x = [1, (2, 3)]; } }\nvar a = A();\nvar i = 0;\ni++;\nprint a.x, a?.x, { 4 };";
+    interpreter.run(&Lox::compile(source).unwrap()).unwrap();
+    let text = trace.text();
+    assert!(!text.lines().any(|line| line.trim_start().starts_with("=>")), "{}", text);
+    for expected in ["(= (. this x) (list 1 (tuple 2 3))) =>", "(i ++) => 0", "(. a x) =>", "(?. a x) =>", "(block 4) => 4"] {
+        assert!(text.contains(expected), "missing {:?} in\n{}", expected, text);
+    }
+}
+
+#[test]
+fn exit_inside_try_and_a_block_still_stops_run_program() {
+    let mut interpreter = Interpreter::new().with_output(Capture::default());
+    let program = Lox::compile("try { var x = { exit(4); 1 }; } catch (e) { print e; }\nprint 2;").unwrap();
+    assert_eq!(interpreter.run_program(&program).expect("exit isn't an error"), Some(4));
+    assert_eq!(interpreter.run_program(&Lox::compile("print 1;").unwrap()).unwrap(), None);
+}
@@ -0,0 +1,96 @@
+use lox::{ Interpreter, Lox, RuntimeError, Severity, Token, TokenType };
+
+// the first runtime error a program raises, with its output thrown away
+fn runtime_error(source: &str) -> RuntimeError {
+    let program = Lox::compile(source).unwrap_or_else(|_| panic!("failed to compile {:?}", source));
+    let mut interpreter = Interpreter::new().with_output(std::io::sink());
+    interpreter.run(&program).expect_err("should fail at run time")
+}
+
+#[test]
+fn a_type_mismatch_on_line_two_reports_line_two() {
+    let result = Lox::run_capture("var a = 1;\nprint a + \"b\";");
+    assert_eq!(result.errors, vec![String::from("[line 2] Invalid operator for operands")]);
+}
+
+#[test]
+fn operator_errors_point_at_the_operator() {
+    let error = runtime_error("var a = 1;\n\nprint a\n  < \"b\";");
+    let token = error.token.expect("the operator");
+    assert_eq!(token.line, 4);
+    assert_eq!(token.lexeme, "<");
+}
+
+#[test]
+fn errors_carry_the_token_they_happened_at() {
+    let error = runtime_error("var x = 1;\nprint -\"text\";");
+    assert_eq!(error.token.as_ref().map(|token| token.line), Some(2));
+    assert_eq!(error.message, "Operand must be a number.");
+
+    let error = runtime_error("fun f(a) {}\n\n\nf();");
+    assert_eq!(error.token.as_ref().map(|token| token.line), Some(4));
+    assert_eq!(error.message, "Expected 1 arguments but got 0.");
+}
+
+#[test]
+fn an_undefined_variable_error_names_the_variable() {
+    let error = runtime_error("print nope;");
+    match error {
+        RuntimeError { token: Some(token), message, .. } => {
+            assert_eq!(token.lexeme, "nope");
+            assert_eq!(message, "Undefined variable 'nope'.");
+        }
+        error => panic!("expected an error at a token, got {:?}", error),
+    }
+}
+
+#[test]
+fn an_error_at_a_token_keeps_its_position_and_lexeme() {
+    let diagnostics = Lox::check("{\n  var count = 1;\n  var count = 2;\n  print count;\n}");
+    let diagnostic = &diagnostics[0];
+    assert_eq!(diagnostic.line, 3);
+    assert_eq!(diagnostic.column, Some(7));
+    assert_eq!(diagnostic.lexeme, "count");
+    assert_eq!(diagnostic.message, "Already a variable with this name in this scope.");
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(diagnostic.to_string(), "Error on line 3 at 'count'. Already a variable with this name in this scope.");
+}
+
+#[test]
+fn an_error_at_the_end_of_input_has_no_lexeme() {
+    let diagnostics = Lox::check("print 1");
+    assert_eq!(diagnostics[0].lexeme, "");
+    assert_eq!(diagnostics[0].to_string(), "Error on line 1 at end. Expect ';' after value, found end of input.");
+}
+
+#[test]
+fn an_error_built_from_a_token_renders_like_the_parser_does() {
+    let token = Token::new(TokenType::Identifier, String::from("count"), 3, 7);
+    let diagnostic = Lox::error(&token, String::from("Something went wrong."));
+    assert_eq!((diagnostic.line, diagnostic.column, diagnostic.lexeme.as_str()), (3, Some(7), "count"));
+    assert_eq!(diagnostic.to_string(), "Error on line 3 at 'count'. Something went wrong.");
+    let diagnostic = Lox::error(&Token::new(TokenType::EoF, String::new(), 2, 1), String::from("Expect ';'."));
+    assert_eq!(diagnostic.to_string(), "Error on line 2 at end. Expect ';'.");
+}
+
+#[test]
+fn negating_twice_gives_the_number_back() {
+    let result = Lox::run_capture("print --5 == 5, - -5, -(-2.5);");
+    assert_eq!(result.output, "true 5 2.5\n");
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+}
+
+#[test]
+fn negating_a_string_is_an_error_on_its_line() {
+    let result = Lox::run_capture("print 1;\nprint -\"x\";");
+    assert_eq!(result.errors, vec![String::from("[line 2] Operand must be a number.")]);
+}
+
+#[test]
+fn negating_a_boolean_names_the_operand_and_the_line() {
+    let error = runtime_error("var a = 1;\n\nprint -true;");
+    assert_eq!(error.message, "Operand must be a number.");
+    let token = error.token.expect("the operator");
+    assert_eq!((token.line, token.lexeme.as_str()), (3, "-"));
+    assert_eq!(Lox::run_capture("print -true;").errors, vec![String::from("[line 1] Operand must be a number.")]);
+}
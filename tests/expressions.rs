@@ -0,0 +1,314 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lox::{ Arity, Interpreter, Lox, Value };
+
+fn run(source: &str) -> (String, Vec<String>) {
+    let result = Lox::run_capture(source);
+    (result.output, result.errors)
+}
+
+// the printed value of one expression, which must not raise an error
+fn eval(expr: &str) -> String {
+    let (output, errors) = run(&format!("print {};", expr));
+    assert!(errors.is_empty(), "{} gave {:?}", expr, errors);
+    output.trim_end_matches('\n').to_string()
+}
+
+// the value an expression evaluates to, for telling an Int from a Number where print and type() can't
+fn value(expr: &str) -> Value {
+    let kept = Rc::new(RefCell::new(Value::Nil));
+    let keep = Rc::clone(&kept);
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("keep", Arity::Fixed(1), move |_, arguments| {
+        *keep.borrow_mut() = arguments[0].clone();
+        Ok(Value::Nil)
+    });
+    interpreter.run(&Lox::compile(&format!("keep({});", expr)).unwrap()).unwrap();
+    kept.replace(Value::Nil)
+}
+
+#[test]
+fn coalescing_picks_the_right_side_only_for_nil() {
+    assert_eq!(eval("nil ?? 1"), "1");
+    assert_eq!(eval("false ?? 1"), "false");
+    assert_eq!(eval("0 ?? 1"), "0");
+    assert_eq!(eval("nil ?? nil ?? 3"), "3");
+}
+
+#[test]
+fn coalescing_doesnt_evaluate_the_right_side_for_a_value() {
+    let (output, errors) = run("var calls = 0;\nfun bump() { calls = calls + 1; return 2; }\nprint 1 ?? bump();\nprint nil ?? bump();\nprint calls;");
+    assert_eq!(output, "1\n2\n1\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn compound_assignment_applies_each_operator() {
+    let (output, errors) = run("var a = 10;\na += 5;\nprint a;\na -= 3;\nprint a;\na *= 2;\nprint a;\na /= 8;\nprint a;\nvar s = \"ab\";\ns += \"c\";\nprint s;");
+    assert_eq!(output, "15\n12\n24\n3\nabc\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn compound_assignment_is_an_expression() {
+    let (output, errors) = run("var a = 1;\nprint a += 2;\nprint a;");
+    assert_eq!(output, "3\n3\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn compound_assignment_needs_a_variable() {
+    let (_, errors) = run("var a = 1;\n(a) += 1;");
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].starts_with("Error on line 2 at '+='. Invalid assignment target."), "{:?}", errors);
+}
+
+#[test]
+fn postfix_increment_gives_the_old_value() {
+    let (output, errors) = run("var a = 1;\nprint a++;\nprint a;");
+    assert_eq!(output, "1\n2\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn prefix_increment_gives_the_new_value() {
+    let (output, errors) = run("var a = 1;\nprint ++a;\nprint a;");
+    assert_eq!(output, "2\n2\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn decrement_works_both_ways() {
+    let (output, errors) = run("var a = 5;\nprint a--;\nprint a;\nprint --a;\nprint a;");
+    assert_eq!(output, "5\n4\n3\n3\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn only_a_variable_can_be_incremented() {
+    let (_, errors) = run("1++;");
+    assert!(errors[0].contains("Invalid increment target."), "{:?}", errors);
+    let (_, errors) = run("var s = \"a\";\ns++;");
+    assert_eq!(errors, vec![String::from("[line 2] Operand must be a number.")]);
+}
+
+// parentheses make a tuple, so the comma operator shows up in block values and expression statements
+#[test]
+fn a_comma_expression_gives_its_last_operand() {
+    assert_eq!(eval("{ 1, 2, 3 } == 3"), "true");
+}
+
+#[test]
+fn a_comma_expression_evaluates_every_operand_in_order() {
+    let (output, errors) = run("var a = 0;\nvar r = { a = 1, a = a + 1, a * 10 };\nprint r, a;\na = 5, a = a + 1;\nprint a;");
+    assert_eq!(output, "20 2\n6\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+// `(1, 2, 3) == 3` from the comma operator's request: tuples took the parenthesized form
+#[test]
+fn commas_inside_parentheses_make_a_tuple_not_a_comma_expression() {
+    assert_eq!(eval("(1, 2, 3) == 3"), "false");
+    assert_eq!(eval("(1, 2, 3)"), "(1, 2, 3)");
+    assert_eq!(eval("type((1, 2, 3))"), "tuple");
+}
+
+#[test]
+fn call_arguments_are_still_separated_by_commas() {
+    let (output, errors) = run("fun pick(a, b) { return b; }\nprint pick(1, 2);\nprint pick((1, 2), 3);");
+    assert_eq!(output, "2\n3\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_block_expression_gives_its_final_expression() {
+    assert_eq!(eval("{ var t = 3; t * 2 }"), "6");
+}
+
+#[test]
+fn a_block_expressions_scope_ends_with_it() {
+    let (output, errors) = run("var t = \"outer\";\nvar x = { var t = 21; t * 2 };\nprint x;\nprint t;\nvar y = { var inner = 1; inner };\nprint inner;");
+    assert_eq!(output, "42\nouter\n");
+    assert_eq!(errors, vec![String::from("[line 6] Undefined variable 'inner'.")]);
+}
+
+#[test]
+fn only_nil_and_false_are_falsey() {
+    assert_eq!(eval("!nil"), "true");
+    assert_eq!(eval("!false"), "true");
+    assert_eq!(eval("!true"), "false");
+    assert_eq!(eval("!0"), "false");
+    assert_eq!(eval("!\"\""), "false");
+    assert_eq!(eval("!!0"), "true");
+}
+
+#[test]
+fn star_repeats_strings_and_lists() {
+    assert_eq!(eval("\"ab\" * 3"), "ababab");
+    assert_eq!(eval("[0] * 3"), "[0, 0, 0]");
+    assert_eq!(eval("\"ab\" * 3 == \"ababab\""), "true");
+    assert_eq!(eval("\"ab\" * 0"), "");
+    assert_eq!(eval("2 * 3.5"), "7");
+}
+
+#[test]
+fn a_fractional_or_negative_repeat_count_is_a_runtime_error() {
+    let (_, errors) = run("print \"ab\" * 1.5;\nprint [0] * -1;");
+    assert_eq!(errors, vec![
+        String::from("[line 1] Repeat count must be a non-negative whole number."),
+        String::from("[line 2] Repeat count must be a non-negative whole number."),
+    ]);
+}
+
+#[test]
+fn a_repetition_too_large_to_build_is_a_catchable_error() {
+    let (output, errors) = run("print \"ab\" * 1000000000000;\nprint [1] * 100000000000;\ntry { [1, 2] * 9223372036854775807; } catch (e) { print e; }");
+    assert_eq!(output, "Repetition result too large.\n");
+    assert_eq!(errors, vec![
+        String::from("[line 1] Repetition result too large."),
+        String::from("[line 2] Repetition result too large."),
+    ]);
+}
+
+#[test]
+fn numbers_print_in_their_shortest_form() {
+    let table = [
+        ("1.0", "1"),
+        ("100.0", "100"),
+        ("1.5", "1.5"),
+        ("-2.5", "-2.5"),
+        ("-0.0", "-0"),
+        ("1.0 / 3", "0.3333333333333333"),
+        ("0.1 + 0.2", "0.30000000000000004"),
+        ("123456789.0 * 1000000000000", "123456789000000000000"),
+        ("0.0000001", "0.0000001"),
+        ("1000000000.0 * 1000000000000", "1e21"),
+        ("1.0 / 100000000", "1e-8"),
+        ("-1.0 / 100000000", "-1e-8"),
+        ("1.0 / 0", "inf"),
+        ("-1.0 / 0", "-inf"),
+        ("0.0 / 0", "nan"),
+    ];
+    for (expr, expected) in table {
+        assert_eq!(eval(expr), expected, "for {}", expr);
+    }
+}
+
+#[test]
+fn numbers_print_the_same_inside_a_list() {
+    assert_eq!(eval("[1.0, 2.5, -0.0]"), "[1, 2.5, -0]");
+}
+
+#[test]
+fn tilde_slash_divides_ints_to_an_int() {
+    assert_eq!(eval("5 ~/ 2 == 2"), "true");
+    assert!(matches!(value("5 ~/ 2"), Value::Int(2)));
+    assert_eq!(eval("-5 ~/ 2"), "-3");
+}
+
+#[test]
+fn slash_always_gives_a_number() {
+    assert_eq!(eval("5 / 2 == 2.5"), "true");
+    assert!(matches!(value("4 / 2"), Value::Number(_)));
+    assert_eq!(eval("type(4 / 2)"), "number");
+}
+
+#[test]
+fn mixing_an_int_with_a_number_gives_a_number() {
+    assert!(matches!(value("2 + 3"), Value::Int(5)));
+    assert!(matches!(value("2 + 0.5"), Value::Number(_)));
+    assert_eq!(eval("2 + 0.5"), "2.5");
+    assert_eq!(eval("3 * 1.5"), "4.5");
+    assert_eq!(eval("2 == 2.0"), "true");
+}
+
+#[test]
+fn modulo_takes_the_sign_of_the_left_operand() {
+    let table = [
+        ("7 % 3", "1"),
+        ("-7 % 3", "-1"),
+        ("7 % -3", "1"),
+        ("-7 % -3", "-1"),
+        ("7.5 % 2", "1.5"),
+        ("-7.5 % 2", "-1.5"),
+        ("7.5 % -2", "1.5"),
+        ("6 % 3", "0"),
+    ];
+    for (expr, expected) in table {
+        assert_eq!(eval(expr), expected, "for {}", expr);
+    }
+}
+
+#[test]
+fn an_int_equals_the_number_with_the_same_value() {
+    assert_eq!(eval("1 == 1.0"), "true");
+    assert_eq!(eval("1.0 == 1"), "true");
+    assert_eq!(eval("1 != 2.0"), "true");
+    assert_eq!(eval("1 != 1.0"), "false");
+}
+
+#[test]
+fn int_equality_is_exact() {
+    // both sides round to the same f64, so they would compare equal if ints were promoted here
+    assert_eq!(eval("9007199254740993 == 9007199254740992"), "false");
+    assert_eq!(eval("9007199254740993 == 9007199254740993"), "true");
+    assert_eq!(eval("1 == 2"), "false");
+}
+
+#[test]
+fn a_parenthesized_list_with_commas_is_a_tuple() {
+    assert_eq!(eval("(1, \"two\", nil)"), "(1, two, nil)");
+    assert_eq!(eval("type((1, 2))"), "tuple");
+    assert_eq!(eval("(1 + 2)"), "3");
+}
+
+#[test]
+fn a_tuple_destructures_into_variables() {
+    let (output, errors) = run("var pair = (1, 2);\nvar (a, b) = pair;\nprint a, b;\nvar (x, y) = (\"x\", \"y\");\nprint x + y;");
+    assert_eq!(output, "1 2\nxy\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn destructuring_the_wrong_number_of_values_is_a_runtime_error() {
+    let (_, errors) = run("var (a, b) = (1, 2, 3);");
+    assert_eq!(errors, vec![String::from("[line 1] Expected 2 values to unpack but got 3.")]);
+}
+
+#[test]
+fn equality_compares_values_of_every_kind() {
+    assert_eq!(eval("1 == 1"), "true");
+    assert_eq!(eval("\"a\" == \"a\""), "true");
+    assert_eq!(eval("\"a\" == \"b\""), "false");
+    assert_eq!(eval("nil == nil"), "true");
+    assert_eq!(eval("nil == false"), "false");
+    assert_eq!(eval("1 == \"1\""), "false");
+    assert_eq!(eval("[1, \"a\"] == [1, \"a\"]"), "true");
+}
+
+#[test]
+fn comparing_leaves_both_operands_usable() {
+    let (output, _) = run("var a = \"text\";\nvar b = \"text\";\nprint a == b, a, b;");
+    assert_eq!(output, "true text text\n");
+}
+
+#[test]
+fn a_byte_string_indexes_to_byte_values() {
+    let (output, errors) = run("var b = b\"AB\\x00\\xff\";\nprint b[0], b[3];\nprint len(b), len(b\"\");");
+    assert_eq!(output, "65 255\n4 0\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(matches!(value("b\"AB\"[1]"), Value::Int(66)));
+}
+
+#[test]
+fn a_byte_string_prints_as_a_literal() {
+    assert_eq!(eval("b\"AB\\x00\\xff\""), "b\"AB\\0\\xff\"");
+    assert_eq!(eval("type(b\"x\")"), "bytes");
+}
+
+#[test]
+fn indexing_past_the_end_of_a_byte_string_is_an_error() {
+    let (_, errors) = run("print b\"ab\"[2];");
+    assert_eq!(errors, vec![String::from("[line 1] Index 2 is out of range for length 2.")]);
+}
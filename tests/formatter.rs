@@ -0,0 +1,54 @@
+use lox::Lox;
+
+const MESSY: &str = "var   x=1+2*3 ;   fun add(a,b){return a+b;}\nclass  Point < Base{ init(x,y){this.x=x;this.y=y;}   }\nif(x>1){print x,\"big\";}else print \"small\";\nfor(var i=0;i<3;i=i+1)print i;\nprint [1,2,3];\n";
+
+const CANONICAL: &str = "\
+var x = 1 + 2 * 3;
+
+fun add(a, b) {
+  return a + b;
+}
+
+class Point < Base {
+  init(x, y) {
+    this.x = x;
+    this.y = y;
+  }
+}
+
+if (x > 1) {
+  print x, \"big\";
+} else
+  print \"small\";
+for (var i = 0; i < 3; i = i + 1)
+  print i;
+print [1, 2, 3];
+";
+
+#[test]
+fn messy_source_comes_out_in_canonical_form() {
+    assert_eq!(Lox::format(MESSY).unwrap(), CANONICAL);
+}
+
+#[test]
+fn formatting_twice_changes_nothing() {
+    let once = Lox::format(MESSY).unwrap();
+    assert_eq!(Lox::format(&once).unwrap(), once);
+}
+
+#[test]
+fn formatting_keeps_constant_expressions_as_written() {
+    assert_eq!(Lox::format("print 2+3;").unwrap(), "print 2 + 3;\n");
+}
+
+#[test]
+fn source_that_doesnt_parse_cant_be_formatted() {
+    assert!(Lox::format("print 1 +;").is_err());
+}
+
+#[test]
+fn control_characters_in_strings_come_out_escaped() {
+    let formatted = Lox::format("print \"a\\u{7}b\\x1bc\\u{85}\";").unwrap();
+    assert_eq!(formatted, "print \"a\\u{7}b\\u{1b}c\\u{85}\";\n");
+    assert_eq!(Lox::run_capture(&formatted).output, "a\u{7}b\u{1b}c\u{85}\n");
+}
@@ -0,0 +1,314 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lox::{ Arity, Interpreter, Lox, Value };
+
+fn run(source: &str) -> (String, Vec<String>) {
+    let result = Lox::run_capture(source);
+    (result.output, result.errors)
+}
+
+// the value an expression evaluates to, for telling an Int from a Number where print and type() can't
+fn value(expr: &str) -> Value {
+    let kept = Rc::new(RefCell::new(Value::Nil));
+    let keep = Rc::clone(&kept);
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("keep", Arity::Fixed(1), move |_, arguments| {
+        *keep.borrow_mut() = arguments[0].clone();
+        Ok(Value::Nil)
+    });
+    interpreter.run(&Lox::compile(&format!("keep({});", expr)).unwrap()).unwrap();
+    kept.replace(Value::Nil)
+}
+
+#[test]
+fn type_names_every_kind_of_value() {
+    let source = "fun f() {}\nclass C {}\nprint type(1.5);\nprint type(1);\nprint type(\"s\");\nprint type(true);\nprint type(nil);\nprint type(f);\nprint type(type);\nprint type(C);\nprint type(C());";
+    let (output, errors) = run(source);
+    assert_eq!(output, "number\nnumber\nstring\nboolean\nnil\nfunction\nfunction\nclass\ninstance\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn type_takes_exactly_one_argument() {
+    let (_, errors) = run("type();");
+    assert_eq!(errors, vec![String::from("[line 1] Expected 1 arguments but got 0.")]);
+}
+
+#[test]
+fn a_passing_assert_is_silent() {
+    let (output, errors) = run("assert(1 < 2, \"math is broken\");\nassert(true);\nprint \"ok\";");
+    assert_eq!(output, "ok\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_failing_assert_raises_its_message() {
+    let (_, errors) = run("assert(1 > 2, \"one is not bigger\");");
+    assert_eq!(errors, vec![String::from("[line 1] Assertion failed: one is not bigger")]);
+    let (_, errors) = run("assert(nil);");
+    assert_eq!(errors, vec![String::from("[line 1] Assertion failed.")]);
+}
+
+#[test]
+fn a_failing_assert_can_be_caught() {
+    let (output, _) = run("try { assert(false, \"nope\"); } catch (error) { print error; }");
+    assert_eq!(output, "Assertion failed: nope\n");
+}
+
+// a path in the temp directory that no other test uses
+fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("lox-{}-{}", std::process::id(), name));
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[test]
+fn a_file_written_can_be_read_back() {
+    let path = temp_path("round-trip.txt");
+    let (output, errors) = run(&format!("writeFile(\"{0}\", \"line one\nline two\");\nprint readFile(\"{0}\");", path));
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(output, "line one\nline two\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn reading_a_missing_file_is_an_error() {
+    let path = temp_path("missing.txt");
+    let (_, errors) = run(&format!("readFile(\"{}\");", path));
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with(&format!("[line 1] Could not read file '{}':", path)), "{:?}", errors);
+}
+
+#[test]
+fn range_counts_up_to_the_end() {
+    let (output, errors) = run("print range(0, 3);\nprint range(0, 6, 2);\nprint range(3, 0, -1);\nprint range(3, 0);");
+    assert_eq!(output, "[0, 1, 2]\n[0, 2, 4]\n[3, 2, 1]\n[]\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_zero_range_step_is_an_error() {
+    let (_, errors) = run("print range(0, 3, 0);\nprint range(0, 1.5, 0);");
+    assert_eq!(errors, vec![String::from("[line 1] Range step can't be zero."), String::from("[line 2] Range step can't be zero.")]);
+}
+
+#[test]
+fn a_range_too_large_to_build_is_an_error() {
+    let (_, errors) = run("range(0, 1000000000000000.0);\nrange(0, 9223372036854775807);\nrange(0, 1, 0.0000000001);");
+    assert_eq!(errors, vec![
+        String::from("[line 1] Range is too large."),
+        String::from("[line 2] Range is too large."),
+        String::from("[line 3] Range is too large."),
+    ]);
+}
+
+#[test]
+fn a_range_ends_even_when_the_step_is_lost_in_rounding() {
+    let (output, errors) = run("print len(range(10000000000000000.0, 10000000000000010.0));\nprint range(0.5, 2);");
+    assert_eq!(output, "10\n[0.5, 1.5]\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn sqrt_and_pow_give_exact_results() {
+    let (output, errors) = run("print sqrt(9) == 3, pow(2, 10) == 1024;\nprint sqrt(2.25), pow(2, 0.5) == sqrt(2);");
+    assert_eq!(output, "true true\n1.5 true\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn math_domain_errors_give_nan_instead_of_failing() {
+    let (output, errors) = run("print sqrt(-1), log(-1), log(0);");
+    assert_eq!(output, "nan nan -inf\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn math_natives_reject_non_numbers() {
+    let (_, errors) = run("print sqrt(\"9\");\nprint pow(2, nil);");
+    assert_eq!(errors, vec![String::from("[line 1] Math arguments must be numbers."), String::from("[line 2] Math arguments must be numbers.")]);
+}
+
+#[test]
+fn min_max_and_clamp_pick_the_right_number() {
+    let (output, errors) = run("print max(3, 7, 2) == 7, min(3, 7, 2), min(4, 1.5), max(5);\nprint clamp(5, 0, 3) == 3, clamp(-1, 0, 3), clamp(2, 0, 3);");
+    assert_eq!(output, "true 2 1.5 5\ntrue 0 2\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn min_and_max_need_at_least_one_number() {
+    let (_, errors) = run("print max();\nprint min(1, \"a\");");
+    assert_eq!(errors, vec![String::from("[line 1] Expected at least 1 arguments but got 0."), String::from("[line 2] Math arguments must be numbers.")]);
+}
+
+#[test]
+fn clamp_rejects_bounds_the_wrong_way_round() {
+    let (_, errors) = run("print clamp(1, 3, 0);");
+    assert_eq!(errors, vec![String::from("[line 1] Clamp lower bound can't be greater than the upper bound.")]);
+}
+
+#[test]
+fn split_and_join_are_inverses() {
+    let (output, errors) = run("print split(\"a,b,c\", \",\");\nprint join([\"a\", \"b\"], \"-\");\nprint join(split(\"a,b,c\", \",\"), \",\") == \"a,b,c\";");
+    assert_eq!(output, "[a, b, c]\na-b\ntrue\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn splitting_on_an_empty_separator_gives_characters() {
+    let (output, _) = run("print split(\"abc\", \"\");");
+    assert_eq!(output, "[a, b, c]\n");
+}
+
+#[test]
+fn join_prints_elements_that_arent_strings() {
+    let (output, _) = run("print join([1, nil, true, \"x\"], \",\");");
+    assert_eq!(output, "1,nil,true,x\n");
+}
+
+#[test]
+fn join_shows_instances_through_str() {
+    let (output, errors) = run("class P { __str__() { return \"p\"; } }\nclass Q {}\nprint join([P(), P()], \",\");\nprint join([Q()], \",\");");
+    assert_eq!(output, "p,p\n<instance of Q>\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn split_and_join_check_their_arguments() {
+    let (_, errors) = run("print split(1, \",\");\nprint join(\"ab\", \",\");");
+    assert_eq!(errors, vec![String::from("[line 1] Text must be a string."), String::from("[line 2] Can only join a list.")]);
+}
+
+#[test]
+fn string_predicates_say_true_and_false() {
+    let (output, errors) = run("print contains(\"hello\", \"ell\"), contains(\"hello\", \"z\");\nprint startsWith(\"hello\", \"he\"), startsWith(\"hello\", \"lo\");\nprint endsWith(\"hello\", \"lo\"), endsWith(\"hello\", \"he\");");
+    assert_eq!(output, "true false\ntrue false\ntrue false\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn string_predicates_need_strings() {
+    let (_, errors) = run("print contains(1, \"a\");\nprint startsWith(\"a\", nil);\nprint endsWith([], \"a\");");
+    assert_eq!(errors, vec![
+        String::from("[line 1] Text must be a string."),
+        String::from("[line 2] Prefix must be a string."),
+        String::from("[line 3] Text must be a string."),
+    ]);
+}
+
+#[test]
+fn replace_changes_every_occurrence() {
+    let (output, errors) = run("print replace(\"a-b-c\", \"-\", \"+\");\nprint replace(\"aaa\", \"a\", \"bb\");\nprint replace(\"abc\", \"z\", \"y\");");
+    assert_eq!(output, "a+b+c\nbbbbbb\nabc\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn trim_strips_both_ends() {
+    let (output, _) = run("print \"[\" + trim(\"  hi there \\n\\t\") + \"]\";");
+    assert_eq!(output, "[hi there]\n");
+}
+
+#[test]
+fn replacing_an_empty_string_is_an_error() {
+    let (_, errors) = run("print replace(\"abc\", \"\", \"x\");");
+    assert_eq!(errors, vec![String::from("[line 1] Can't replace an empty string.")]);
+}
+
+#[test]
+fn ord_and_chr_convert_between_characters_and_code_points() {
+    let (output, errors) = run("print ord(\"A\") == 65, chr(65) == \"A\";\nprint ord(\"\u{e9}\"), chr(233), chr(ord(\"z\"));");
+    assert_eq!(output, "true true\n233 \u{e9} z\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn ord_needs_exactly_one_character() {
+    let (_, errors) = run("print ord(\"\");\nprint ord(\"ab\");");
+    assert_eq!(errors, vec![
+        String::from("[line 1] Can only take ord of a single character."),
+        String::from("[line 2] Can only take ord of a single character."),
+    ]);
+}
+
+#[test]
+fn chr_rejects_invalid_code_points() {
+    let (_, errors) = run("print chr(1114112);\nprint chr(55296);\nprint chr(-1);");
+    assert_eq!(errors, vec![
+        String::from("[line 1] Invalid code point."),
+        String::from("[line 2] Invalid code point."),
+        String::from("[line 3] Invalid code point."),
+    ]);
+}
+
+#[test]
+fn stats_counts_function_calls() {
+    // the second stats() call counts as well
+    let (output, errors) = run("fun f() { return 1; }\nvar before = stats().calls;\nf();\nf();\nprint stats().calls - before;");
+    assert_eq!(output, "3\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn stats_counts_evaluated_expressions() {
+    let (output, _) = run("var before = stats().exprs;\n1 + 2;\nprint stats().exprs > before;");
+    assert_eq!(output, "true\n");
+}
+
+#[test]
+fn the_same_seed_gives_the_same_sequence() {
+    let source = "seed(42);\nvar a = [random(), random(), randInt(1, 6)];\nseed(42);\nvar b = [random(), random(), randInt(1, 6)];\nprint a == b;\nseed(7);\nprint [random(), random(), randInt(1, 6)] == a;";
+    let (output, errors) = run(source);
+    assert_eq!(output, "true\nfalse\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn random_numbers_stay_in_range() {
+    let source = "var ok = true;\nfor (var i = 0; i < 1000; i++) {\n  var r = random();\n  var d = randInt(1, 6);\n  if (r < 0 or r >= 1 or d < 1 or d > 6) ok = false;\n}\nprint ok;";
+    let (output, _) = run(source);
+    assert_eq!(output, "true\n");
+    assert!(matches!(value("randInt(1, 6)"), Value::Int(1..=6)));
+}
+
+#[test]
+fn rand_int_and_seed_check_their_arguments() {
+    let (_, errors) = run("print randInt(6, 1);\nseed(\"x\");");
+    assert_eq!(errors, vec![
+        String::from("[line 1] randInt lower bound can't be greater than the upper bound."),
+        String::from("[line 2] Seed must be a number."),
+    ]);
+}
+
+#[test]
+fn clock_never_goes_backwards() {
+    let (output, errors) = run("var first = clock();\nvar n = 0;\nfor (var i = 0; i < 1000; i++) n += i;\nvar second = clock();\nprint second >= first, first >= 0;");
+    assert_eq!(output, "true true\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn time_is_a_plausible_unix_timestamp() {
+    // after 2020-01-01 and before 2100-01-01
+    let (output, _) = run("var now = time();\nprint now > 1577836800, now < 4102444800;");
+    assert_eq!(output, "true true\n");
+}
+
+#[test]
+fn parse_int_reads_digits_in_a_radix() {
+    let (output, errors) = run("print parseInt(\"ff\", 16) == 255, parseInt(\"101\", 2) == 5, parseInt(\"Z\", 36);\nprint parseInt(\"42\"), parseInt(\"-17\");");
+    assert_eq!(output, "true true 35\n42 -17\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(matches!(value("parseInt(\"1\")"), Value::Int(1)));
+}
+
+#[test]
+fn parse_int_rejects_bad_digits_and_radixes() {
+    let (_, errors) = run("print parseInt(\"12x\");\nprint parseInt(\"2\", 2);\nprint parseInt(\"1\", 37);");
+    assert_eq!(errors, vec![
+        String::from("[line 1] Can't parse '12x' as an int in base 10."),
+        String::from("[line 2] Can't parse '2' as an int in base 2."),
+        String::from("[line 3] Radix must be an int from 2 to 36."),
+    ]);
+}
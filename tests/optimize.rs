@@ -0,0 +1,89 @@
+use lox::{ ast_eq_ignoring_spans, fold_constants, fold_program, Expr, Lox, Stmt, TokenType, Value };
+
+fn is_int(expr: &Expr, expected: i64) -> bool {
+    matches!(expr, Expr::Literal { value: Value::Int(n) } if *n == expected)
+}
+
+#[test]
+fn literal_operands_fold_into_a_literal() {
+    assert!(is_int(&fold_constants(Expr::binary(Expr::int(2), TokenType::Plus, Expr::int(3))), 5));
+    let product = Expr::binary(
+        Expr::grouping(Expr::binary(Expr::int(1), TokenType::Plus, Expr::int(2))),
+        TokenType::Star,
+        Expr::unary(TokenType::Minus, Expr::int(4)),
+    );
+    assert!(is_int(&fold_constants(product), -12));
+}
+
+#[test]
+fn an_operand_that_is_a_variable_is_left_alone() {
+    let folded = fold_constants(Expr::binary(Expr::variable("x"), TokenType::Plus, Expr::int(1)));
+    match folded {
+        Expr::Binary { left, right, .. } => {
+            assert!(matches!(*left, Expr::Variable { .. }));
+            assert!(is_int(&right, 1));
+        }
+        _ => panic!("expected the addition to stay"),
+    }
+}
+
+#[test]
+fn only_the_constant_part_of_an_expression_folds() {
+    let product = Expr::binary(Expr::int(2), TokenType::Star, Expr::int(3));
+    let folded = fold_constants(Expr::binary(Expr::variable("x"), TokenType::Plus, product));
+    match folded {
+        Expr::Binary { right, .. } => assert!(is_int(&right, 6)),
+        _ => panic!("expected the addition to stay"),
+    }
+}
+
+#[test]
+fn dividing_by_zero_is_left_to_fail_at_run_time() {
+    let folded = fold_constants(Expr::binary(Expr::int(1), TokenType::Slash, Expr::int(0)));
+    assert!(matches!(folded, Expr::Binary { .. }));
+}
+
+#[test]
+fn a_literal_false_if_keeps_only_its_else_branch() {
+    let mut statements = vec![Stmt::if_else(
+        Expr::boolean(false),
+        Stmt::print(vec![Expr::int(1)]),
+        Some(Stmt::print(vec![Expr::int(2)])),
+    )];
+    fold_program(&mut statements);
+    match &statements[..] {
+        [Stmt::Print { expressions }] => assert!(is_int(&expressions[0], 2)),
+        _ => panic!("expected only the else branch to stay"),
+    }
+}
+
+#[test]
+fn a_literal_false_while_is_dropped() {
+    let mut statements = vec![Stmt::while_loop(Expr::boolean(false), Stmt::print(vec![Expr::int(1)]))];
+    fold_program(&mut statements);
+    assert!(!statements.iter().any(|stmt| matches!(stmt, Stmt::While { .. })));
+}
+
+#[test]
+fn a_condition_with_side_effects_keeps_both_branches() {
+    let mut statements = vec![
+        Stmt::if_else(Expr::call(Expr::variable("f"), Vec::new()), Stmt::print(vec![Expr::int(1)]), Some(Stmt::print(vec![Expr::int(2)]))),
+        Stmt::while_loop(Expr::call(Expr::variable("f"), Vec::new()), Stmt::print(vec![Expr::int(1)])),
+    ];
+    fold_program(&mut statements);
+    assert!(matches!(statements[0], Stmt::If { else_branch: Some(_), .. }), "{:?}", statements);
+    assert!(matches!(statements[1], Stmt::While { .. }), "{:?}", statements);
+}
+
+#[test]
+fn compiling_does_not_fold_unless_asked() {
+    let program = Lox::compile("print 2 + 3;").unwrap();
+    let expected = Stmt::print(vec![Expr::binary(Expr::int(2), TokenType::Plus, Expr::int(3))]);
+    assert!(ast_eq_ignoring_spans(&[program.statements[0].as_ref().clone()], &[expected]));
+}
+
+#[test]
+fn compile_optimized_folds() {
+    let program = Lox::compile_optimized("print 2 + 3;").unwrap();
+    assert!(ast_eq_ignoring_spans(&[program.statements[0].as_ref().clone()], &[Stmt::print(vec![Expr::int(5)])]));
+}
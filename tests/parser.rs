@@ -0,0 +1,230 @@
+use lox::{ ast_eq_ignoring_spans, Expr, Lox, Parser, RunOptions, Scanner, Stmt, Token, TokenType, Value };
+
+fn run(source: &str) -> (String, Vec<String>) {
+    let result = Lox::run_capture(source);
+    (result.output, result.errors)
+}
+
+#[test]
+fn a_parse_error_shows_the_line_with_a_caret_under_the_token() {
+    let (_, errors) = run("var a = 1;\nprint a +;\n");
+    assert_eq!(errors, vec![String::from("Error on line 2 at ';'. Expect expression.\n    print a +;\n             ^")]);
+}
+
+#[test]
+fn a_token_on_line_zero_gets_no_source_line() {
+    let tokens = vec![Token::new(TokenType::Print, String::from("print"), 0, 1), Token::new(TokenType::Semicolon, String::from(";"), 0, 6)];
+    let errors = Parser::new(tokens, "print ;").parse().expect_err("should not parse");
+    assert_eq!(errors[0].to_string(), "Error on line 0 at ';'. Expect expression.");
+}
+
+#[test]
+fn the_caret_lines_up_after_a_tab() {
+    let (_, errors) = run("\tprint 1 +;");
+    let lines: Vec<&str> = errors[0].lines().skip(1).collect();
+    let caret = lines[1].find('^').unwrap();
+    let semicolon = lines[0].find(';').unwrap();
+    assert_eq!(caret, semicolon, "{:?}", lines);
+}
+
+#[test]
+fn consume_errors_name_the_token_found() {
+    let (_, errors) = run("print (1 + 2;");
+    assert!(errors[0].starts_with("Error on line 1 at ';'. Expect ')' after expression, found ';'."), "{:?}", errors);
+    let (_, errors) = run("var 1 = 2;");
+    assert!(errors[0].starts_with("Error on line 1 at '1'. Expect variable name, found '1'."), "{:?}", errors);
+    let (_, errors) = run("while true {}");
+    assert!(errors[0].starts_with("Error on line 1 at 'true'. Expect '(' after 'while', found 'true'."), "{:?}", errors);
+}
+
+#[test]
+fn a_mismatched_closer_reports_what_was_found() {
+    let (_, errors) = run("print (1 + 2];");
+    assert!(errors[0].starts_with("Error on line 1 at ']'. Expect ')' after expression, found ']'."), "{:?}", errors);
+}
+
+#[test]
+fn running_out_of_tokens_reports_the_end_of_input() {
+    let (_, errors) = run("print (1 + 2");
+    assert!(errors[0].starts_with("Error on line 1 at end. Expect ')' after expression, found end of input."), "{:?}", errors);
+}
+
+// the default nesting limit is sized for an 8 MB stack, the size of a main thread, rather than a test thread's
+fn on_8_mb_stack<T: Send + 'static>(run: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::Builder::new().stack_size(8 * 1024 * 1024).spawn(run).unwrap().join().unwrap()
+}
+
+fn assert_too_deep(source: String) {
+    let (_, errors) = on_8_mb_stack(move || run(&source));
+    assert!(errors.iter().any(|error| error.contains("Nesting too deep.")), "{:?}", errors);
+}
+
+#[test]
+fn ten_thousand_open_parens_give_a_parse_error() {
+    assert_too_deep(format!("print {}1;", "(".repeat(10000)));
+}
+
+#[test]
+fn deeply_nested_statements_give_a_parse_error() {
+    assert_too_deep(format!("{}print 1;", "if (true) ".repeat(10000)));
+}
+
+#[test]
+fn deeply_nested_functions_give_a_parse_error() {
+    assert_too_deep(format!("{}{}", "fun f() {\n".repeat(20000), "}\n".repeat(20000)));
+}
+
+#[test]
+fn deeply_nested_blocks_give_a_parse_error() {
+    assert_too_deep(format!("{}print 1;{}", "{\n".repeat(20000), "}\n".repeat(20000)));
+}
+
+#[test]
+fn deeply_nested_block_expressions_give_a_parse_error() {
+    assert_too_deep(format!("{}1{};", "var a = {\n".repeat(20000), ";\na }\n".repeat(20000)));
+}
+
+#[test]
+fn deeply_nested_class_methods_give_a_parse_error() {
+    assert_too_deep(format!("{}{}", "class C { m() {\n".repeat(20000), "} }\n".repeat(20000)));
+}
+
+#[test]
+fn nesting_within_the_limit_still_runs() {
+    let (output, errors) = run(&format!("print {}1{};", "(".repeat(40), ")".repeat(40)));
+    assert_eq!(errors, Vec::<String>::new());
+    assert_eq!(output, "1\n");
+}
+
+#[test]
+fn ordinary_deep_nesting_parses_and_runs() {
+    let source = format!("{}print {}1{};{}", "if (true) { ".repeat(20), "(".repeat(40), ")".repeat(40), " }".repeat(20));
+    let (output, errors) = on_8_mb_stack(move || run(&source));
+    assert_eq!(output, "1\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn run_options_can_raise_the_nesting_limit() {
+    let source = format!("{}print {}1{};{}", "if (true) { ".repeat(60), "(".repeat(100), ")".repeat(100), " }".repeat(60));
+    let options = RunOptions { max_nesting: Some(1024), ..RunOptions::default() };
+    let (default, raised) = std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(move || (Lox::compile(&source).is_ok(), Lox::compile_with(&source, options).is_ok()))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert!(!default);
+    assert!(raised);
+}
+
+#[test]
+fn a_chained_comparison_suggests_and() {
+    let (_, errors) = run("print 1 < 2 < 3;");
+    assert!(errors[0].starts_with("Error on line 1 at '<'. Comparisons can't be chained. Use 'and' instead, as in 'a < b and b < c'."), "{:?}", errors);
+}
+
+#[test]
+fn comparisons_joined_by_and_still_parse() {
+    let (output, errors) = run("print 1 < 2 and 2 < 3;");
+    assert_eq!(errors, Vec::<String>::new());
+    assert_eq!(output, "true\n");
+}
+
+#[test]
+fn the_nesting_limit_is_configurable() {
+    let source = format!("print {}1{};", "(".repeat(10), ")".repeat(10));
+    let tokens = Scanner::new(&source).scan_tokens();
+    assert!(Parser::new(tokens, &source).parse().is_ok());
+
+    let tokens = Scanner::new(&source).scan_tokens();
+    match Parser::new(tokens, &source).with_max_nesting(8).parse() {
+        Err(errors) => assert_eq!(errors[0].diagnostic.message, "Nesting too deep."),
+        Ok(_) => panic!("should hit the lower limit"),
+    }
+}
+
+fn parse_expression(source: &str) -> Result<Expr, String> {
+    let tokens = Scanner::new(source).scan_tokens();
+    Parser::new(tokens, source).parse_expression().map_err(|error| error.diagnostic.message)
+}
+
+#[test]
+fn a_single_expression_parses_with_precedence() {
+    match parse_expression("1 + 2 * 3") {
+        Ok(Expr::Binary { left, operator, right }) => {
+            assert!(matches!(*left, Expr::Literal { value: Value::Int(1) }));
+            assert_eq!(operator.token_type, TokenType::Plus);
+            assert!(matches!(*right, Expr::Binary { .. }));
+        }
+        _ => panic!("expected an addition"),
+    }
+}
+
+#[test]
+fn tokens_after_the_expression_are_an_error() {
+    assert_eq!(parse_expression("1 + 2 3").err(), Some(String::from("Expect end of expression.")));
+    assert_eq!(parse_expression("1 + 2;").err(), Some(String::from("Expect end of expression.")));
+}
+
+fn parses(source: &str) -> bool {
+    let tokens = Scanner::new(source).scan_tokens();
+    Parser::new(tokens, source).parse().is_ok()
+}
+
+#[test]
+fn a_trailing_comma_is_allowed_in_calls_and_lists() {
+    assert!(parses("f(1, 2,);"));
+    assert!(parses("print [1, 2,];"));
+}
+
+#[test]
+fn an_empty_or_doubled_trailing_comma_is_rejected() {
+    assert!(!parses("f(,);"));
+    assert!(!parses("f(1,,);"));
+    assert!(!parses("print [,];"));
+    assert!(!parses("print [1,,];"));
+}
+
+#[test]
+fn an_empty_token_stream_parses_to_nothing() {
+    let statements = Parser::new(Vec::new(), "").parse().unwrap_or_else(|_| panic!("empty input should parse"));
+    assert!(statements.is_empty());
+}
+
+#[test]
+fn an_empty_token_stream_has_no_expression() {
+    let error = Parser::new(Vec::new(), "").parse_expression().expect_err("nothing to parse");
+    assert_eq!(error.diagnostic.message, "Expect expression.");
+}
+
+#[test]
+fn the_caret_still_lines_up_with_a_wider_tab() {
+    let source = "\tprint 1 +;";
+    let tokens = Scanner::new(source).with_tab_width(4).scan_tokens();
+    let errors = Parser::new(tokens, source).with_tab_width(4).parse().expect_err("should not parse");
+    assert_eq!(errors[0].diagnostic.column, Some(14));
+    let rendered = errors[0].to_string();
+    let lines: Vec<&str> = rendered.lines().skip(1).collect();
+    assert_eq!(lines[1].find('^'), lines[0].find(';'), "{:?}", lines);
+}
+
+fn parse(source: &str) -> Result<Vec<Stmt>, Vec<String>> {
+    let tokens = Scanner::new(source).scan_tokens();
+    Parser::new(tokens, source).parse().map_err(|errors| errors.iter().map(|error| error.diagnostic.message.clone()).collect())
+}
+
+#[test]
+fn a_parse_matches_a_hand_built_tree() {
+    let parsed = parse("1 + 2 * 3;").unwrap();
+    let product = Expr::binary(Expr::int(2), TokenType::Star, Expr::int(3));
+    let expected = vec![Stmt::expression(Expr::binary(Expr::int(1), TokenType::Plus, product))];
+    assert!(ast_eq_ignoring_spans(&parsed, &expected), "{:?}", parsed);
+}
+
+#[test]
+fn trees_compare_equal_wherever_their_source_sat() {
+    let spread = parse("\n\n  print   x\n  + 1;").unwrap();
+    assert!(ast_eq_ignoring_spans(&spread, &parse("print x + 1;").unwrap()));
+    assert!(!ast_eq_ignoring_spans(&spread, &parse("print x - 1;").unwrap()));
+}
@@ -0,0 +1,65 @@
+use std::cell::RefCell;
+use std::io::{ self, Write };
+use std::rc::Rc;
+
+use lox::{ Interpreter, Lox, Program, TextEdit };
+
+const BEFORE: &str = "fun a() {\n  return 1;\n}\nfun b() {\n  return 2;\n}\nfun c() {\n  return 3;\n}\nprint a() + b() + c();\n";
+const AFTER: &str = "fun a() {\n  return 1;\n}\nfun b() {\n  return 20;\n}\nfun c() {\n  return 3;\n}\nprint a() + b() + c();\n";
+
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// what running the program prints
+fn output(program: &Program) -> String {
+    let capture = Capture::default();
+    Interpreter::new().with_output(capture.clone()).run(program).unwrap();
+    let bytes = capture.0.borrow();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[test]
+fn editing_one_function_body_reuses_the_other_declarations() {
+    let old = Lox::compile(BEFORE).unwrap();
+    let edit = TextEdit { start_line: 5, old_end_line: 5, new_end_line: 5 };
+    let new = Lox::reparse(&old, &edit, AFTER).unwrap();
+
+    assert_eq!(new.statements.len(), 4);
+    assert!(Rc::ptr_eq(&old.statements[0], &new.statements[0]));
+    assert!(!Rc::ptr_eq(&old.statements[1], &new.statements[1]));
+    assert!(Rc::ptr_eq(&old.statements[2], &new.statements[2]));
+}
+
+#[test]
+fn a_reparsed_program_matches_compiling_from_scratch() {
+    let old = Lox::compile(BEFORE).unwrap();
+    let edit = TextEdit { start_line: 5, old_end_line: 5, new_end_line: 5 };
+    let new = Lox::reparse(&old, &edit, AFTER).unwrap();
+    let fresh = Lox::compile(AFTER).unwrap();
+
+    assert_eq!(new.spans, fresh.spans);
+    assert_eq!(output(&new), "24\n");
+}
+
+#[test]
+fn adding_lines_reparses_everything_after_the_edit() {
+    let old = Lox::compile(BEFORE).unwrap();
+    let source = BEFORE.replace("  return 2;\n", "  print 2;\n  return 2;\n");
+    let edit = TextEdit { start_line: 5, old_end_line: 4, new_end_line: 5 };
+    let new = Lox::reparse(&old, &edit, &source).unwrap();
+
+    assert!(Rc::ptr_eq(&old.statements[0], &new.statements[0]));
+    assert!(!Rc::ptr_eq(&old.statements[2], &new.statements[2]));
+    assert_eq!(new.spans, Lox::compile(&source).unwrap().spans);
+    assert_eq!(output(&new), "2\n6\n");
+}
@@ -0,0 +1,38 @@
+use std::io::Write;
+use std::process::{ Command, Stdio };
+
+// feeds the lines to the built binary's REPL, which skips the prompts when stdin isn't a terminal
+fn repl(lines: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox")).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(lines.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn a_function_split_across_three_lines_is_one_entry() {
+    assert_eq!(repl("fun three() {\n  return 3;\n}\nprint three();\n"), "3\n");
+}
+
+#[test]
+fn globals_carry_over_between_entries() {
+    assert_eq!(repl("var a = 4;\nvar b = a + 1;\nprint b;\n"), "5\n");
+}
+
+#[test]
+fn a_blank_line_abandons_an_unfinished_entry() {
+    let output = repl("fun broken() {\n\nprint 1;\n");
+    assert!(output.contains("Expect '}' after block, found end of input."), "{:?}", output);
+    assert!(output.ends_with("Parser error.\n1\n"), "{:?}", output);
+}
+
+#[test]
+fn the_end_of_input_ends_the_session() {
+    assert_eq!(repl("print 1;\n"), "1\n");
+    assert_eq!(repl(""), "");
+}
+
+#[test]
+fn an_unfinished_entry_at_the_end_of_input_is_dropped() {
+    assert_eq!(repl("fun broken() {\n  print 2;\n"), "");
+}
@@ -0,0 +1,275 @@
+use std::fs;
+use std::io::Cursor;
+use std::process::Command;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+use lox::{ tokens_to_json, Lox, ReaderScanner, ScanError, ScanErrorKind, Scanner, Token, TokenType };
+
+static SCRIPTS: AtomicUsize = AtomicUsize::new(0);
+
+// the JSON the built binary prints for a script under --dump-tokens=json
+fn dump(source: &str) -> String {
+    let name = format!("lox-scanner-{}-{}.lox", std::process::id(), SCRIPTS.fetch_add(1, Ordering::Relaxed));
+    let path = std::env::temp_dir().join(name);
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox")).arg("--dump-tokens=json").arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    String::from_utf8_lossy(&output.stdout).trim_end().to_string()
+}
+
+#[test]
+fn json_has_one_object_per_token() {
+    assert_eq!(dump("1 + 2"), concat!(
+        "[{ \"type\": \"NUMBER\", \"lexeme\": \"1\", \"line\": 1 }, ",
+        "{ \"type\": \"PLUS\", \"lexeme\": \"+\", \"line\": 1 }, ",
+        "{ \"type\": \"NUMBER\", \"lexeme\": \"2\", \"line\": 1 }]",
+    ));
+}
+
+#[test]
+fn json_escapes_string_lexemes() {
+    assert_eq!(dump("\"a\\\"b\""), "[{ \"type\": \"STRING\", \"lexeme\": \"\\\"a\\\\\\\"b\\\"\", \"line\": 1 }]");
+}
+
+// the line of each token in the JSON dump
+fn lines(source: &str) -> Vec<u32> {
+    dump(source).split("\"line\": ").skip(1)
+        .map(|rest| rest[..rest.find(' ').unwrap()].parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn each_kind_of_line_ending_counts_as_one_line() {
+    assert_eq!(lines("a\r\nb\rc\nd"), vec![1, 2, 3, 4]);
+    assert_eq!(lines("a\r\n\r\nb"), vec![1, 3]);
+}
+
+#[test]
+fn line_endings_inside_a_string_count_the_same_way() {
+    // a string token carries the line it ends on
+    assert_eq!(lines("\"one\r\ntwo\rthree\nfour\" after"), vec![4, 4]);
+}
+
+// what the program prints, which must run without errors
+fn printed(source: &str) -> String {
+    let result = Lox::run_capture(source);
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    result.output
+}
+
+#[test]
+fn hex_and_unicode_escapes_decode_to_characters() {
+    assert_eq!(printed("print \"\\x41\" == \"A\";"), "true\n");
+    assert_eq!(printed("print \"\\u{1F600}\";"), "\u{1F600}\n");
+    assert_eq!(printed("print \"\\u{e9}t\\u{e9}\";"), "\u{e9}t\u{e9}\n");
+}
+
+// the message of every problem --check finds, scan errors included
+fn problems(source: &str) -> Vec<String> {
+    Lox::check(source).into_iter().map(|diagnostic| diagnostic.message).collect()
+}
+
+#[test]
+fn a_malformed_escape_is_a_scan_error() {
+    for source in ["\"\\u{zzz}\";", "\"\\u{D800}\";", "\"\\x4\";"] {
+        assert_eq!(problems(source)[0], "Invalid escape sequence.", "{}", source);
+    }
+}
+
+#[test]
+fn a_raw_string_keeps_its_backslashes() {
+    assert_eq!(printed("print r\"C:\\path\\n\";"), "C:\\path\\n\n");
+    assert_eq!(printed("print r\"\\n\" == \"\\\\n\";"), "true\n");
+}
+
+#[test]
+fn a_raw_string_and_a_plain_one_differ_for_the_same_text() {
+    assert_eq!(printed("print r\"a\\tb\" == \"a\\tb\";"), "false\n");
+    assert_eq!(printed("print r\"plain\" == \"plain\";"), "true\n");
+}
+
+#[test]
+fn a_triple_quoted_string_keeps_its_newlines() {
+    assert_eq!(printed("print \"\"\"one\ntwo \"quoted\"\nthree\"\"\";"), "one\ntwo \"quoted\"\nthree\n");
+}
+
+#[test]
+fn the_token_after_a_triple_quoted_string_is_on_the_right_line() {
+    assert_eq!(lines("\"\"\"one\ntwo\nthree\"\"\"\nnext"), vec![3, 4]);
+}
+
+#[test]
+fn an_unterminated_triple_quoted_string_is_a_scan_error() {
+    assert!(problems("\"\"\"one\ntwo\"").contains(&String::from("Unterminated string.")));
+}
+
+#[test]
+fn bad_input_is_reported_where_it_is_and_skipped() {
+    let diagnostics = Lox::check("var a = 1;\nvar b = @2;\nprint \"open");
+    let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+    assert_eq!(messages, vec![
+        "Error on line 2 at '@'. Unexpected character.",
+        "Error on line 3 at '\"'. Unterminated string.",
+        "Error on line 3 at end. Expect expression.",
+    ]);
+    assert_eq!(diagnostics[0].column, Some(9));
+}
+
+#[test]
+fn token_types_display_as_written_in_source() {
+    assert_eq!(TokenType::RightParen.to_string(), ")");
+    assert_eq!(TokenType::Plus.to_string(), "+");
+    assert_eq!(TokenType::GreaterEqual.to_string(), ">=");
+    assert_eq!(TokenType::While.to_string(), "while");
+}
+
+#[test]
+fn token_types_without_fixed_text_display_by_kind() {
+    assert_eq!(TokenType::Identifier.to_string(), "identifier");
+    assert_eq!(TokenType::StringLiteral.to_string(), "string");
+    assert_eq!(TokenType::NumberLiteral.to_string(), "number");
+    assert_eq!(TokenType::EoF.to_string(), "end of input");
+}
+
+fn types(source: &str) -> Vec<TokenType> {
+    Scanner::new(source).scan_tokens().iter().map(|token| token.token_type).collect()
+}
+
+#[test]
+fn arrows_scan_as_single_tokens() {
+    assert_eq!(types("=>"), vec![TokenType::FatArrow, TokenType::EoF]);
+    assert_eq!(types("->"), vec![TokenType::Arrow, TokenType::EoF]);
+}
+
+#[test]
+fn arrow_characters_apart_stay_separate_tokens() {
+    assert_eq!(types("= >"), vec![TokenType::Equal, TokenType::Greater, TokenType::EoF]);
+    assert_eq!(types("- >"), vec![TokenType::Minus, TokenType::Greater, TokenType::EoF]);
+    assert_eq!(types("== >= -= ="), vec![TokenType::EqualEqual, TokenType::GreaterEqual, TokenType::MinusEqual, TokenType::Equal, TokenType::EoF]);
+}
+
+#[test]
+fn bad_input_is_collected_as_scan_errors() {
+    let mut scanner = Scanner::new("var a = 1;\nvar b = @;\nprint \"open");
+    let tokens = scanner.scan_tokens();
+    let errors = scanner.into_errors();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+
+    assert_eq!(errors[0].kind, ScanErrorKind::UnexpectedCharacter);
+    assert_eq!((errors[0].line, errors[0].column, errors[0].lexeme.as_str()), (2, 9, "@"));
+    assert_eq!(errors[1].kind, ScanErrorKind::UnterminatedString);
+    assert_eq!(errors[1].kind.message(), "Unterminated string.");
+
+    // the bad characters are skipped, so every token that comes out is a real one
+    assert!(tokens.iter().all(|token| token.lexeme != "@"));
+    assert_eq!(tokens.last().map(|token| token.token_type), Some(TokenType::EoF));
+}
+
+#[test]
+fn the_json_dump_is_available_without_the_binary() {
+    let tokens = Scanner::new("1 + 2").scan_tokens();
+    assert_eq!(tokens_to_json(&tokens), dump("1 + 2"));
+}
+
+#[test]
+fn empty_source_scans_to_just_the_end() {
+    assert_eq!(types(""), vec![TokenType::EoF]);
+    assert!(Scanner::new("").is_at_end());
+}
+
+#[test]
+fn whitespace_and_comments_scan_to_just_the_end() {
+    assert_eq!(types("  \t\r\n\n   "), vec![TokenType::EoF]);
+    assert_eq!(types("// only a comment"), vec![TokenType::EoF]);
+    assert_eq!(types("\n  // one\n// two\n"), vec![TokenType::EoF]);
+}
+
+fn lexemes(source: &str) -> Vec<String> {
+    Scanner::new(source).scan_tokens().iter().map(|token| token.lexeme.clone()).collect()
+}
+
+#[test]
+fn a_dot_followed_by_digits_is_part_of_the_number() {
+    assert_eq!(types("3.14"), vec![TokenType::NumberLiteral, TokenType::EoF]);
+    assert_eq!(lexemes("3.14")[0], "3.14");
+}
+
+#[test]
+fn a_dot_without_digits_after_it_is_its_own_token() {
+    assert_eq!(types("3."), vec![TokenType::NumberLiteral, TokenType::Dot, TokenType::EoF]);
+    assert_eq!(types("3.foo"), vec![TokenType::NumberLiteral, TokenType::Dot, TokenType::Identifier, TokenType::EoF]);
+    assert_eq!(&lexemes("3.foo")[..3], ["3", ".", "foo"]);
+}
+
+#[test]
+fn a_second_dot_starts_a_new_token() {
+    assert_eq!(types("1.2.3"), vec![TokenType::NumberLiteral, TokenType::Dot, TokenType::NumberLiteral, TokenType::EoF]);
+    assert_eq!(&lexemes("1.2.3")[..3], ["1.2", ".", "3"]);
+}
+
+fn columns(mut scanner: Scanner) -> Vec<u32> {
+    scanner.scan_tokens().iter().map(|token| token.column).collect()
+}
+
+#[test]
+fn a_tab_counts_as_one_column_by_default() {
+    assert_eq!(columns(Scanner::new("\tx"))[0], 2);
+    assert_eq!(columns(Scanner::new("\tx").with_tab_width(1))[0], 2);
+}
+
+#[test]
+fn a_tab_counts_as_the_configured_number_of_columns() {
+    assert_eq!(columns(Scanner::new("\tx").with_tab_width(4))[0], 5);
+    assert_eq!(columns(Scanner::new("ab\tx").with_tab_width(4))[..2], [1, 7]);
+    assert_eq!(columns(Scanner::new("\t\tx").with_tab_width(8))[0], 17);
+}
+
+const PROGRAM: &str = "var greeting = \"hello\nworld\";\nfun f(a) {\n  return a * 2;\n}\nprint f(21); // done\n";
+
+// everything a token carries, for comparing two scans
+fn described(tokens: &[Token]) -> Vec<(TokenType, String, u32, u32)> {
+    tokens.iter().map(|token| (token.token_type, token.lexeme.clone(), token.line, token.column)).collect()
+}
+
+#[test]
+fn scanning_from_a_reader_gives_the_same_tokens_as_a_str() {
+    let from_str = Scanner::new(PROGRAM).scan_tokens();
+    let from_reader = ReaderScanner::new(Cursor::new(PROGRAM)).scan_tokens().unwrap();
+    assert_eq!(described(&from_reader), described(&from_str));
+}
+
+#[test]
+fn a_reader_resumes_strings_that_span_lines() {
+    let source = "print \"a\nb\" + \"c\nd\" + r\"e\\\nf\";\nvar s = \"\"\"\n\"g\"\n\"\"\"; var t = b\"h\ni\";\n\tprint \"j\n";
+    let mut from_str = Scanner::new(source);
+    let tokens = from_str.scan_tokens();
+    let mut from_reader = ReaderScanner::new(Cursor::new(source));
+    assert_eq!(described(&from_reader.scan_tokens().unwrap()), described(&tokens));
+    let lines = |errors: Vec<ScanError>| errors.iter().map(|error| (error.kind, error.line, error.column)).collect::<Vec<_>>();
+    assert_eq!(lines(from_reader.into_errors()), lines(from_str.into_errors()));
+}
+
+// a literal is looked at once when it closes, not again for every line it runs over
+#[test]
+fn a_reader_scans_a_long_multi_line_string_once() {
+    let source = format!("print \"{}\";\nprint 1;\n", "a line of the string\n".repeat(100_000));
+    let tokens = ReaderScanner::new(Cursor::new(source.as_str())).scan_tokens().unwrap();
+    assert_eq!(described(&tokens), described(&Scanner::new(&source).scan_tokens()));
+    assert_eq!(tokens[1].lexeme.lines().count(), 100_001);
+}
+
+#[test]
+fn a_reader_hands_out_tokens_a_line_at_a_time() {
+    let mut scanner = ReaderScanner::new(Cursor::new("print 1;\nprint 2;\n"));
+    assert_eq!(scanner.next_tokens().unwrap().map(|tokens| tokens.len()), Some(3));
+    assert_eq!(scanner.next_tokens().unwrap().map(|tokens| tokens.len()), Some(3));
+    assert!(scanner.next_tokens().unwrap().is_none());
+}
+
+#[test]
+fn a_reader_keeps_scan_errors_in_order() {
+    let mut scanner = ReaderScanner::new(Cursor::new("var a = @;\nvar b = #;\n"));
+    scanner.scan_tokens().unwrap();
+    let lines: Vec<u32> = scanner.into_errors().iter().map(|error| error.line).collect();
+    assert_eq!(lines, vec![1, 2]);
+}
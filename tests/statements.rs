@@ -0,0 +1,273 @@
+use std::io;
+use std::time::Instant;
+
+use lox::{ Interpreter, Lox };
+
+fn run(source: &str) -> (String, Vec<String>) {
+    let result = Lox::run_capture(source);
+    (result.output, result.errors)
+}
+
+#[test]
+fn if_while_and_for_pick_and_repeat_statements() {
+    let (output, errors) = run("for (var i = 0; i < 3; i = i + 1) { if (i == 1) print \"one\"; else print i; }\nvar n = 0;\nwhile (n < 2) n = n + 1;\nprint n;");
+    assert_eq!(output, "0\none\n2\n2\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_closure_keeps_its_own_variables() {
+    let source = "fun counter() {\n  var i = 0;\n  fun count() { i = i + 1; return i; }\n  return count;\n}\nvar a = counter();\nvar b = counter();\na();\nprint a();\nprint b();";
+    let (output, errors) = run(source);
+    assert_eq!(output, "2\n1\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn functions_can_recurse() {
+    let (output, errors) = run("fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\nprint fib(10);");
+    assert_eq!(output, "55\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn calling_with_the_wrong_number_of_arguments_is_an_error() {
+    let (_, errors) = run("fun f(a, b) {}\nf(1);");
+    assert_eq!(errors, vec![String::from("[line 2] Expected 2 arguments but got 1.")]);
+}
+
+#[test]
+fn shadowing_and_reassignment_work_with_interned_names() {
+    let source = "var a = \"global\";\n{\n  var a = \"outer\";\n  {\n    var a = \"inner\";\n    print a;\n    a = \"inner again\";\n    print a;\n  }\n  print a;\n  a = \"outer again\";\n}\nprint a;\na = \"global again\";\nprint a;";
+    let (output, errors) = run(source);
+    assert_eq!(output, "inner\ninner again\nouter\nglobal\nglobal again\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+// a timing rather than a check, so it only runs when asked for:
+// cargo test --release --test statements -- --ignored --nocapture
+#[test]
+#[ignore]
+fn benchmark_variable_lookups() {
+    let source = "var a = 1;\nvar b = 2;\nvar total = 0;\nfun add(x) { return x + a; }\nfor (var i = 0; i < 300000; i = i + 1) {\n  {\n    var c = i;\n    {\n      total = total + add(c) + b;\n    }\n  }\n}\nprint total;";
+    let program = Lox::compile(source).unwrap();
+    let mut interpreter = Interpreter::new().with_output(io::sink());
+    let started = Instant::now();
+    interpreter.run(&program).unwrap();
+    let stats = interpreter.stats();
+    eprintln!("{} expressions and {} calls in {:?}", stats.exprs, stats.calls, started.elapsed());
+}
+
+#[test]
+fn names_that_differ_only_slightly_are_different_variables() {
+    let (output, errors) = run("var ab = 1;\nvar ba = 2;\nvar abc = 3;\nvar Ab = 4;\nprint ab;\nprint ba;\nprint abc;\nprint Ab;");
+    assert_eq!(output, "1\n2\n3\n4\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_loop_body_runs_a_thousand_times_from_one_tree() {
+    let source = "var ticks = 0;\nfun tick() { ticks = ticks + 1; }\nvar i = 0;\nwhile (i < 1000) { tick(); i = i + 1; }\nprint ticks;";
+    let (output, errors) = run(source);
+    assert_eq!(output, "1000\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn switch_runs_the_matching_case() {
+    let (output, errors) = run("switch (2) { case 1: print \"one\"; case 2: print \"two\"; default: print \"other\"; }");
+    assert_eq!(output, "two\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn switch_falls_back_to_the_default() {
+    let (output, errors) = run("switch (\"z\") { case \"a\": print \"a\"; default: print \"default\"; }");
+    assert_eq!(output, "default\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn switch_cases_dont_fall_through() {
+    let (output, errors) = run("switch (1) { case 1: print \"one\"; print \"still one\"; case 2: print \"two\"; default: print \"other\"; }");
+    assert_eq!(output, "one\nstill one\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn switch_without_a_match_or_default_does_nothing() {
+    let (output, errors) = run("switch (3) { case 1: print 1; }\nprint \"after\";");
+    assert_eq!(output, "after\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn do_while_runs_its_body_once_when_the_condition_starts_false() {
+    let (output, errors) = run("var n = 0;\ndo { print \"body\"; n = n + 1; } while (false);\nprint n;");
+    assert_eq!(output, "body\n1\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn do_while_repeats_until_the_condition_fails() {
+    let (output, errors) = run("var n = 0;\ndo n = n + 1; while (n < 5);\nprint n;");
+    assert_eq!(output, "5\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn a_constant_can_be_read() {
+    let (output, errors) = run("const PI = 3.14;\nprint PI * 2;");
+    assert_eq!(output, "6.28\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn reassigning_a_constant_is_a_runtime_error() {
+    let (output, errors) = run("const PI = 3.14;\nPI = 3;\nprint PI;");
+    assert_eq!(output, "3.14\n");
+    assert_eq!(errors, vec![String::from("[line 2] Cannot assign to constant 'PI'.")]);
+}
+
+#[test]
+fn a_constant_in_a_block_guards_only_its_own_scope() {
+    let (output, errors) = run("var x = 1;\n{\n  const x = 2;\n  print x;\n}\nx = 3;\nprint x;");
+    assert_eq!(output, "2\n3\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn match_runs_the_arm_for_a_literal() {
+    let (output, errors) = run("match 2 { 1 => print \"one\"; 2 => print \"two\"; _ => print \"other\"; }");
+    assert_eq!(output, "two\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn match_falls_through_to_the_wildcard() {
+    let (output, _) = run("match \"z\" { \"a\" => print \"a\"; _ => print \"other\"; }");
+    assert_eq!(output, "other\n");
+}
+
+#[test]
+fn match_without_a_hit_or_wildcard_is_a_runtime_error() {
+    let (output, errors) = run("match 3 { 1 => print 1; }\nprint \"after\";");
+    assert_eq!(output, "after\n");
+    assert_eq!(errors, vec![String::from("[line 1] No match arm for 3.")]);
+}
+
+#[test]
+fn match_can_start_a_block_expression() {
+    let (output, errors) = run("var x = { match 2 { 2 => print \"two\"; } 5 };\nprint x;");
+    assert_eq!(output, "two\n5\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn print_takes_one_two_or_three_values() {
+    let (output, errors) = run("print 1;\nprint 1, \"two\";\nprint 1, \"two\", nil;");
+    assert_eq!(output, "1\n1 two\n1 two nil\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn for_in_walks_a_list() {
+    let (output, errors) = run("var total = 0;\nfor (n in [1, 2, 3]) total += n;\nprint total;");
+    assert_eq!(output, "6\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn for_in_walks_the_characters_of_a_string() {
+    let (output, _) = run("for (c in \"abc\") print c;");
+    assert_eq!(output, "a\nb\nc\n");
+}
+
+#[test]
+fn for_in_over_a_number_is_a_runtime_error() {
+    let (_, errors) = run("for (x in 3) print x;");
+    assert_eq!(errors, vec![String::from("[line 1] Can only iterate over lists and strings.")]);
+}
+
+#[test]
+fn unless_runs_its_body_when_the_condition_is_falsey() {
+    let (output, _) = run("unless (false) print 1;\nunless (true) print 2;");
+    assert_eq!(output, "1\n");
+}
+
+#[test]
+fn unless_runs_its_else_when_the_condition_holds() {
+    let (output, _) = run("unless (1 < 2) print \"body\"; else print \"else\";");
+    assert_eq!(output, "else\n");
+}
+
+#[test]
+fn break_with_a_label_leaves_both_loops() {
+    let source = "var i = 0;\nouter: while (i < 3) {\n  var j = 0;\n  while (j < 3) {\n    if (j == 1) break outer;\n    print i, j;\n    j++;\n  }\n  i++;\n}\nprint \"done\", i;";
+    let (output, errors) = run(source);
+    assert_eq!(output, "0 0\ndone 0\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn continue_with_a_label_skips_to_the_outer_loop() {
+    let (output, _) = run("outer: for (var i = 0; i < 3; i++) {\n  for (var j = 0; j < 3; j++) {\n    if (j == 1) continue outer;\n    print i, j;\n  }\n}");
+    assert_eq!(output, "0 0\n1 0\n2 0\n");
+}
+
+#[test]
+fn breaking_to_an_unknown_label_is_a_parse_error() {
+    let (output, errors) = run("while (true) { break nowhere; }");
+    assert_eq!(output, "");
+    assert!(errors[0].contains("Undefined label 'nowhere'."), "{:?}", errors);
+}
+
+#[test]
+fn catch_binds_the_message_of_a_runtime_error() {
+    let (output, errors) = run("try {\n  print 1 ~/ 0;\n  print \"not reached\";\n} catch (e) {\n  print \"caught: \" + e;\n}\nprint \"after\";");
+    assert_eq!(output, "caught: Division by zero.\nafter\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn an_error_outside_try_still_reaches_the_top_level() {
+    let (output, errors) = run("try { print 1; } catch (e) { print e; }\nprint 1 ~/ 0;");
+    assert_eq!(output, "1\n");
+    assert_eq!(errors, vec![String::from("[line 2] Division by zero.")]);
+}
+
+#[test]
+fn a_thrown_value_is_caught_as_is() {
+    let (output, errors) = run("try { throw \"oops\"; } catch (e) { print e; }\ntry { throw 42; } catch (e) { print e + 1; }");
+    assert_eq!(output, "oops\n43\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn an_uncaught_throw_reaches_the_top_level() {
+    let (output, errors) = run("print 1;\nthrow \"up\";\nprint 2;");
+    assert_eq!(output, "1\n2\n");
+    assert_eq!(errors, vec![String::from("[line 2] up")]);
+}
+
+#[test]
+fn every_argument_can_be_given_by_name() {
+    let (output, errors) = run("fun greet(greeting, name) { return greeting + \", \" + name; }\nprint greet(name: \"Sam\", greeting: \"Hi\");");
+    assert_eq!(output, "Hi, Sam\n");
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn named_arguments_can_follow_positional_ones() {
+    let (output, _) = run("fun f(a, b, c) { print a, b, c; }\nf(1, c: 3, b: 2);");
+    assert_eq!(output, "1 2 3\n");
+}
+
+#[test]
+fn an_unknown_or_repeated_name_is_a_runtime_error() {
+    let (_, errors) = run("fun greet(greeting, name) {}\ngreet(nom: \"Sam\", greeting: \"Hi\");\ngreet(\"Hi\", name: \"a\", name: \"b\");");
+    assert_eq!(errors, vec![
+        String::from("[line 2] Unknown parameter 'nom'."),
+        String::from("[line 3] Parameter 'name' was given more than once."),
+    ]);
+}
@@ -0,0 +1,102 @@
+use lox::{ Lox, RunResult };
+
+// runs the program both ways and insists on the same output, errors and exit code
+fn same_on_both(source: &str) -> RunResult {
+    let interpreted = Lox::run_capture(source);
+    let compiled = Lox::run_vm_capture(source);
+    assert_eq!(compiled.output, interpreted.output, "output differs for {:?}", source);
+    assert_eq!(compiled.errors, interpreted.errors, "errors differ for {:?}", source);
+    assert_eq!(compiled.exit_code, interpreted.exit_code, "exit code differs for {:?}", source);
+    interpreted
+}
+
+#[test]
+fn arithmetic_matches_the_interpreter() {
+    let output = same_on_both("print 1 + 2 * 3, 7 / 2, 7 ~/ 2, -7 ~/ 2, 7 % 3, -7 % 3;\nprint 1 + 0.5, 2 * 1.5, 10 - 2.5, -(3), 0.1 + 0.2;\nprint 1 == 1.0, 1 < 2, 2 <= 1.5, \"a\" + \"b\", \"ab\" * 3;").output;
+    assert_eq!(output, "7 3.5 3 -4 1 -1\n1.5 3 7.5 -3 0.30000000000000004\ntrue true false ab ababab\n");
+}
+
+#[test]
+fn control_flow_matches_the_interpreter() {
+    let source = "var total = 0;\nfor (var i = 0; i < 10; i = i + 1) total = total + i;\nprint total;\nvar n = 0;\nwhile (n < 3) n = n + 1;\nprint n;\nif (n == 3) print \"three\"; else print \"other\";\nprint nil or \"x\";\nprint false and 1;\nprint nil ?? 5;";
+    assert_eq!(same_on_both(source).output, "45\n3\nthree\nx\nfalse\n5\n");
+}
+
+#[test]
+fn functions_match_the_interpreter() {
+    let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\nprint fib(15);\nfun greet(name) { return \"hi \" + name; }\nprint greet(\"lox\");\nfun nothing() {}\nprint nothing();";
+    assert_eq!(same_on_both(source).output, "610\nhi lox\nnil\n");
+}
+
+#[test]
+fn natives_are_available_on_the_vm() {
+    let output = same_on_both("print type(1), type(1.5), type(\"s\"), type(type), sqrt(16), max(1, 5, 3);\nassert(true);").output;
+    assert_eq!(output, "number number string function 4 5\n");
+}
+
+#[test]
+fn a_native_can_be_reassigned_on_the_vm() {
+    assert_eq!(same_on_both("clock = 1; print \"ok\";").output, "ok\n");
+    assert_eq!(same_on_both("sqrt = 2; print sqrt;").output, "2\n");
+}
+
+#[test]
+fn runtime_errors_match_the_interpreter() {
+    assert_eq!(same_on_both("print undefinedThing;").errors, vec![String::from("[line 1] Undefined variable 'undefinedThing'.")]);
+    same_on_both("print 1 + \"a\";");
+    same_on_both("print -\"a\";");
+    same_on_both("fun f(a) {}\nf(1, 2);");
+    same_on_both("type(1, 2);");
+    same_on_both("print max();");
+    same_on_both("assert(false, \"no\");");
+}
+
+#[test]
+fn truthiness_matches_the_interpreter() {
+    let source = "if (0) print \"zero\";\nif (\"\") print \"empty\";\nif (nil) print \"nil\"; else print \"not nil\";\nprint !false;";
+    assert_eq!(same_on_both(source).output, "zero\nempty\nnot nil\ntrue\n");
+}
+
+#[test]
+fn numbers_print_the_same_on_both() {
+    let output = same_on_both("print 0.1 + 0.2;\nprint 1000000000.0 * 1000000000000;\nprint 1.0 / 100000000;\nprint -1.0 / 0;").output;
+    assert_eq!(output, "0.30000000000000004\n1e21\n1e-8\n-inf\n");
+}
+
+#[test]
+fn ints_follow_the_same_overflow_rules() {
+    let result = same_on_both("var big = 9223372036854775807;\nprint big;\nprint big + 1;\nprint \"after\";");
+    assert_eq!(result.output, "9223372036854775807\nafter\n");
+    assert_eq!(result.errors, vec![String::from("[line 3] Integer overflow.")]);
+    assert_eq!(same_on_both("print -(-9223372036854775807 - 1);").errors, vec![String::from("[line 1] Integer overflow.")]);
+    assert_eq!(same_on_both("var i = 9223372036854775807;\ni++;").errors, vec![String::from("[line 2] Integer overflow.")]);
+    assert_eq!(same_on_both("print 9223372036854775807 + 1.0;").output, "9223372036854776000\n");
+}
+
+#[test]
+fn dividing_an_int_by_zero_fails_on_both() {
+    assert_eq!(same_on_both("print 1 ~/ 0;").errors, vec![String::from("[line 1] Division by zero.")]);
+    assert_eq!(same_on_both("print 1 % 0;").errors, vec![String::from("[line 1] Division by zero.")]);
+    let output = same_on_both("print 1 / 0, 1.0 ~/ 0;").output;
+    assert_eq!(output, "inf inf\n");
+}
+
+#[test]
+fn repeating_a_string_too_far_fails_on_both() {
+    assert_eq!(same_on_both("print \"ab\" * 1000000000000;").errors, vec![String::from("[line 1] Repetition result too large.")]);
+}
+
+#[test]
+fn exit_stops_the_vm_with_its_code() {
+    let result = same_on_both("print 1;\nexit(3);\nprint 2;");
+    assert_eq!(result.output, "1\n");
+    assert_eq!(result.exit_code, Some(3));
+}
+
+#[test]
+fn a_byte_string_stops_the_vm_before_anything_runs() {
+    let result = Lox::run_vm_capture("print 1;\nprint b\"ab\";");
+    assert_eq!(result.output, "");
+    assert_eq!(result.errors.len(), 1);
+    assert!(result.errors[0].ends_with("Byte strings are not supported by the VM."), "{:?}", result.errors);
+}